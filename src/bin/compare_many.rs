@@ -0,0 +1,10 @@
+use std::process::ExitCode;
+
+/// Main entry point for the `compare_many` command-line tool.
+///
+/// This tool compares any number of CSV files and writes a presence matrix showing
+/// which files contain each row.
+/// It is an alias of `rust_csv_scripts many` and accepts the same arguments.
+fn main() -> ExitCode {
+    rust_csv_scripts::cli::run_alias("many")
+}
@@ -0,0 +1,10 @@
+use std::process::ExitCode;
+
+/// Main entry point for the `merge_csv` command-line tool.
+///
+/// This tool merges two edited copies of a common base CSV file, writing the
+/// merged rows and any conflicts.
+/// It is an alias of `rust_csv_scripts merge` and accepts the same arguments.
+fn main() -> ExitCode {
+    rust_csv_scripts::cli::run_alias("merge")
+}
@@ -0,0 +1,9 @@
+use std::process::ExitCode;
+
+/// Main entry point for the `run_jobs` command-line tool.
+///
+/// This tool runs every comparison described in a TOML or YAML job manifest.
+/// It is an alias of `rust_csv_scripts jobs` and accepts the same arguments.
+fn main() -> ExitCode {
+    rust_csv_scripts::cli::run_alias("jobs")
+}
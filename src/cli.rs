@@ -0,0 +1,666 @@
+use std::collections::{BTreeMap, HashSet};
+use std::ffi::OsString;
+use std::path::Path;
+use std::process::ExitCode;
+use clap::{ArgAction, Args, Parser, Subcommand, ValueEnum};
+use serde::Serialize;
+use crate::config::resolve_profile;
+use crate::csv_compare::{compare_with_options, output_path};
+use crate::dialect::Dialect;
+use crate::dir_compare::{compare_dirs, DirCompareOptions};
+use crate::encoding::{detect_encoding_with, validate_utf8, DetectionMode, Utf8Validation, DEFAULT_SAMPLE_BYTES};
+use crate::jobs::{load_manifest, run_jobs, write_job_report};
+use crate::merge::{merge_three_way, MergeOptions};
+use crate::nulls::{NullTokens, STANDARD_NULL_TOKENS};
+use crate::nway::{compare_many, NWayOptions};
+use crate::ragged::RaggedPolicy;
+use crate::spec::{split_list, CompareSpec};
+
+/// Command-line interface shared by the `rust_csv_scripts` binary and the tool aliases.
+#[derive(Debug, Parser)]
+#[command(name = "rust_csv_scripts", version, about = "Utility for comparing CSV files and checking encoding")]
+pub struct Cli {
+    #[command(flatten)]
+    pub global: GlobalArgs,
+
+    /// Launch the GUI (kept for compatibility, use the `gui` subcommand instead)
+    #[arg(short = 'g', long = "use_gui", hide = true, num_args = 0..=1)]
+    pub use_gui: Option<Option<String>>,
+
+    #[command(subcommand)]
+    pub command: Option<Commands>,
+}
+
+/// Options accepted by every subcommand.
+#[derive(Debug, Clone, Args)]
+pub struct GlobalArgs {
+    /// Field delimiter of the CSV files: comma, semicolon, tab, pipe or a single character
+    #[arg(long, global = true)]
+    pub delimiter: Option<String>,
+
+    /// Quote character of the CSV files
+    #[arg(long, global = true)]
+    pub quote: Option<char>,
+
+    /// Escape character of the CSV files, if quotes are escaped with it instead of doubled
+    #[arg(long, global = true)]
+    pub escape: Option<char>,
+
+    /// Trim whitespace around fields when reading
+    #[arg(long, global = true, overrides_with = "no_trim")]
+    pub trim: bool,
+
+    /// Keep whitespace around fields even if a profile enables --trim
+    #[arg(long = "no-trim", global = true, overrides_with = "trim")]
+    pub no_trim: bool,
+
+    /// Format of the results printed to standard output
+    #[arg(long, value_enum, global = true, default_value_t = OutputFormat::Text)]
+    pub format: OutputFormat,
+
+    /// Print more details
+    #[arg(short, long, action = ArgAction::Count, global = true)]
+    pub verbose: u8,
+
+    /// Only print errors
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    pub quiet: bool,
+}
+
+impl GlobalArgs {
+    /// Returns the dialect options as a partial spec, so they layer like any other option.
+    fn dialect_spec(&self) -> CompareSpec {
+        CompareSpec {
+            delimiter: self.delimiter.clone(),
+            quote: self.quote,
+            escape: self.escape,
+            trim: if self.trim {
+                Some(true)
+            } else if self.no_trim {
+                Some(false)
+            } else {
+                None
+            },
+            ..CompareSpec::default()
+        }
+    }
+}
+
+/// Format of the results printed to standard output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    Text,
+    Json,
+}
+
+/// The available tools.
+#[derive(Debug, Subcommand)]
+pub enum Commands {
+    /// Compare two CSV files (or two directories) and write the unique rows of the first
+    Compare(Box<CompareArgs>),
+    /// Detect the encoding of a file
+    Encoding(EncodingArgs),
+    /// Three-way merge of CSV files sharing a common base
+    Merge(MergeArgs),
+    /// Compare any number of CSV files and report which files contain each row
+    Many(ManyArgs),
+    /// Run a batch of comparisons described in a TOML or YAML manifest
+    Jobs(JobsArgs),
+    /// Launch the graphical user interface
+    Gui,
+}
+
+/// Arguments of the `compare` subcommand.
+#[derive(Debug, Args)]
+pub struct CompareArgs {
+    /// The first CSV file (or directory with --dir)
+    pub file1: String,
+    /// The second CSV file (or directory with --dir)
+    pub file2: String,
+    /// Comma-separated list of columns to ignore during comparison ("Amount, USD" quotes a name with commas)
+    #[arg(short, long)]
+    pub ignore: Option<String>,
+    /// Output file encoding (same names as --input-encoding1, except auto; default utf8)
+    #[arg(short, long)]
+    pub encoding: Option<String>,
+    /// What happens to characters the output encoding cannot represent (error, replace
+    /// or transliterate; default error)
+    #[arg(long)]
+    pub unencodable: Option<String>,
+    /// Encoding of the first file (auto, utf8, utf8bom, utf16le, utf16be, latin1,
+    /// windows1250, windows1251, windows1252, shiftjis, gbk or euckr; default utf8)
+    #[arg(long = "input-encoding1")]
+    pub input_encoding1: Option<String>,
+    /// Encoding of the second file (same names as --input-encoding1; default utf8)
+    #[arg(long = "input-encoding2")]
+    pub input_encoding2: Option<String>,
+    /// Comma-separated list of columns to compare by numeric value
+    #[arg(short, long)]
+    pub numeric: Option<String>,
+    /// Number format of the first file (us, eu, fr, ch or plain)
+    #[arg(long)]
+    pub locale1: Option<String>,
+    /// Number format of the second file (us, eu, fr, ch or plain)
+    #[arg(long)]
+    pub locale2: Option<String>,
+    /// Round numeric values to the nearest multiple of this before comparing them
+    #[arg(short, long = "round-to")]
+    pub round_to: Option<f64>,
+    /// Treat empty, NULL, null, N/A, - and \N as the same missing value
+    #[arg(long, overrides_with = "no_nulls")]
+    pub nulls: bool,
+    /// Treat no value as missing, even if a profile sets null tokens or column nulls
+    #[arg(long = "no-nulls", overrides_with = "nulls", conflicts_with = "null_tokens")]
+    pub no_nulls: bool,
+    /// Comma-separated list of tokens treated as a missing value (replaces --nulls)
+    #[arg(long = "null-tokens")]
+    pub null_tokens: Option<String>,
+    /// Null tokens for one column, as column=tok1|tok2 (repeatable)
+    #[arg(long = "column-nulls")]
+    pub column_nulls: Vec<String>,
+    /// Accept short records and treat their missing trailing fields as null
+    #[arg(long = "missing-as-null", overrides_with = "no_missing_as_null")]
+    pub missing_as_null: bool,
+    /// Reject short records even if a profile enables --missing-as-null
+    #[arg(long = "no-missing-as-null", overrides_with = "missing_as_null")]
+    pub no_missing_as_null: bool,
+    /// Handling of rows with the wrong field count: strict, reject, pad, truncate or pad-truncate
+    #[arg(long)]
+    pub ragged: Option<String>,
+    /// Comma-separated list of key columns, to count changed rows separately from added ones
+    #[arg(short, long)]
+    pub key: Option<String>,
+    /// Name of a comparison profile from csvscripts.toml
+    #[arg(short, long)]
+    pub profile: Option<String>,
+    /// Configuration file to read profiles from (default: csvscripts.toml found upward, then the user config)
+    #[arg(long)]
+    pub config: Option<String>,
+    /// Treat file1 and file2 as directories and compare files pairwise
+    #[arg(short, long)]
+    pub dir: bool,
+    /// Directory mode: regex pairing files by its first capture group instead of relative path
+    #[arg(long)]
+    pub pattern: Option<String>,
+    /// Directory mode: where to write the per-pair outputs
+    #[arg(long = "output-dir", default_value = "compare_output")]
+    pub output_dir: String,
+    /// Directory mode: summary file, JSON if it ends in .json, else CSV (default: <output-dir>/summary.csv)
+    #[arg(long)]
+    pub summary: Option<String>,
+}
+
+/// Arguments of the `encoding` subcommand.
+#[derive(Debug, Args)]
+pub struct EncodingArgs {
+    /// The file to check
+    pub file: String,
+    /// How much of the file to examine: head (its start), spread (its start, middle
+    /// and end) or full (all of it, validating UTF-8 throughout)
+    #[arg(long, default_value = "spread")]
+    pub scan: String,
+    /// Number of bytes examined by the head and spread scans
+    #[arg(long, default_value_t = DEFAULT_SAMPLE_BYTES)]
+    pub sample_size: u64,
+    /// Scan the whole file for invalid UTF-8 and list where each sequence is
+    #[arg(long)]
+    pub invalid: bool,
+    /// Maximum number of invalid sequences listed with --invalid
+    #[arg(long, default_value_t = 100)]
+    pub max_invalid: usize,
+}
+
+/// Arguments of the `merge` subcommand.
+#[derive(Debug, Args)]
+pub struct MergeArgs {
+    /// The common ancestor CSV file
+    pub base: String,
+    /// Our edited copy of the base file
+    pub ours: String,
+    /// Their edited copy of the base file
+    pub theirs: String,
+    /// Comma-separated list of columns identifying a row
+    #[arg(short, long, required = true)]
+    pub key: String,
+    /// Path of the merged file (default: <ours>_merged.csv)
+    #[arg(short, long)]
+    pub output: Option<String>,
+    /// Path of the conflicts file (default: <ours>_conflicts.csv)
+    #[arg(short, long)]
+    pub conflicts: Option<String>,
+    /// Handling of rows with the wrong field count: strict, reject, pad, truncate or pad-truncate
+    #[arg(long)]
+    pub ragged: Option<String>,
+}
+
+/// Arguments of the `many` subcommand.
+#[derive(Debug, Args)]
+pub struct ManyArgs {
+    /// The CSV files to compare (at least two)
+    #[arg(required = true, num_args = 2..)]
+    pub files: Vec<String>,
+    /// Comma-separated list of columns to ignore during comparison
+    #[arg(short, long)]
+    pub ignore: Option<String>,
+    /// Comma-separated list of key columns to match rows by instead of whole rows
+    #[arg(short, long)]
+    pub key: Option<String>,
+    /// Comma-separated labels for the files in the matrix (e.g. dev,qa,prod)
+    #[arg(short, long)]
+    pub labels: Option<String>,
+    /// Path of the presence matrix
+    #[arg(short, long, default_value = "presence_matrix.csv")]
+    pub output: String,
+    /// Handling of rows with the wrong field count: strict, reject, pad, truncate or pad-truncate
+    #[arg(long)]
+    pub ragged: Option<String>,
+}
+
+/// Arguments of the `jobs` subcommand.
+#[derive(Debug, Args)]
+pub struct JobsArgs {
+    /// The job manifest (.toml, .yaml or .yml)
+    pub manifest: String,
+    /// Number of jobs to run at the same time (overrides the manifest)
+    #[arg(short, long)]
+    pub parallel: Option<usize>,
+    /// Consolidated report path, JSON if it ends in .json (overrides the manifest)
+    #[arg(short, long)]
+    pub report: Option<String>,
+}
+
+/// Prints results according to the global output format and verbosity.
+struct Output {
+    format: OutputFormat,
+    verbose: u8,
+    quiet: bool,
+}
+
+impl Output {
+    /// Prints a line of the text summary, unless quiet or in JSON mode.
+    fn info(&self, line: &str) {
+        if self.format == OutputFormat::Text && !self.quiet {
+            println!("{}", line);
+        }
+    }
+
+    /// Prints a detail line, only with `--verbose` in text mode.
+    fn detail(&self, line: &str) {
+        if self.format == OutputFormat::Text && self.verbose > 0 {
+            println!("{}", line);
+        }
+    }
+
+    /// Prints a result as JSON, only in JSON mode.
+    fn json<T: Serialize>(&self, value: &T) {
+        if self.format == OutputFormat::Json {
+            match serde_json::to_string_pretty(value) {
+                Ok(text) => println!("{}", text),
+                Err(e) => eprintln!("Error formatting JSON output: {}", e),
+            }
+        }
+    }
+
+    /// Prints an error and returns the failure exit code.
+    fn fail(&self, context: &str, error: impl std::fmt::Display) -> ExitCode {
+        eprintln!("{}: {}", context, error);
+        ExitCode::FAILURE
+    }
+}
+
+/// Parses the process arguments as if `subcommand` had been given first, and runs it.
+///
+/// This lets the older single-purpose binaries (`compare_csv`, `check_encoding`, ...)
+/// act as aliases of the matching subcommand with unchanged arguments.
+pub fn run_alias(subcommand: &str) -> ExitCode {
+    let mut args: Vec<OsString> = std::env::args_os().collect();
+    args.insert(args.len().min(1), subcommand.into());
+    run(Cli::parse_from(args))
+}
+
+/// Runs the command selected on the command line.
+///
+/// The `gui` subcommand must be handled by the caller, since the GUI is part of the
+/// `rust_csv_scripts` binary rather than the library.
+pub fn run(cli: Cli) -> ExitCode {
+    let out = Output { format: cli.global.format, verbose: cli.global.verbose, quiet: cli.global.quiet };
+    let dialect = match cli.global.dialect_spec().to_options() {
+        Ok(options) => options.dialect,
+        Err(e) => return out.fail("Invalid dialect", e),
+    };
+
+    match cli.command {
+        Some(Commands::Compare(args)) => run_compare(*args, &cli.global, &out),
+        Some(Commands::Encoding(args)) => run_encoding(args, &out),
+        Some(Commands::Merge(args)) => run_merge(args, dialect, &out),
+        Some(Commands::Many(args)) => run_many(args, dialect, &out),
+        Some(Commands::Jobs(args)) => run_jobs_command(args, &cli.global, &out),
+        Some(Commands::Gui) => out.fail("Error", "The GUI is only available from the rust_csv_scripts binary"),
+        None => {
+            eprintln!("No command given. Run with --help to list the available commands.");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Runs the `compare` subcommand.
+fn run_compare(args: CompareArgs, global: &GlobalArgs, out: &Output) -> ExitCode {
+    let cli_spec = match compare_spec(&args, global) {
+        Ok(spec) => spec,
+        Err(e) => return out.fail("Invalid option", e),
+    };
+
+    // Layer the command-line options on top of the selected profile, if any.
+    let spec = match &args.profile {
+        Some(name) => match resolve_profile(name, Path::new("."), args.config.as_deref().map(Path::new)) {
+            Ok(profile) => profile.merged_with(&cli_spec),
+            Err(e) => return out.fail("Error loading profile", e),
+        },
+        None => cli_spec,
+    };
+    let options = match spec.to_options() {
+        Ok(options) => options,
+        Err(e) => return out.fail("Invalid option", e),
+    };
+    out.detail(&format!("Options: {:?}", spec));
+
+    // In directory mode, compare every pair of files and print the totals.
+    if args.dir {
+        let dir_options = DirCompareOptions {
+            compare: options,
+            pattern: args.pattern,
+            output_dir: args.output_dir,
+            summary: args.summary,
+        };
+        return match compare_dirs(&args.file1, &args.file2, &dir_options) {
+            Ok(summary) => {
+                for pair in &summary.pairs {
+                    out.detail(&format!("  {}: {}", pair.key, pair.status));
+                }
+                out.info(&format!(
+                    "{} matching, {} differing, {} failed, {} only in '{}', {} only in '{}'",
+                    summary.matched, summary.differing, summary.errors, summary.left_only, args.file1, summary.right_only, args.file2
+                ));
+                out.info(&format!("Directory summary written to '{}'", summary.summary_file));
+                out.json(&summary);
+                ExitCode::SUCCESS
+            }
+            Err(e) => out.fail("Error comparing directories", e),
+        };
+    }
+
+    match compare_with_options(&args.file1, &args.file2, &options) {
+        Ok(report) => {
+            out.info(&format!("Unique rows from '{}' written to '{}'", args.file1, report.output_file));
+            out.detail(&format!("{} row(s) in '{}', {} row(s) in '{}'", report.rows_file1, args.file1, report.rows_file2, args.file2));
+            out.detail(&format!("Read '{}' as {} and '{}' as {}", args.file1, report.input_encoding1, args.file2, report.input_encoding2));
+            // With key columns, break the unique rows down into changed and added rows.
+            if !options.key_columns.is_empty() {
+                out.info(&format!("{} unique row(s): {} changed, {} added", report.unique_rows, report.changed_rows, report.added_rows));
+            }
+            if let Some(path) = &report.number_failures_file {
+                out.info(&format!("{} cell(s) could not be parsed as numbers, see '{}'", report.number_failures.len(), path));
+            }
+            if let Some(path) = &report.rejects_file {
+                out.info(&format!("{} row(s) rejected and {} repaired, see '{}'", report.rejected_rows, report.repaired_rows, path));
+            }
+            if let Some(path) = &report.unencodable_file {
+                out.info(&format!("{} cell(s) could not be written exactly as {}, see '{}'", report.unencodable_cells.len(), options.encoding, path));
+            }
+            out.json(&report);
+            ExitCode::SUCCESS
+        }
+        Err(e) => out.fail("Error comparing files", e),
+    }
+}
+
+/// Collects the comparison options given on the command line; unset flags stay `None`,
+/// so a profile can fill them in, while flags that turn an option off are `Some`.
+fn compare_spec(args: &CompareArgs, global: &GlobalArgs) -> Result<CompareSpec, String> {
+    let mut column_nulls = BTreeMap::new();
+    for spec in &args.column_nulls {
+        let (column, tokens) = NullTokens::parse_column_spec(spec)?;
+        column_nulls.insert(column, tokens);
+    }
+    Ok(CompareSpec {
+        ignore_columns: args.ignore.as_deref().map(split_list),
+        key_columns: args.key.as_deref().map(split_list),
+        numeric_columns: args.numeric.as_deref().map(split_list),
+        locale1: args.locale1.clone(),
+        locale2: args.locale2.clone(),
+        round_to: args.round_to,
+        null_tokens: match &args.null_tokens {
+            Some(tokens) => Some(tokens.split(',').map(|t| t.to_string()).collect()),
+            None if args.nulls => Some(STANDARD_NULL_TOKENS.iter().map(|t| t.to_string()).collect()),
+            None if args.no_nulls => Some(Vec::new()),
+            None => None,
+        },
+        column_nulls: if !column_nulls.is_empty() || args.no_nulls { Some(column_nulls) } else { None },
+        missing_as_null: if args.missing_as_null {
+            Some(true)
+        } else if args.no_missing_as_null {
+            Some(false)
+        } else {
+            None
+        },
+        ragged: args.ragged.clone(),
+        encoding: args.encoding.clone(),
+        unencodable: args.unencodable.clone(),
+        input_encoding1: args.input_encoding1.clone(),
+        input_encoding2: args.input_encoding2.clone(),
+        ..global.dialect_spec()
+    })
+}
+
+/// Runs the `encoding` subcommand.
+fn run_encoding(args: EncodingArgs, out: &Output) -> ExitCode {
+    let mode = match DetectionMode::from_name(&args.scan, args.sample_size) {
+        Ok(mode) => mode,
+        Err(e) => return out.fail("Invalid option", e),
+    };
+    let detection = match detect_encoding_with(Path::new(&args.file), mode) {
+        Ok(detection) => detection,
+        Err(e) => return out.fail("Error detecting encoding", e),
+    };
+    out.info(&format!("Detected encoding: {}", detection.encoding));
+    out.info(&format!("Confidence: {:.2}%", detection.confidence * 100.0));
+    out.info(&format!("Byte-order mark: {}", detection.bom.map_or("none".to_string(), |bom| bom.to_string())));
+    let examined = if detection.is_complete() { "whole file".to_string() } else { detection.mode.to_string() };
+    out.info(&format!("Examined: {} ({} of {} bytes)", examined, detection.bytes_examined, detection.file_size));
+    if let Some(valid) = detection.valid_utf8 {
+        out.detail(&format!("Valid UTF-8: {}", if valid { "yes" } else { "no" }));
+    }
+
+    // With --invalid, locate every invalid UTF-8 sequence in the file.
+    let validation = if args.invalid {
+        match validate_utf8(Path::new(&args.file), args.max_invalid) {
+            Ok(validation) => Some(validation),
+            Err(e) => return out.fail("Error checking UTF-8", e),
+        }
+    } else {
+        None
+    };
+    if let Some(validation) = &validation {
+        print_invalid_sequences(validation, out);
+    }
+
+    out.json(&serde_json::json!({
+        "file": args.file,
+        "encoding": detection.encoding,
+        "confidence": detection.confidence,
+        "bom": detection.bom,
+        "mode": detection.mode.name(),
+        "bytes_examined": detection.bytes_examined,
+        "file_size": detection.file_size,
+        "valid_utf8": validation.as_ref().map(|v| v.is_valid()).or(detection.valid_utf8),
+        "invalid_utf8": validation,
+    }));
+    ExitCode::SUCCESS
+}
+
+/// Prints the invalid UTF-8 sequences of a file, one per line.
+fn print_invalid_sequences(validation: &Utf8Validation, out: &Output) {
+    if validation.is_valid() {
+        out.info("Valid UTF-8: yes");
+        return;
+    }
+    let listed = validation.sequences.len() as u64;
+    let shown = if listed < validation.invalid_count { format!(", first {} listed", listed) } else { String::new() };
+    out.info(&format!("Invalid UTF-8 sequences: {}{}", validation.invalid_count, shown));
+    let legacy = validation.legacy_encoding.map_or(String::new(), |encoding| encoding.to_string());
+    for sequence in &validation.sequences {
+        let decoded = match &sequence.decoded {
+            Some(text) => format!(", '{}' in {}", text, legacy),
+            None => String::new(),
+        };
+        out.info(&format!("  line {}, column {} (byte {}): {}{} in \"{}\"",
+            sequence.line, sequence.column, sequence.offset, sequence.bytes, decoded, sequence.context));
+    }
+}
+
+/// Runs the `merge` subcommand.
+fn run_merge(args: MergeArgs, dialect: Dialect, out: &Output) -> ExitCode {
+    let ragged = match args.ragged.as_deref().map(str::parse::<RaggedPolicy>).transpose() {
+        Ok(ragged) => ragged.unwrap_or_default(),
+        Err(e) => return out.fail("Invalid option", e),
+    };
+    let options = MergeOptions {
+        key_columns: split_list(&args.key),
+        ragged,
+        output: args.output,
+        conflicts: args.conflicts,
+        dialect,
+    };
+
+    match merge_three_way(&args.base, &args.ours, &args.theirs, &options) {
+        Ok(report) => {
+            out.info(&format!("Merged {} row(s) into '{}'", report.merged_rows, report.output_file));
+            out.info(&format!("Changes applied: {} from ours, {} from theirs", report.changes_from_ours, report.changes_from_theirs));
+            if let Some(path) = &report.conflicts_file {
+                out.info(&format!("{} conflict(s) written to '{}'", report.conflicts, path));
+            }
+            out.json(&report);
+            ExitCode::SUCCESS
+        }
+        Err(e) => out.fail("Error merging files", e),
+    }
+}
+
+/// Runs the `many` subcommand.
+fn run_many(args: ManyArgs, dialect: Dialect, out: &Output) -> ExitCode {
+    let ragged = match args.ragged.as_deref().map(str::parse::<RaggedPolicy>).transpose() {
+        Ok(ragged) => ragged.unwrap_or_default(),
+        Err(e) => return out.fail("Invalid option", e),
+    };
+    let options = NWayOptions {
+        ignore_columns: args.ignore.as_deref().map(split_list).unwrap_or_default().into_iter().collect::<HashSet<_>>(),
+        key_columns: args.key.as_deref().map(split_list).unwrap_or_default(),
+        labels: args.labels.as_deref().map(split_list).unwrap_or_default(),
+        ragged,
+        output: args.output,
+        dialect,
+    };
+
+    match compare_many(&args.files, &options) {
+        Ok(report) => {
+            out.info(&format!("Presence matrix written to '{}'", report.matrix_file));
+            out.info(&format!("{} distinct row(s), {} missing from at least one file", report.distinct_rows, report.incomplete_rows));
+            for file in &report.files {
+                out.info(&format!("  {}: {} row(s), {} distinct, {} missing", file.label, file.rows, file.distinct, file.missing));
+            }
+            out.info(&format!("Summary written to '{}'", report.summary_file));
+            out.info(&format!("Incomplete rows written to '{}'", report.missing_file));
+            out.json(&report);
+            ExitCode::SUCCESS
+        }
+        Err(e) => out.fail("Error comparing files", e),
+    }
+}
+
+/// Runs the `jobs` subcommand.
+fn run_jobs_command(args: JobsArgs, global: &GlobalArgs, out: &Output) -> ExitCode {
+    let manifest_path = Path::new(&args.manifest);
+    let mut manifest = match load_manifest(manifest_path) {
+        Ok(manifest) => manifest,
+        Err(e) => return out.fail("Error loading manifest", e),
+    };
+
+    // The global dialect options are the default for jobs that do not set their own.
+    let defaults = global.dialect_spec();
+    for job in &mut manifest.jobs {
+        job.options = defaults.merged_with(&job.options);
+    }
+
+    // Command-line values take precedence over the manifest settings.
+    let parallel = args.parallel.or(manifest.parallel).unwrap_or(1);
+    let base_dir = manifest_path.parent().unwrap_or(Path::new("."));
+    let report = args.report
+        .or_else(|| manifest.report.as_ref().map(|r| base_dir.join(r).display().to_string()))
+        .unwrap_or_else(|| output_path(&manifest_path.with_extension("csv").display().to_string(), "report"));
+
+    let results = run_jobs(&manifest, base_dir, parallel);
+
+    // Print one line per job, then write the consolidated report.
+    for r in &results {
+        match &r.error {
+            None => out.info(&format!("[ok]    {}: {} unique row(s) in {} ms", r.name, r.unique_rows, r.elapsed_ms)),
+            Some(e) => out.info(&format!("[error] {}: {}", r.name, e)),
+        }
+    }
+    let failed = results.iter().filter(|r| r.error.is_some()).count();
+    out.info(&format!("{} job(s) run, {} failed", results.len(), failed));
+    out.json(&results);
+
+    if let Err(e) = write_job_report(&report, &results) {
+        return out.fail("Error writing report", e);
+    }
+    out.info(&format!("Report written to '{}'", report));
+
+    if failed > 0 { ExitCode::FAILURE } else { ExitCode::SUCCESS }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parses a `compare` command line and returns its options layered on `profile`.
+    fn compare_options(profile: &CompareSpec, args: &[&str]) -> CompareSpec {
+        let cli = Cli::try_parse_from(["rust_csv_scripts", "compare", "a.csv", "b.csv"].iter().chain(args)).unwrap();
+        let Some(Commands::Compare(compare)) = cli.command else { panic!("not a compare command") };
+        profile.merged_with(&compare_spec(&compare, &cli.global).unwrap())
+    }
+
+    #[test]
+    fn flags_turn_off_what_a_profile_turns_on() {
+        let profile = CompareSpec {
+            trim: Some(true),
+            null_tokens: Some(vec!["NULL".to_string()]),
+            column_nulls: Some(BTreeMap::from([("status".to_string(), vec!["-".to_string()])])),
+            ..CompareSpec::default()
+        };
+
+        let spec = compare_options(&profile, &[]);
+        assert_eq!(spec.trim, Some(true));
+        assert!(spec.to_options().unwrap().null_tokens.is_null("status", "-"));
+
+        let spec = compare_options(&profile, &["--no-trim", "--no-nulls"]);
+        assert_eq!(spec.trim, Some(false));
+        let options = spec.to_options().unwrap();
+        assert!(!options.dialect.trim);
+        assert!(options.null_tokens.is_empty());
+
+        // The last of two opposite flags wins, and column nulls can still be given.
+        let spec = compare_options(&profile, &["--no-trim", "--trim", "--nulls", "--no-nulls", "--column-nulls", "id=0"]);
+        assert_eq!(spec.trim, Some(true));
+        let options = spec.to_options().unwrap();
+        assert!(!options.null_tokens.is_null("status", "-"));
+        assert!(options.null_tokens.is_null("id", "0"));
+    }
+
+    #[test]
+    fn no_nulls_conflicts_with_null_tokens() {
+        let args = ["rust_csv_scripts", "compare", "a.csv", "b.csv", "--no-nulls", "--null-tokens", "NULL"];
+        assert!(Cli::try_parse_from(args).is_err());
+    }
+}
@@ -0,0 +1,186 @@
+use std::error::Error;
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+use crate::spec::CompareSpec;
+
+/// Name of the project-level configuration file, discovered upward from the working directory.
+pub const CONFIG_FILE_NAME: &str = "csvscripts.toml";
+
+/// A configuration file holding named comparison profiles.
+///
+/// ```toml
+/// [profile.weekly]
+/// ignore_columns = ["exported_at", "batch_id"]
+/// encoding = "utf8bom"
+/// delimiter = "semicolon"
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    #[serde(rename = "profile")]
+    pub profiles: BTreeMap<String, CompareSpec>, // Profiles by name
+}
+
+/// Finds the nearest `csvscripts.toml`, starting at `start` and walking up to the root.
+pub fn find_project_config(start: &Path) -> Option<PathBuf> {
+    // Resolve relative paths such as "." so that their parents can be visited.
+    let start = start.canonicalize().unwrap_or_else(|_| start.to_path_buf());
+    start.ancestors()
+        .map(|dir| dir.join(CONFIG_FILE_NAME))
+        .find(|path| path.is_file())
+}
+
+/// Returns the path of the user-level configuration file.
+///
+/// This is `%APPDATA%\csvscripts\csvscripts.toml` on Windows, and
+/// `$XDG_CONFIG_HOME/csvscripts/csvscripts.toml` (defaulting to `~/.config`) elsewhere.
+pub fn user_config_path() -> Option<PathBuf> {
+    let base = if cfg!(windows) {
+        env::var_os("APPDATA").map(PathBuf::from)
+    } else {
+        env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+    };
+    base.map(|dir| dir.join("csvscripts").join(CONFIG_FILE_NAME))
+}
+
+/// Reads a configuration file. A missing file yields an empty configuration.
+pub fn load_config(path: &Path) -> Result<Config, Box<dyn Error>> {
+    if !path.exists() {
+        return Ok(Config::default());
+    }
+    let text = fs::read_to_string(path)?;
+    toml::from_str(&text).map_err(|e| format!("Invalid config file '{}': {}", path.display(), e).into())
+}
+
+/// Writes a configuration file, creating its directory if needed.
+pub fn save_config(path: &Path, config: &Config) -> Result<(), Box<dyn Error>> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, toml::to_string_pretty(config)?)?;
+    Ok(())
+}
+
+/// Loads the profiles visible from `working_dir`.
+///
+/// User-level profiles are loaded first, then the nearest project file is layered on
+/// top: a project profile with the same name overrides the user profile field by field.
+pub fn load_profiles(working_dir: &Path) -> Result<Config, Box<dyn Error>> {
+    let mut config = match user_config_path() {
+        Some(path) => load_config(&path)?,
+        None => Config::default(),
+    };
+    if let Some(path) = find_project_config(working_dir) {
+        layer_profiles(&mut config, load_config(&path)?);
+    }
+    Ok(config)
+}
+
+/// Layers the profiles of `project` on top of `config`, merging profiles with the same
+/// name field by field so that the project values win.
+fn layer_profiles(config: &mut Config, project: Config) {
+    for (name, spec) in project.profiles {
+        let merged = match config.profiles.get(&name) {
+            Some(user) => user.merged_with(&spec),
+            None => spec,
+        };
+        config.profiles.insert(name, merged);
+    }
+}
+
+/// Looks up a profile by name among the user and project profiles, or in `config_file`
+/// alone when one is given.
+///
+/// # Errors
+///
+/// Returns an error if a configuration file is invalid or the profile does not exist.
+pub fn resolve_profile(name: &str, working_dir: &Path, config_file: Option<&Path>) -> Result<CompareSpec, Box<dyn Error>> {
+    let config = match config_file {
+        Some(path) => load_config(path)?,
+        None => load_profiles(working_dir)?,
+    };
+    config.profiles.get(name).cloned().ok_or_else(|| {
+        let known: Vec<&str> = config.profiles.keys().map(|k| k.as_str()).collect();
+        format!("Unknown profile '{}'. Available profiles: {}", name, if known.is_empty() { "none".to_string() } else { known.join(", ") }).into()
+    })
+}
+
+/// Saves a profile to the project configuration if one is found from `working_dir`,
+/// otherwise to the user configuration.
+///
+/// # Returns
+///
+/// The path of the file the profile was written to.
+pub fn save_profile(name: &str, spec: &CompareSpec, working_dir: &Path) -> Result<PathBuf, Box<dyn Error>> {
+    let path = find_project_config(working_dir)
+        .or_else(user_config_path)
+        .ok_or("No configuration directory available")?;
+    let mut config = load_config(&path)?;
+    config.profiles.insert(name.to_string(), spec.clone());
+    save_config(&path, &config)?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    const USER: &str = "[profile.weekly]\nignore_columns = [\"ts\"]\ndelimiter = \"semicolon\"\n\n[profile.user_only]\nencoding = \"utf8bom\"\n";
+    const PROJECT: &str = "[profile.weekly]\nignore_columns = [\"batch\"]\nkey_columns = [\"id\"]\n";
+
+    fn list(items: &[&str]) -> Option<Vec<String>> {
+        Some(items.iter().map(|s| s.to_string()).collect())
+    }
+
+    #[test]
+    fn project_profiles_override_user_profiles_field_by_field() {
+        let dir = TempDir::new();
+        let mut config = load_config(Path::new(&dir.write("user.toml", USER))).unwrap();
+        layer_profiles(&mut config, load_config(Path::new(&dir.write("project.toml", PROJECT))).unwrap());
+
+        let weekly = &config.profiles["weekly"];
+        assert_eq!(weekly.ignore_columns, list(&["batch"]));
+        assert_eq!(weekly.key_columns, list(&["id"]));
+        assert_eq!(weekly.delimiter.as_deref(), Some("semicolon"));
+        assert_eq!(config.profiles["user_only"].encoding.as_deref(), Some("utf8bom"));
+    }
+
+    #[test]
+    fn finds_the_nearest_project_config() {
+        let dir = TempDir::new();
+        dir.write(&format!("repo/{}", CONFIG_FILE_NAME), PROJECT);
+        dir.write(&format!("repo/sub/{}", CONFIG_FILE_NAME), USER);
+        dir.write("repo/sub/deeper/data.csv", "id\n");
+
+        let found = find_project_config(Path::new(&dir.path("repo/sub/deeper"))).unwrap();
+        assert_eq!(found, Path::new(&dir.path("repo/sub")).canonicalize().unwrap().join(CONFIG_FILE_NAME));
+        let found = find_project_config(Path::new(&dir.path("repo"))).unwrap();
+        assert!(found.ends_with(format!("repo/{}", CONFIG_FILE_NAME)));
+    }
+
+    #[test]
+    fn loads_saves_and_resolves_profiles() {
+        let dir = TempDir::new();
+        let path = PathBuf::from(dir.path("nested/config.toml"));
+        assert_eq!(load_config(&path).unwrap(), Config::default());
+
+        let mut config = Config::default();
+        config.profiles.insert("weekly".to_string(), CompareSpec { key_columns: list(&["id"]), ..CompareSpec::default() });
+        save_config(&path, &config).unwrap();
+        assert_eq!(load_config(&path).unwrap(), config);
+
+        let spec = resolve_profile("weekly", Path::new(&dir.path("")), Some(&path)).unwrap();
+        assert_eq!(spec.key_columns, list(&["id"]));
+        let error = resolve_profile("daily", Path::new(&dir.path("")), Some(&path)).unwrap_err();
+        assert_eq!(error.to_string(), "Unknown profile 'daily'. Available profiles: weekly");
+
+        let error = load_config(Path::new(&dir.write("bad.toml", "[profile.x]\ntrim = \"yes\"\n"))).unwrap_err();
+        assert!(error.to_string().starts_with("Invalid config file"));
+    }
+}
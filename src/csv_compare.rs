@@ -1,11 +1,74 @@
 use std::error::Error;
-use std::collections::HashSet;
-use csv::{ReaderBuilder, WriterBuilder, StringRecord};
-use std::io::Write;
-use crate::encoding::Encoding;
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::path::Path;
+use std::sync::Arc;
+use csv::{WriterBuilder, StringRecord};
+use crate::encoding::{decode_reader, open_decoded, resolve_input_encoding, Encoding, InputEncoding};
+use crate::dialect::Dialect;
+use crate::number::{canonical_number, NumberLocale};
+use crate::nulls::{clear_null_markers, NullTokens, NULL_MARKER};
+use crate::ragged::{read_regular_record, RaggedPolicy, Rejects};
+use crate::progress::{CountingReader, FileProgress, Progress};
+use crate::transcode::{EncodedWriter, OutputEncoder, UnencodableCell, UnencodablePolicy};
+use serde::Serialize;
+
+/// Options controlling how two CSV files are compared.
+///
+/// `CompareOptions::default()` reproduces the plain comparison: every column is
+/// compared as text and the output is written as UTF-8.
+#[derive(Debug, Clone, Default)]
+pub struct CompareOptions {
+    pub ignore_columns: HashSet<String>,  // Column names to ignore during comparison
+    pub encoding: Encoding,               // Encoding of the output CSV file
+    pub unencodable: UnencodablePolicy,   // What happens to characters the output encoding cannot represent
+    pub input_encoding1: InputEncoding,   // Encoding of the first file, or whether to detect it
+    pub input_encoding2: InputEncoding,   // Encoding of the second file, or whether to detect it
+    pub numeric_columns: HashSet<String>, // Columns compared by numeric value instead of text
+    pub locale1: NumberLocale,            // How numbers are written in the first file
+    pub locale2: NumberLocale,            // How numbers are written in the second file
+    pub round_to: Option<f64>,            // Numeric values are rounded to a multiple of this before comparing
+    pub null_tokens: NullTokens,          // Spellings of a missing value that compare as equal
+    pub missing_as_null: bool,            // Treat fields missing from short records as null
+    pub ragged: RaggedPolicy,             // How records with the wrong number of fields are handled
+    pub output_file: Option<String>,      // Path of the output file (default `<file1>_modified.csv`)
+    pub dialect: Dialect,                 // Delimiter and quoting of the input and output files
+    pub key_columns: Vec<String>,         // Columns identifying a row, to tell changed rows from added ones
+    pub progress: Option<Arc<Progress>>,  // Counters updated while reading, with a cancellation flag
+}
+
+/// A cell in a numeric column that could not be parsed as a number.
+#[derive(Debug, Clone, Serialize)]
+pub struct NumberParseFailure {
+    pub file: String,   // Path of the file containing the cell
+    pub line: u64,      // Line number of the record in the file (1-based)
+    pub column: String, // Header of the column
+    pub value: String,  // The raw cell content
+}
+
+/// Summary of a comparison run.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CompareReport {
+    pub output_file: String,                        // Path of the CSV file with the unique rows
+    pub input_encoding1: Encoding,                  // Encoding the first file was read as
+    pub input_encoding2: Encoding,                  // Encoding the second file was read as
+    pub rows_file1: u64,                            // Number of data rows read from the first file
+    pub rows_file2: u64,                            // Number of data rows read from the second file
+    pub unique_rows: u64,                           // Number of rows written to the output file
+    pub same_rows: bool,                            // Both files hold the same rows the same number of times
+    pub changed_rows: u64,                          // Unique rows whose key exists in the second file
+    pub added_rows: u64,                            // Unique rows whose key is absent from the second file
+    pub number_failures: Vec<NumberParseFailure>,   // Numeric cells that failed to parse
+    pub number_failures_file: Option<String>,       // Report file listing the failures, if any
+    pub rejected_rows: u64,                         // Irregular or malformed rows left out of the comparison
+    pub repaired_rows: u64,                         // Irregular rows padded or truncated before comparing
+    pub rejects_file: Option<String>,               // File listing rejected and repaired rows, if any
+    pub unencodable_cells: Vec<UnencodableCell>,    // Output cells written differently because of their encoding
+    pub unencodable_file: Option<String>,           // Report file listing those cells, if any
+}
 
 /// Compares rows between two CSV files, filters out ignored columns, and writes
-/// unique rows from `file1` (i.e., rows not found in `file2`) to a new CSV file 
+/// unique rows from `file1` (i.e., rows not found in `file2`) to a new CSV file
 /// with the same headers as `file1`.
 ///
 /// # Arguments
@@ -17,62 +80,328 @@ use crate::encoding::Encoding;
 ///
 /// # Returns
 ///
-/// Returns `Ok(())` if the operation succeeds, or an error if there are issues 
-/// reading/writing the files or processing the data.
+/// Returns `Ok(())` if the operation succeeds, or an error if there are issues
+/// reading/writing the files or processing the data. Use `compare_with_options` to
+/// get the `CompareReport` of the run.
 pub fn compare_and_write_unique_rows(file1: &str, file2: &str, ignore_columns: &HashSet<String>, encoding: Encoding) -> Result<(), Box<dyn Error>> {
-    // Read rows from file2 into a HashSet for fast comparison (removes duplicates).
-    let mut reader2 = ReaderBuilder::new().from_path(file2)?;
-    let mut rows_in_file2: HashSet<Vec<String>> = HashSet::new();
+    let options = CompareOptions {
+        ignore_columns: ignore_columns.clone(),
+        encoding,
+        ..CompareOptions::default()
+    };
+    compare_with_options(file1, file2, &options)?;
+    Ok(())
+}
+
+/// Compares two CSV files using the given options and writes the rows of `file1`
+/// that are not found in `file2` to `options.output_file` (default `<file1>_modified.csv`).
+///
+/// Cells in `options.numeric_columns` are parsed with the locale of their file and
+/// compared by value. Cells that fail to parse are compared as text and listed in
+/// the returned report, which is also written to `<file1>_number_errors.csv` (or next
+/// to `options.output_file` when one is given).
+/// Cells matching `options.null_tokens` compare equal to each other. Records with the
+/// wrong number of fields are handled according to `options.ragged`, and any rejected
+/// or repaired records are listed in `<file1>_rejects.csv`. When `options.key_columns`
+/// is set, the unique rows are further counted as changed (key found in `file2`) or added.
+/// The report also tells whether both files hold the same rows the same number of times.
+/// When `options.progress` is set, the rows and bytes read are reported through it, and
+/// the comparison stops with a `Cancelled` error once it is cancelled, removing the
+/// partly written output and rejects files. Inputs whose encoding is set to `InputEncoding::Auto`
+/// are detected before anything is written, and both files are decoded to UTF-8 as they
+/// are read, so files in different encodings can be compared against each other. The output is written in `options.encoding`; cells
+/// it cannot represent are handled according to `options.unencodable`, and those
+/// replaced or transliterated are listed in `<file1>_unencodable.csv`.
+///
+/// # Arguments
+///
+/// * `file1` - The path to the first CSV file.
+/// * `file2` - The path to the second CSV file.
+/// * `options` - The comparison options.
+///
+/// # Returns
+///
+/// A `CompareReport` describing the run, or an error if the files cannot be read or written.
+pub fn compare_with_options(file1: &str, file2: &str, options: &CompareOptions) -> Result<CompareReport, Box<dyn Error>> {
+    let mut report = CompareReport {
+        input_encoding1: resolve_input_encoding(Path::new(file1), options.input_encoding1)?,
+        input_encoding2: resolve_input_encoding(Path::new(file2), options.input_encoding2)?,
+        ..CompareReport::default()
+    };
+    // Side reports are named after the output file if one was given, else after file1.
+    let report_base = options.output_file.as_deref().unwrap_or(file1);
+    let mut rejects = Rejects::new(output_path(report_base, "rejects"));
+    let progress = options.progress.as_deref();
+    if let Some(progress) = progress {
+        let size = |file: &str| fs::metadata(file).map_or(0, |m| m.len());
+        progress.start(size(file1) + size(file2));
+    }
+
+    // Count the rows of file2, so file1 can be checked against them both for membership
+    // and for holding each row the same number of times.
+    // Records of any length are accepted when a ragged policy or missing-as-null is active.
+    let flexible = options.ragged.is_flexible() || options.missing_as_null;
+    let pad = missing_fill(options).unwrap_or("");
+    let input2 = decode_reader(CountingReader::new(File::open(file2)?, progress), report.input_encoding2);
+    let mut reader2 = options.dialect.reader_builder().flexible(flexible).from_reader(input2);
+    let mut rows_in_file2: HashMap<Vec<String>, usize> = HashMap::new();
+    let mut keys_in_file2: HashSet<Vec<String>> = HashSet::new();
 
     // Retrieve the headers from file2 for later use in filtering.
     let headers2 = reader2.headers()?.clone();
+    let key_indices2 = key_indices(&headers2, &options.key_columns)?;
+    let side2 = Side { file: file2, locale: &options.locale2, kept_headers: kept_headers(&headers2, &options.ignore_columns) };
+    let progress2 = FileProgress::new(progress);
 
     // Iterate through the rows of file2 and filter the columns to be ignored.
-    for result in reader2.records() {
-        let record = result?;
-        let filtered_record: Vec<String> = filter_columns(&record, &headers2, ignore_columns);
-        rows_in_file2.insert(filtered_record); // Store filtered rows from file2
+    while let Some(record) = read_regular_record(&mut reader2, file2, headers2.len(), options.ragged, pad, &mut rejects)? {
+        if let Err(cancelled) = progress2.record() {
+            rejects.remove();
+            return Err(cancelled.into());
+        }
+        report.rows_file2 += 1;
+        let filtered_record: Vec<String> = filter_columns(&record, &headers2, &options.ignore_columns, missing_fill(options));
+        let line = record.position().map_or(0, |p| p.line());
+        let normalized = normalize_record(filtered_record, &side2, options, line, &mut report.number_failures);
+        *rows_in_file2.entry(normalized).or_default() += 1; // Count filtered rows from file2
+        if !key_indices2.is_empty() {
+            keys_in_file2.insert(record_key(&clear_null_markers(&record), &key_indices2));
+        }
     }
 
     // Prepare the name for the output file by modifying the original file1 name.
-    let output_file = format!("{}_modified.csv", file1.trim_end_matches(|c| c == '.' || c == 'c' || c == 's' || c == 'v' || c == 'C' || c == 'S' || c == 'V'));
-    
-    // Initialize a CSV writer for the output file.
-    let mut writer = WriterBuilder::new().from_path(&output_file)?;
+    let output_file = options.output_file.clone().unwrap_or_else(|| output_path(file1, "modified"));
 
-    // If the selected encoding is UTF-8 with BOM, write the BOM to the file.
-    if let Encoding::Utf8Bom = encoding {
-        writer.flush()?;  // Flush the buffer before writing the BOM.
-        let mut file = writer.into_inner()?; // Access the underlying file.
-        file.write_all(b"\xEF\xBB\xBF")?; // Write BOM bytes.
-        writer = WriterBuilder::new().from_writer(file); // Reinitialize writer after BOM.
-    }
+    // Initialize a CSV writer for the output file, encoding it (with its BOM, if any) as it is written.
+    let file = EncodedWriter::new(File::create(&output_file)?, options.encoding)?;
+    let mut writer = options.dialect.writer_builder().flexible(options.missing_as_null).from_writer(file);
+    let mut encoder = OutputEncoder::new(options.encoding, options.unencodable);
 
     // Write the original headers (from file1) to the new output file.
-    let mut reader1 = ReaderBuilder::new().from_path(file1)?;
-    writer.write_record(reader1.headers()?)?;
-
-    // Retrieve headers from file1 to be used in filtering.
+    let input1 = decode_reader(CountingReader::new(File::open(file1)?, progress), report.input_encoding1);
+    let mut reader1 = options.dialect.reader_builder().flexible(flexible).from_reader(input1);
     let headers1 = reader1.headers()?.clone();
+    writer.write_record(&*encoder.prepare(&headers1, &headers1, 1)?)?;
+
+    // Retrieve the key columns of file1.
+    let key_indices1 = key_indices(&headers1, &options.key_columns)?;
+    let side1 = Side { file: file1, locale: &options.locale1, kept_headers: kept_headers(&headers1, &options.ignore_columns) };
+    let progress1 = FileProgress::new(progress);
+    let mut surplus = false; // A row of file1 is found more often than in file2
 
     // Iterate through each row in file1.
-    for result in reader1.records() {
-        let record = result?;
+    while let Some(record) = read_regular_record(&mut reader1, file1, headers1.len(), options.ragged, pad, &mut rejects)? {
+        if let Err(cancelled) = progress1.record() {
+            // Leave no truncated output behind.
+            drop(writer);
+            let _ = fs::remove_file(&output_file);
+            rejects.remove();
+            return Err(cancelled.into());
+        }
+        report.rows_file1 += 1;
         // Filter columns based on the ignore list.
-        let filtered_record: Vec<String> = filter_columns(&record, &headers1, ignore_columns);
+        let filtered_record: Vec<String> = filter_columns(&record, &headers1, &options.ignore_columns, missing_fill(options));
+        let line = record.position().map_or(0, |p| p.line());
+        let normalized = normalize_record(filtered_record, &side1, options, line, &mut report.number_failures);
 
         // If the filtered row from file1 doesn't exist in file2, write it to the new file.
-        if !rows_in_file2.contains(&filtered_record) {
-            writer.write_record(&record)?; // Write the full row (not just filtered) to the output.
+        let Some(count) = rows_in_file2.get_mut(&normalized) else {
+            let record = clear_null_markers(&record);
+            writer.write_record(&*encoder.prepare(&record, &headers1, line)?)?; // Write the full row (not just filtered) to the output.
+            report.unique_rows += 1;
+            if !key_indices1.is_empty() {
+                if keys_in_file2.contains(&record_key(&record, &key_indices1)) {
+                    report.changed_rows += 1;
+                } else {
+                    report.added_rows += 1;
+                }
+            }
+            continue;
+        };
+        match count.checked_sub(1) {
+            Some(rest) => *count = rest,
+            None => surplus = true,
         }
     }
+    report.same_rows = report.unique_rows == 0 && !surplus && rows_in_file2.values().all(|&count| count == 0);
 
     writer.flush()?; // Ensure all data is written to the output file.
-    println!("Unique rows from '{}' written to '{}'", file1, output_file);
+    report.output_file = output_file;
+
+    // Write the cells that failed numeric parsing to a separate report.
+    if !report.number_failures.is_empty() {
+        let failures_file = output_path(report_base, "number_errors");
+        write_number_failures(&failures_file, &report.number_failures)?;
+        report.number_failures_file = Some(failures_file);
+    }
+
+    // List the cells that could not be written exactly in the output encoding.
+    report.unencodable_cells = encoder.cells;
+    if !report.unencodable_cells.is_empty() {
+        let unencodable_file = output_path(report_base, "unencodable");
+        write_unencodable_cells(&unencodable_file, &report.unencodable_cells)?;
+        report.unencodable_file = Some(unencodable_file);
+    }
+
+    // Summarize the records that were rejected or repaired.
+    rejects.flush()?;
+    report.rejected_rows = rejects.rejected;
+    report.repaired_rows = rejects.repaired;
+    if let Some(path) = rejects.path() {
+        report.rejects_file = Some(path.to_string());
+    }
 
+    Ok(report)
+}
+
+/// Reads the header row of a CSV file.
+///
+/// # Arguments
+///
+/// * `path` - The path to the CSV file.
+/// * `dialect` - The delimiter and quoting of the file.
+/// * `encoding` - The encoding of the file.
+///
+/// # Returns
+///
+/// The column names in file order, or an error if the file cannot be read.
+pub fn read_headers(path: &str, dialect: &Dialect, encoding: Encoding) -> Result<Vec<String>, Box<dyn Error>> {
+    let mut reader = dialect.reader_builder().from_reader(open_decoded(Path::new(path), encoding)?);
+    Ok(reader.headers()?.iter().map(|h| h.to_string()).collect())
+}
+
+/// Builds the path of a file derived from `file1`, e.g. `data_modified.csv`.
+///
+/// The `.csv` extension of `file1` (in any case) is stripped and `_<suffix>.csv` is appended.
+pub fn output_path(file1: &str, suffix: &str) -> String {
+    let stem = match file1.len().checked_sub(4) {
+        Some(i) if file1.is_char_boundary(i) && file1[i..].eq_ignore_ascii_case(".csv") => &file1[..i],
+        _ => file1,
+    };
+    format!("{}_{}.csv", stem, suffix)
+}
+
+/// Lists the files a comparison of `file1` may write: the output file (`output_file`,
+/// or `<file1>_modified.csv` by default) and the side reports named after it.
+pub fn written_files(file1: &str, output_file: Option<&str>) -> Vec<String> {
+    let report_base = output_file.unwrap_or(file1);
+    let mut files = vec![output_file.map_or_else(|| output_path(file1, "modified"), str::to_string)];
+    files.extend(["rejects", "number_errors", "unencodable"].iter().map(|suffix| output_path(report_base, suffix)));
+    files
+}
+
+/// Finds the positions of the key columns in a header row.
+///
+/// # Arguments
+///
+/// * `headers` - The header row of the file.
+/// * `key_columns` - The names of the columns that identify a row.
+///
+/// # Returns
+///
+/// The index of each key column, in the order given, or an error naming the first
+/// key column missing from the headers.
+pub fn key_indices(headers: &StringRecord, key_columns: &[String]) -> Result<Vec<usize>, String> {
+    key_columns.iter()
+        .map(|key| headers.iter()
+            .position(|h| h == key)
+            .ok_or_else(|| format!("Key column '{}' not found in headers", key)))
+        .collect()
+}
+
+/// Extracts the key of a record from the given key column positions.
+///
+/// Missing fields (in short records) yield empty key parts.
+pub fn record_key(record: &StringRecord, indices: &[usize]) -> Vec<String> {
+    indices.iter()
+        .map(|&i| record.get(i).unwrap_or("").to_string())
+        .collect()
+}
+
+/// Writes the list of cells that failed numeric parsing as a CSV report.
+fn write_number_failures(path: &str, failures: &[NumberParseFailure]) -> Result<(), Box<dyn Error>> {
+    let mut writer = WriterBuilder::new().from_path(path)?;
+    writer.write_record(["file", "line", "column", "value"])?;
+    for failure in failures {
+        writer.write_record([failure.file.as_str(), &failure.line.to_string(), failure.column.as_str(), failure.value.as_str()])?;
+    }
+    writer.flush()?;
     Ok(())
 }
 
+/// Writes the list of cells changed to fit the output encoding as a CSV report.
+fn write_unencodable_cells(path: &str, cells: &[UnencodableCell]) -> Result<(), Box<dyn Error>> {
+    let mut writer = WriterBuilder::new().from_path(path)?;
+    writer.write_record(["line", "column", "value", "written"])?;
+    for cell in cells {
+        writer.write_record([&cell.line.to_string(), cell.column.as_str(), cell.value.as_str(), cell.written.as_str()])?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Returns the headers that remain after removing the ignored columns, in file order.
+pub(crate) fn kept_headers(headers: &StringRecord, ignore_columns: &HashSet<String>) -> Vec<String> {
+    headers.iter()
+        .filter(|h| !ignore_columns.contains(*h))
+        .map(|h| h.to_string())
+        .collect()
+}
+
+/// Per-file context needed to normalize the records of one side of a comparison.
+pub(crate) struct Side<'a> {
+    pub(crate) file: &'a str,             // Path of the file the records come from
+    pub(crate) locale: &'a NumberLocale,  // Number format of the file
+    pub(crate) kept_headers: Vec<String>, // Headers left after removing ignored columns
+}
+
+/// Returns the value used for fields missing from short records, if they should be kept.
+pub(crate) fn missing_fill(options: &CompareOptions) -> Option<&'static str> {
+    if options.missing_as_null { Some(NULL_MARKER) } else { None }
+}
+
+/// Normalizes the fields of a filtered record so equivalent values compare equal.
+///
+/// Fields matching a null token are replaced by a common marker. Fields in numeric
+/// columns are parsed with the file's locale and replaced by their canonical numeric
+/// form; fields that fail to parse are kept as text and recorded in `failures`.
+/// Other fields are returned unchanged.
+pub(crate) fn normalize_record(
+    filtered: Vec<String>,
+    side: &Side,
+    options: &CompareOptions,
+    line: u64,
+    failures: &mut Vec<NumberParseFailure>,
+) -> Vec<String> {
+    if options.numeric_columns.is_empty() && options.null_tokens.is_empty() {
+        return filtered;
+    }
+
+    filtered.into_iter()
+        .zip(&side.kept_headers)
+        .map(|(field, header)| {
+            if field == NULL_MARKER || options.null_tokens.is_null(header, &field) {
+                return NULL_MARKER.to_string();
+            }
+            if !options.numeric_columns.contains(header) || field.trim().is_empty() {
+                return field;
+            }
+            match side.locale.parse(&field) {
+                Some(value) => canonical_number(value, options.round_to),
+                None => {
+                    failures.push(NumberParseFailure {
+                        file: side.file.to_string(),
+                        line,
+                        column: header.clone(),
+                        value: field.clone(),
+                    });
+                    field
+                }
+            }
+        })
+        .collect()
+}
+
 /// Filters out ignored columns from a CSV record based on the provided set of column names.
 ///
 /// This function takes a CSV record and its associated headers and filters out the
@@ -84,11 +413,13 @@ pub fn compare_and_write_unique_rows(file1: &str, file2: &str, ignore_columns: &
 /// * `record` - The CSV record to filter.
 /// * `headers` - The headers associated with the CSV file.
 /// * `ignore_columns` - A set of column names to ignore during filtering.
+/// * `missing` - The value to use for fields absent from a short record. With `None`
+///   absent fields are skipped, so the result is shorter than the header list.
 ///
 /// # Returns
 ///
 /// A `Vec<String>` containing only the columns that were not ignored.
-fn filter_columns(record: &StringRecord, headers: &StringRecord, ignore_columns: &HashSet<String>) -> Vec<String> {
+pub(crate) fn filter_columns(record: &StringRecord, headers: &StringRecord, ignore_columns: &HashSet<String>, missing: Option<&str>) -> Vec<String> {
     let mut filtered_record = Vec::new();
 
     // Iterate through each column in the record by index.
@@ -98,9 +429,158 @@ fn filter_columns(record: &StringRecord, headers: &StringRecord, ignore_columns:
             // Add the field from the record to the filtered list if it exists.
             if let Some(field) = record.get(i) {
                 filtered_record.push(field.to_string());
+            } else if let Some(fill) = missing {
+                filtered_record.push(fill.to_string());
             }
         }
     }
 
     filtered_record
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::progress::Cancelled;
+    use crate::test_util::TempDir;
+
+    #[test]
+    fn output_path_strips_only_the_csv_extension() {
+        assert_eq!(output_path("data.csv", "modified"), "data_modified.csv");
+        assert_eq!(output_path("DATA.CSV", "rejects"), "DATA_rejects.csv");
+        assert_eq!(output_path("sales.csv", "modified"), "sales_modified.csv");
+        assert_eq!(output_path("ours", "merged"), "ours_merged.csv");
+        assert_eq!(output_path("é.csv", "merged"), "é_merged.csv");
+        assert_eq!(output_path("é", "merged"), "é_merged.csv");
+    }
+
+    #[test]
+    fn compares_numbers_by_value_and_reports_parse_failures() {
+        let dir = TempDir::new();
+        let file1 = dir.write("eu.csv", "id;amount\n1;1.234,50\n2;0,996\n3;abc\n4;7\n");
+        let file2 = dir.write("us.csv", "id;amount\n1;1,234.5\n2;1.004\n3;abc\n4;7.5\n");
+        let options = CompareOptions {
+            numeric_columns: HashSet::from(["amount".to_string()]),
+            locale1: NumberLocale::european(),
+            locale2: NumberLocale::us(),
+            round_to: Some(0.01),
+            dialect: Dialect { delimiter: ';', ..Dialect::default() },
+            ..CompareOptions::default()
+        };
+
+        let report = compare_with_options(&file1, &file2, &options).unwrap();
+        assert_eq!(dir.read("eu_modified.csv"), "id;amount\n4;7\n");
+        assert_eq!(report.number_failures_file.as_deref(), Some(dir.path("eu_number_errors.csv").as_str()));
+        assert_eq!(
+            dir.read("eu_number_errors.csv"),
+            format!("file,line,column,value\n{},4,amount,abc\n{},4,amount,abc\n", file2, file1)
+        );
+    }
+
+    #[test]
+    fn null_tokens_compare_equal() {
+        let dir = TempDir::new();
+        let file1 = dir.write("a.csv", "id,value\n1,N/A\n2, - \n3,x\n4,\n");
+        let file2 = dir.write("b.csv", "id,value\n1,null\n2,\\N\n3,y\n4,NULL\n");
+
+        compare_with_options(&file1, &file2, &CompareOptions::default()).unwrap();
+        assert_eq!(dir.read("a_modified.csv"), "id,value\n1,N/A\n2, - \n3,x\n4,\n");
+
+        let options = CompareOptions { null_tokens: NullTokens::standard(), ..CompareOptions::default() };
+        let report = compare_with_options(&file1, &file2, &options).unwrap();
+        assert_eq!(report.unique_rows, 1);
+        assert_eq!(dir.read("a_modified.csv"), "id,value\n3,x\n");
+    }
+
+    #[test]
+    fn missing_fields_are_null_in_their_own_column() {
+        let dir = TempDir::new();
+        // Each missing field becomes a null in its own column, so the short `1` equals
+        // `1,x,NULL` once `name` is ignored, and `3` equals `3,,NULL`.
+        let file1 = dir.write("a.csv", "id,name,note\n1,x,NULL\n2,y,\n3\n");
+        let file2 = dir.write("b.csv", "id,name,note\n1\n2,y\n3,,NULL\n");
+        let options = CompareOptions {
+            ignore_columns: HashSet::from(["name".to_string()]),
+            null_tokens: NullTokens::from_tokens(["NULL"]),
+            missing_as_null: true,
+            ..CompareOptions::default()
+        };
+
+        let report = compare_with_options(&file1, &file2, &options).unwrap();
+        assert_eq!((report.rows_file1, report.rows_file2, report.unique_rows), (3, 3, 1));
+        assert_eq!(dir.read("a_modified.csv"), "id,name,note\n2,y,\n");
+    }
+
+    #[test]
+    fn padded_fields_are_null_when_missing_fields_are() {
+        let dir = TempDir::new();
+        let file1 = dir.write("a.csv", "id,name,note\n1,x,NULL\n2,y\n");
+        let file2 = dir.write("b.csv", "id,name,note\n1,x\n");
+        let options = CompareOptions {
+            null_tokens: NullTokens::from_tokens(["NULL"]),
+            missing_as_null: true,
+            ragged: RaggedPolicy::Pad,
+            ..CompareOptions::default()
+        };
+
+        let report = compare_with_options(&file1, &file2, &options).unwrap();
+        assert_eq!((report.unique_rows, report.repaired_rows), (1, 2));
+        // The padded field is written empty, not as the null marker.
+        assert_eq!(dir.read("a_modified.csv"), "id,name,note\n2,y,\n");
+    }
+
+    #[test]
+    fn progress_counts_the_bytes_on_disk() {
+        let dir = TempDir::new();
+        let utf16: Vec<u8> = [0xFF, 0xFE].into_iter()
+            .chain("id,name\n1,é\n2,ü\n".encode_utf16().flat_map(|u| u.to_le_bytes()))
+            .collect();
+        let file1 = dir.write("utf16.csv", &utf16);
+        let file2 = dir.write("utf8.csv", "id,name\n1,é\n");
+        let progress = Arc::new(Progress::new());
+        let options = CompareOptions { progress: Some(progress.clone()), ..CompareOptions::default() };
+
+        let report = compare_with_options(&file1, &file2, &options).unwrap();
+        assert_eq!(report.unique_rows, 1);
+        assert_eq!(progress.rows(), 3);
+        assert_eq!(progress.total_bytes(), utf16.len() as u64 + 13);
+        assert_eq!(progress.bytes_read(), progress.total_bytes());
+        assert_eq!(progress.fraction(), Some(1.0));
+    }
+
+    #[test]
+    fn cancelling_removes_the_partial_output() {
+        let dir = TempDir::new();
+        let file1 = dir.write("a.csv", "id\n1\n2\n");
+        let file2 = dir.write("b.csv", "id\n");
+        let progress = Arc::new(Progress::new());
+        progress.cancel();
+        let options = CompareOptions { progress: Some(progress), ..CompareOptions::default() };
+
+        let error = compare_with_options(&file1, &file2, &options).unwrap_err();
+        assert!(error.is::<Cancelled>());
+        assert!(!Path::new(&dir.path("a_modified.csv")).exists());
+    }
+
+    #[test]
+    fn cancelling_removes_the_rejects_file() {
+        let dir = TempDir::new();
+        let file1 = dir.write("a.csv", "id,name\n1\n2,b\n");
+        let progress = Arc::new(Progress::new());
+        progress.cancel();
+        let options = CompareOptions { progress: Some(progress), ragged: RaggedPolicy::Reject, ..CompareOptions::default() };
+
+        // Cancelled while reading file1, after its short record was rejected.
+        let file2 = dir.write("b.csv", "id,name\n");
+        let error = compare_with_options(&file1, &file2, &options).unwrap_err();
+        assert!(error.is::<Cancelled>());
+        assert!(!Path::new(&dir.path("a_modified.csv")).exists());
+        assert!(!Path::new(&dir.path("a_rejects.csv")).exists());
+
+        // Cancelled while reading file2, after its short record was rejected.
+        let file2 = dir.write("b.csv", "id,name\n3\n4,d\n");
+        let error = compare_with_options(&file1, &file2, &options).unwrap_err();
+        assert!(error.is::<Cancelled>());
+        assert!(!Path::new(&dir.path("a_rejects.csv")).exists());
+    }
+}
@@ -0,0 +1,192 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+use csv::{ReaderBuilder, WriterBuilder};
+use serde::{Deserialize, Serialize};
+
+/// The CSV dialect of a file: delimiter, quoting and whitespace handling.
+///
+/// The default is standard comma-separated values with `"` quoting.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Dialect {
+    pub delimiter: char,          // Field separator, e.g. `,`, `;` or a tab
+    pub quote: char,              // Quote character
+    pub escape: Option<char>,     // Escape character, if quotes are escaped instead of doubled
+    pub trim: bool,               // Trim whitespace around fields when reading
+}
+
+impl Default for Dialect {
+    fn default() -> Self {
+        Self { delimiter: ',', quote: '"', escape: None, trim: false }
+    }
+}
+
+impl Dialect {
+    /// Checks that the delimiter, quote and escape characters are single ASCII bytes.
+    pub fn validate(&self) -> Result<(), String> {
+        for c in [Some(self.delimiter), Some(self.quote), self.escape].into_iter().flatten() {
+            if !c.is_ascii() {
+                return Err(format!("Dialect character '{}' must be ASCII", c));
+            }
+        }
+        Ok(())
+    }
+
+    /// Guesses the dialect of a sample of CSV text.
+    ///
+    /// Each common delimiter (comma, semicolon, tab and pipe) is counted outside
+    /// double-quoted fields on the first lines of the sample. The delimiter that occurs
+    /// the same number of times on the most lines wins, ties going to the higher count.
+    /// A sample without any of them is assumed to be comma-separated.
+    pub fn sniff(sample: &str) -> Self {
+        let lines: Vec<&str> = sample.lines().filter(|l| !l.trim().is_empty()).take(20).collect();
+        let mut best = (',', 0, 0); // Delimiter, lines agreeing on its count, and that count
+
+        for candidate in [',', ';', '\t', '|'] {
+            let mut frequency: HashMap<usize, usize> = HashMap::new();
+            for line in &lines {
+                let count = count_unquoted(line, candidate);
+                if count > 0 {
+                    *frequency.entry(count).or_default() += 1;
+                }
+            }
+            if let Some((count, agreeing)) = frequency.into_iter().max_by_key(|&(count, agreeing)| (agreeing, count)) {
+                if (agreeing, count) > (best.1, best.2) {
+                    best = (candidate, agreeing, count);
+                }
+            }
+        }
+
+        Self { delimiter: best.0, ..Self::default() }
+    }
+
+    /// Returns a `ReaderBuilder` configured for this dialect.
+    pub fn reader_builder(&self) -> ReaderBuilder {
+        let mut builder = ReaderBuilder::new();
+        builder.delimiter(self.delimiter as u8)
+            .quote(self.quote as u8)
+            .escape(self.escape.map(|c| c as u8))
+            .double_quote(self.escape.is_none());
+        if self.trim {
+            builder.trim(csv::Trim::All);
+        }
+        builder
+    }
+
+    /// Returns a `WriterBuilder` configured for this dialect.
+    pub fn writer_builder(&self) -> WriterBuilder {
+        let mut builder = WriterBuilder::new();
+        builder.delimiter(self.delimiter as u8)
+            .quote(self.quote as u8)
+            .double_quote(self.escape.is_none());
+        if let Some(escape) = self.escape {
+            builder.escape(escape as u8);
+        }
+        builder
+    }
+}
+
+impl FromStr for Dialect {
+    type Err = String;
+
+    /// Parses a dialect from a delimiter name or character: `comma`, `semicolon`,
+    /// `tab`, `pipe`, or any single ASCII character such as `;`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let delimiter = match s {
+            "comma" | "csv" => ',',
+            "semicolon" => ';',
+            "tab" | "tsv" | "\\t" => '\t',
+            "pipe" => '|',
+            _ => {
+                let mut chars = s.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) if c.is_ascii() => c,
+                    _ => return Err(format!("Invalid delimiter '{}'. Use comma, semicolon, tab, pipe or a single ASCII character.", s)),
+                }
+            }
+        };
+        Ok(Self { delimiter, ..Self::default() })
+    }
+}
+
+/// Counts the occurrences of `delimiter` in a line, skipping double-quoted text.
+fn count_unquoted(line: &str, delimiter: char) -> usize {
+    let mut quoted = false;
+    let mut count = 0;
+    for c in line.chars() {
+        if c == '"' {
+            quoted = !quoted;
+        } else if c == delimiter && !quoted {
+            count += 1;
+        }
+    }
+    count
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writes `records` in `dialect`, reads them back and returns the written text.
+    fn round_trip(dialect: Dialect, records: &[[&str; 3]]) -> String {
+        let mut writer = dialect.writer_builder().has_headers(false).from_writer(Vec::new());
+        for record in records {
+            writer.write_record(record).unwrap();
+        }
+        let text = String::from_utf8(writer.into_inner().unwrap()).unwrap();
+
+        let mut reader = dialect.reader_builder().has_headers(false).from_reader(text.as_bytes());
+        let read: Vec<Vec<String>> = reader.records()
+            .map(|r| r.unwrap().iter().map(str::to_string).collect())
+            .collect();
+        assert_eq!(read, records.iter().map(|r| r.map(str::to_string).to_vec()).collect::<Vec<_>>());
+        text
+    }
+
+    #[test]
+    fn round_trips_delimiters_quotes_and_line_terminators() {
+        let records = [["id", "name", "note"], ["1", "a;b", "say \"hi\""], ["2", "line\nbreak", "crlf\r\nend"]];
+
+        let text = round_trip(Dialect::default(), &records);
+        assert!(text.starts_with("id,name,note\n1,a;b,\"say \"\"hi\"\"\"\n"), "{}", text);
+
+        let semicolon = Dialect { delimiter: ';', quote: '\'', ..Dialect::default() };
+        let text = round_trip(semicolon, &records);
+        assert!(text.starts_with("id;name;note\n1;'a;b';say \"hi\"\n"), "{}", text);
+
+        let escaped = Dialect { delimiter: '\t', escape: Some('\\'), ..Dialect::default() };
+        let text = round_trip(escaped, &records);
+        assert!(text.starts_with("id\tname\tnote\n1\ta;b\t\"say \\\"hi\\\"\"\n"), "{}", text);
+    }
+
+    #[test]
+    fn trims_fields_when_reading() {
+        let dialect = Dialect { trim: true, ..Dialect::default() };
+        let mut reader = dialect.reader_builder().from_reader(" id , name \n 1 , a \n".as_bytes());
+        assert_eq!(reader.headers().unwrap(), vec!["id", "name"]);
+        assert_eq!(reader.records().next().unwrap().unwrap(), vec!["1", "a"]);
+    }
+
+    #[test]
+    fn sniffs_the_delimiter() {
+        assert_eq!(Dialect::sniff("id;name;note\n1;a;b\n2;c;d\n").delimiter, ';');
+        assert_eq!(Dialect::sniff("id\tname\n1\ta, b\n2\tc\n").delimiter, '\t');
+        assert_eq!(Dialect::sniff("id|name|note\n1|a|b\n").delimiter, '|');
+        assert_eq!(Dialect::sniff("id,name\n1,\"a;b;c\"\n2,d\n").delimiter, ',');
+        assert_eq!(Dialect::sniff("id\n1\n").delimiter, ',');
+        assert_eq!(Dialect::sniff("").delimiter, ',');
+    }
+
+    #[test]
+    fn parses_and_validates_dialects() {
+        assert_eq!("semicolon".parse::<Dialect>().unwrap().delimiter, ';');
+        assert_eq!("tab".parse::<Dialect>().unwrap().delimiter, '\t');
+        assert_eq!("|".parse::<Dialect>().unwrap().delimiter, '|');
+        assert!(";;".parse::<Dialect>().unwrap_err().starts_with("Invalid delimiter ';;'"));
+        assert!("é".parse::<Dialect>().is_err());
+
+        assert!(Dialect::default().validate().is_ok());
+        let dialect = Dialect { escape: Some('§'), ..Dialect::default() };
+        assert_eq!(dialect.validate().unwrap_err(), "Dialect character '§' must be ASCII");
+    }
+}
@@ -0,0 +1,244 @@
+use std::error::Error;
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::path::Path;
+use csv::{Reader, StringRecord};
+use serde::Serialize;
+use crate::csv_compare::{filter_columns, key_indices, kept_headers, missing_fill, normalize_record, record_key, CompareOptions, Side};
+use crate::encoding::{decode_reader, resolve_input_encoding, DecodedReader, InputEncoding};
+use crate::nulls::clear_null_markers;
+use crate::number::NumberLocale;
+use crate::progress::{CountingReader, FileProgress};
+use crate::ragged::{read_regular_record, Rejects};
+
+/// How a row differs between the two files of a comparison.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DiffKind {
+    /// The row is only in the first file.
+    Added,
+    /// The row is only in the second file.
+    Removed,
+    /// Both files have a row with the same key, but with different values.
+    Changed,
+}
+
+/// One row of a side-by-side diff.
+#[derive(Debug, Clone, Serialize)]
+pub struct DiffRow {
+    pub kind: DiffKind,              // How the row differs
+    pub left_line: Option<u64>,      // Line of the row in the first file, if present there
+    pub right_line: Option<u64>,     // Line of the row in the second file, if present there
+    pub left: Vec<String>,           // Cells of the first file's row, by `DiffModel::columns` (empty if absent)
+    pub right: Vec<String>,          // Cells of the second file's row, by `DiffModel::columns` (empty if absent)
+    pub changed: Vec<usize>,         // Positions in `DiffModel::columns` of the cells that differ
+}
+
+impl DiffRow {
+    /// Returns `true` if the row is on line `query` of either file, or one of its cells
+    /// contains `query` (ignoring case).
+    pub fn matches(&self, query: &str) -> bool {
+        let query = query.trim();
+        if let Ok(line) = query.parse::<u64>() {
+            if self.left_line == Some(line) || self.right_line == Some(line) {
+                return true;
+            }
+        }
+        let query = query.to_lowercase();
+        self.left.iter().chain(&self.right).any(|cell| cell.to_lowercase().contains(&query))
+    }
+}
+
+/// The rows that differ between two CSV files, ready to be shown side by side.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DiffModel {
+    pub columns: Vec<String>, // Columns of the first file, then those found only in the second
+    pub rows: Vec<DiffRow>,   // Differing rows: those of the first file in file order, then removed rows
+}
+
+impl DiffModel {
+    /// Returns the number of rows of the given kind.
+    pub fn count(&self, kind: DiffKind) -> usize {
+        self.rows.iter().filter(|row| row.kind == kind).count()
+    }
+}
+
+/// A row of one file that has no equal row in the other file.
+struct Unmatched {
+    record: StringRecord,     // The row as read
+    normalized: Vec<String>,  // Compared values, by the kept headers of its file
+    key: Vec<String>,         // Values of the key columns (empty without key columns)
+}
+
+/// A record with its compared values.
+type ComparedRecord = (StringRecord, Vec<String>);
+
+/// Reads the records of one side of the diff and their compared values.
+struct SideReader<'a> {
+    reader: Reader<DecodedReader<CountingReader<'a, File>>>, // Reader positioned after the header row
+    headers: StringRecord,                                   // Header row of the file
+    keys: Vec<usize>,                                        // Positions of the key columns
+    side: Side<'a>,                                          // Context for normalizing the records
+    progress: FileProgress<'a>,                              // Reports the records read
+}
+
+impl<'a> SideReader<'a> {
+    /// Opens a file, decoding it from `encoding`, and reads its headers.
+    fn open(file: &'a str, locale: &'a NumberLocale, encoding: InputEncoding, options: &'a CompareOptions) -> Result<Self, Box<dyn Error>> {
+        let flexible = options.ragged.is_flexible() || options.missing_as_null;
+        let encoding = resolve_input_encoding(Path::new(file), encoding)?;
+        let input = decode_reader(CountingReader::new(File::open(file)?, options.progress.as_deref()), encoding);
+        let mut reader = options.dialect.reader_builder().flexible(flexible).from_reader(input);
+        let headers = reader.headers()?.clone();
+        let keys = key_indices(&headers, &options.key_columns).map_err(|e| format!("{} in '{}'", e, file))?;
+        let side = Side { file, locale, kept_headers: kept_headers(&headers, &options.ignore_columns) };
+        Ok(Self { reader, headers, keys, side, progress: FileProgress::new(options.progress.as_deref()) })
+    }
+
+    /// Returns the next record with its compared values, or `None` at the end of the file.
+    fn next(&mut self, options: &CompareOptions, rejects: &mut Rejects) -> Result<Option<ComparedRecord>, Box<dyn Error>> {
+        let pad = missing_fill(options).unwrap_or("");
+        let Some(record) = read_regular_record(&mut self.reader, self.side.file, self.headers.len(), options.ragged, pad, rejects)? else {
+            return Ok(None);
+        };
+        self.progress.record()?;
+        let filtered = filter_columns(&record, &self.headers, &options.ignore_columns, missing_fill(options));
+        let line = record.position().map_or(0, |p| p.line());
+        let normalized = normalize_record(filtered, &self.side, options, line, &mut Vec::new());
+        Ok(Some((clear_null_markers(&record).into_owned(), normalized)))
+    }
+}
+
+/// Builds the side-by-side diff of two CSV files.
+///
+/// Rows are compared exactly like `compare_with_options` does, so the added rows are the
+/// rows it writes to its output file. Rows of the second file not found in the first are
+/// removed rows. When `options.key_columns` is set, an added and a removed row with the
+/// same key are paired into one changed row, with the differing cells listed. Irregular
+/// records are handled according to `options.ragged` but not written to a rejects file.
+///
+/// The second file is read twice, so only the differing rows are kept in memory.
+///
+/// # Arguments
+///
+/// * `file1` - The path to the first CSV file.
+/// * `file2` - The path to the second CSV file.
+/// * `options` - The comparison options.
+///
+/// # Returns
+///
+/// The `DiffModel`, or an error if the files cannot be read or the comparison is cancelled.
+pub fn diff_files(file1: &str, file2: &str, options: &CompareOptions) -> Result<DiffModel, Box<dyn Error>> {
+    let mut rejects = Rejects::discard();
+    if let Some(progress) = options.progress.as_deref() {
+        let size = |file: &str| fs::metadata(file).map_or(0, |m| m.len());
+        progress.start(size(file1) + 2 * size(file2));
+    }
+
+    // Collect the compared values of the second file.
+    let mut right = SideReader::open(file2, &options.locale2, options.input_encoding2, options)?;
+    let mut rows_in_file2: HashSet<Vec<String>> = HashSet::new();
+    while let Some((_, normalized)) = right.next(options, &mut rejects)? {
+        rows_in_file2.insert(normalized);
+    }
+
+    // Keep the rows of the first file that are not in the second one.
+    let mut left = SideReader::open(file1, &options.locale1, options.input_encoding1, options)?;
+    let mut rows_in_file1: HashSet<Vec<String>> = HashSet::new();
+    let mut added = Vec::new();
+    while let Some((record, normalized)) = left.next(options, &mut rejects)? {
+        if !rows_in_file2.contains(&normalized) {
+            added.push(Unmatched { key: record_key(&record, &left.keys), record, normalized: normalized.clone() });
+        }
+        rows_in_file1.insert(normalized);
+    }
+    drop(rows_in_file2);
+
+    // Read the second file again for its rows that are not in the first one.
+    let mut right = SideReader::open(file2, &options.locale2, options.input_encoding2, options)?;
+    let mut removed = Vec::new();
+    while let Some((record, normalized)) = right.next(options, &mut rejects)? {
+        if !rows_in_file1.contains(&normalized) {
+            removed.push(Unmatched { key: record_key(&record, &right.keys), record, normalized });
+        }
+    }
+
+    // Show every column of the first file, then those only the second file has.
+    let mut columns: Vec<String> = left.headers.iter().map(|h| h.to_string()).collect();
+    for header in right.headers.iter() {
+        if !columns.iter().any(|c| c == header) {
+            columns.push(header.to_string());
+        }
+    }
+    let left_cells = cell_mapper(&columns, &left.headers);
+    let right_cells = cell_mapper(&columns, &right.headers);
+
+    // Pair added and removed rows sharing a key into changed rows.
+    let mut removed_by_key: HashMap<&Vec<String>, usize> = HashMap::new();
+    if !options.key_columns.is_empty() {
+        for (i, row) in removed.iter().enumerate() {
+            removed_by_key.entry(&row.key).or_insert(i);
+        }
+    }
+    let mut paired = vec![false; removed.len()];
+    let mut model = DiffModel { columns: columns.clone(), rows: Vec::with_capacity(added.len() + removed.len()) };
+
+    for row in &added {
+        let mut diff = DiffRow {
+            kind: DiffKind::Added,
+            left_line: row.record.position().map(|p| p.line()),
+            right_line: None,
+            left: left_cells(&row.record),
+            right: Vec::new(),
+            changed: Vec::new(),
+        };
+        if let Some(&i) = removed_by_key.get(&row.key) {
+            if !paired[i] {
+                paired[i] = true;
+                let other = &removed[i];
+                diff.kind = DiffKind::Changed;
+                diff.right_line = other.record.position().map(|p| p.line());
+                diff.right = right_cells(&other.record);
+                diff.changed = changed_columns(&columns, (&left.side.kept_headers, &row.normalized), (&right.side.kept_headers, &other.normalized));
+            }
+        }
+        model.rows.push(diff);
+    }
+    for (row, _) in removed.iter().zip(&paired).filter(|(_, &paired)| !paired) {
+        model.rows.push(DiffRow {
+            kind: DiffKind::Removed,
+            left_line: None,
+            right_line: row.record.position().map(|p| p.line()),
+            left: Vec::new(),
+            right: right_cells(&row.record),
+            changed: Vec::new(),
+        });
+    }
+
+    Ok(model)
+}
+
+/// Returns a function laying out the cells of a record by the diff columns.
+fn cell_mapper<'a>(columns: &[String], headers: &StringRecord) -> impl Fn(&StringRecord) -> Vec<String> + 'a {
+    let positions: Vec<Option<usize>> = columns.iter().map(|c| headers.iter().position(|h| h == c)).collect();
+    move |record| positions.iter()
+        .map(|p| p.and_then(|i| record.get(i)).unwrap_or("").to_string())
+        .collect()
+}
+
+/// Finds the columns whose compared values differ between two rows.
+fn changed_columns(columns: &[String], left: (&[String], &[String]), right: (&[String], &[String])) -> Vec<usize> {
+    let right_values: HashMap<&String, &String> = right.0.iter().zip(right.1).collect();
+    let mut changed: Vec<usize> = left.0.iter().zip(left.1)
+        .filter(|(header, value)| right_values.get(header) != Some(value))
+        .filter_map(|(header, _)| columns.iter().position(|c| c == header))
+        .collect();
+    // Compared columns the first file does not have also differ.
+    for header in right.0.iter().filter(|h| !left.0.contains(h)) {
+        if let Some(i) = columns.iter().position(|c| c == header) {
+            changed.push(i);
+        }
+    }
+    changed.sort_unstable();
+    changed
+}
@@ -0,0 +1,192 @@
+// src/diff_view.rs
+
+use eframe::egui::{self, Color32, RichText};
+use rust_csv_scripts::diff::{DiffKind, DiffModel, DiffRow};
+
+const CELL_WIDTH: f32 = 120.0;  // Width of one cell in the diff table
+const LINE_WIDTH: f32 = 50.0;   // Width of the line number columns
+const KIND_WIDTH: f32 = 70.0;   // Width of the column naming the kind of difference
+const CELL_CHARS: usize = 16;   // Longer cells are shortened (the full text shows on hover)
+
+/// A window showing the differing rows of two files side by side.
+///
+/// Only the visible rows are drawn, so diffs with millions of rows stay responsive.
+pub struct DiffView {
+    model: DiffModel,             // The differing rows
+    title: String,                // Window title naming the compared files
+    show_added: bool,             // Whether rows only in the first file are listed
+    show_removed: bool,           // Whether rows only in the second file are listed
+    show_changed: bool,           // Whether rows changed between the files are listed
+    visible: Vec<usize>,          // Indices into `model.rows` passing the filters
+    query: String,                // Line number or text to search for
+    selected: Option<usize>,      // Position in `visible` of the last search hit
+    scroll_to: Option<usize>,     // Position in `visible` to scroll to on the next frame
+    search_status: String,        // Result of the last search
+}
+
+impl DiffView {
+    /// Creates a view listing all rows of the diff.
+    pub fn new(model: DiffModel, title: String) -> Self {
+        let mut view = Self {
+            model,
+            title,
+            show_added: true,
+            show_removed: true,
+            show_changed: true,
+            visible: Vec::new(),
+            query: String::new(),
+            selected: None,
+            scroll_to: None,
+            search_status: String::new(),
+        };
+        view.refilter();
+        view
+    }
+
+    /// Recomputes the rows passing the kind filters.
+    fn refilter(&mut self) {
+        self.visible = self.model.rows.iter()
+            .enumerate()
+            .filter(|(_, row)| match row.kind {
+                DiffKind::Added => self.show_added,
+                DiffKind::Removed => self.show_removed,
+                DiffKind::Changed => self.show_changed,
+            })
+            .map(|(i, _)| i)
+            .collect();
+        self.selected = None;
+    }
+
+    /// Selects and scrolls to the next visible row matching the search, wrapping around.
+    fn find_next(&mut self) {
+        if self.query.trim().is_empty() || self.visible.is_empty() {
+            return;
+        }
+        let start = self.selected.map_or(0, |s| s + 1);
+        let count = self.visible.len();
+        let hit = (0..count)
+            .map(|offset| (start + offset) % count)
+            .find(|&pos| self.model.rows[self.visible[pos]].matches(&self.query));
+        match hit {
+            Some(pos) => {
+                self.selected = Some(pos);
+                self.scroll_to = Some(pos);
+                self.search_status = format!("Row {} of {}", pos + 1, count);
+            }
+            None => self.search_status = "No match".to_string(),
+        }
+    }
+
+    /// Draws the window. Returns `false` once the user has closed it.
+    pub fn show(&mut self, ctx: &egui::Context) -> bool {
+        let mut open = true;
+        egui::Window::new(&self.title)
+            .open(&mut open)
+            .default_size([900.0, 500.0])
+            .resizable(true)
+            .show(ctx, |ui| {
+                // Filters by kind of difference, with their counts
+                ui.horizontal(|ui| {
+                    let mut changed = false;
+                    changed |= ui.checkbox(&mut self.show_added, format!("Added ({})", self.model.count(DiffKind::Added))).changed();
+                    changed |= ui.checkbox(&mut self.show_removed, format!("Removed ({})", self.model.count(DiffKind::Removed))).changed();
+                    changed |= ui.checkbox(&mut self.show_changed, format!("Changed ({})", self.model.count(DiffKind::Changed))).changed();
+                    if changed {
+                        self.refilter();
+                    }
+                });
+
+                // Jump to a line number or search the cells
+                ui.horizontal(|ui| {
+                    ui.label("Find (line or text):");
+                    let response = ui.text_edit_singleline(&mut self.query);
+                    let entered = response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                    if ui.button("Find Next").clicked() || entered {
+                        self.find_next();
+                    }
+                    ui.label(&self.search_status);
+                });
+                ui.separator();
+
+                self.table(ui);
+            });
+        open
+    }
+
+    /// Draws the header and the visible rows of the diff table.
+    fn table(&mut self, ui: &mut egui::Ui) {
+        let row_height = ui.text_style_height(&egui::TextStyle::Body);
+        let row_step = row_height + ui.spacing().item_spacing.y;
+        let columns = &self.model.columns;
+
+        egui::ScrollArea::horizontal().show(ui, |ui| {
+            // Column headers for both sides
+            ui.horizontal(|ui| {
+                ui.add_sized([KIND_WIDTH, row_height], egui::Label::new(RichText::new("Kind").strong()));
+                for side in ["Line 1", "Line 2"] {
+                    ui.add_sized([LINE_WIDTH, row_height], egui::Label::new(RichText::new(side).strong()));
+                    for column in columns {
+                        ui.add_sized([CELL_WIDTH, row_height], egui::Label::new(RichText::new(shorten(column)).strong()))
+                            .on_hover_text(column);
+                    }
+                    ui.separator();
+                }
+            });
+
+            let mut area = egui::ScrollArea::vertical().auto_shrink([false, false]);
+            if let Some(pos) = self.scroll_to.take() {
+                area = area.vertical_scroll_offset(pos as f32 * row_step);
+            }
+            area.show_rows(ui, row_height, self.visible.len(), |ui, range| {
+                for pos in range {
+                    let row = &self.model.rows[self.visible[pos]];
+                    ui.horizontal(|ui| {
+                        if self.selected == Some(pos) {
+                            let rect = egui::Rect::from_min_size(ui.max_rect().min, egui::vec2(ui.max_rect().width(), row_height));
+                            ui.painter().rect_filled(rect, 0.0, ui.visuals().selection.bg_fill);
+                        }
+                        draw_row(ui, row, columns.len(), row_height);
+                    });
+                }
+            });
+        });
+    }
+}
+
+/// Draws one diff row: its kind, then the line and cells of each side.
+fn draw_row(ui: &mut egui::Ui, row: &DiffRow, column_count: usize, row_height: f32) {
+    let (kind, color) = match row.kind {
+        DiffKind::Added => ("added", Color32::from_rgb(60, 150, 60)),
+        DiffKind::Removed => ("removed", Color32::from_rgb(190, 60, 60)),
+        DiffKind::Changed => ("changed", Color32::from_rgb(200, 140, 30)),
+    };
+    ui.add_sized([KIND_WIDTH, row_height], egui::Label::new(RichText::new(kind).color(color)));
+
+    for (line, cells) in [(row.left_line, &row.left), (row.right_line, &row.right)] {
+        let line = line.map_or(String::new(), |l| l.to_string());
+        ui.add_sized([LINE_WIDTH, row_height], egui::Label::new(RichText::new(line).weak()));
+        for i in 0..column_count {
+            let text = cells.get(i).map_or("", String::as_str);
+            let mut rich = RichText::new(shorten(text));
+            // Highlight the cells that differ between the two sides.
+            if row.changed.contains(&i) {
+                rich = rich.background_color(color.linear_multiply(0.35));
+            }
+            let response = ui.add_sized([CELL_WIDTH, row_height], egui::Label::new(rich).wrap(false));
+            if text.chars().count() > CELL_CHARS {
+                response.on_hover_text(text);
+            }
+        }
+        ui.separator();
+    }
+}
+
+/// Shortens a cell to fit its column, marking the cut with an ellipsis.
+fn shorten(text: &str) -> String {
+    if text.chars().count() <= CELL_CHARS {
+        return text.to_string();
+    }
+    let mut short: String = text.chars().take(CELL_CHARS - 1).collect();
+    short.push('…');
+    short
+}
@@ -0,0 +1,348 @@
+use std::error::Error;
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use csv::WriterBuilder;
+use regex::Regex;
+use serde::Serialize;
+use crate::csv_compare::{compare_with_options, output_path, CompareOptions};
+use crate::progress::Cancelled;
+
+/// Options controlling a directory-to-directory comparison.
+#[derive(Debug, Clone, Default)]
+pub struct DirCompareOptions {
+    pub compare: CompareOptions,  // Options applied to every pair of files
+    pub pattern: Option<String>,  // Regex used to pair files (see `index_dir`)
+    pub output_dir: String,       // Directory receiving the per-pair outputs
+    pub summary: Option<String>,  // Path of the summary (`.json` for JSON, else CSV)
+}
+
+/// Outcome of comparing one pair of files, or of a file found on only one side.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PairResult {
+    pub key: String,                    // Pairing key (the relative path by default)
+    pub left: Option<String>,           // Path of the file in the left directory
+    pub right: Option<String>,          // Path of the file in the right directory
+    pub status: String,                 // `match`, `differs`, `error`, `left_only` or `right_only`
+    pub rows_left: u64,                 // Data rows in the left file
+    pub rows_right: u64,                // Data rows in the right file
+    pub unique_rows: u64,               // Rows of the left file not found in the right file
+    pub missing_rows: u64,              // Rows of the right file not found in the left file (see `compare_dirs`)
+    pub output: Option<String>,         // Path of the unique rows file
+    pub missing_output: Option<String>, // Path of the file listing the missing rows, if any
+    pub error: Option<String>,          // Error message if the comparison failed
+}
+
+/// Summary of a directory comparison.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DirSummary {
+    pub left_dir: String,         // The left directory
+    pub right_dir: String,        // The right directory
+    pub pairs: Vec<PairResult>,   // One entry per key, in key order
+    pub matched: u64,             // Pairs with no differences
+    pub differing: u64,           // Pairs with differences
+    pub errors: u64,              // Pairs whose comparison failed
+    pub left_only: u64,           // Files present only in the left directory
+    pub right_only: u64,          // Files present only in the right directory
+    #[serde(skip)]
+    pub summary_file: String,     // Path the summary was written to
+}
+
+/// Compares two directories of CSV files, pairing files by relative path or pattern.
+///
+/// Each pair is compared with `options.compare`, writing the unique rows of the left
+/// file to `<output_dir>/<key>_modified.csv`. Files present on only one side are listed
+/// in the summary, which is written as CSV or JSON depending on the summary file extension
+/// (default `<output_dir>/summary.csv`). A pair has status `match` when the files contain
+/// the same rows the same number of times, so that e.g. `[A, A, B]` and `[A, B, B]` differ.
+/// When such a pair has no unique rows in the left file, the right file is also compared
+/// against the left one, and its rows not found there are written to
+/// `<output_dir>/<key>_missing.csv`.
+///
+/// Directories are followed at most once, so symbolic link cycles are harmless, and the
+/// output directory is skipped when it lies inside one of the compared directories.
+///
+/// # Arguments
+///
+/// * `left_dir` - The directory with the first files of each pair.
+/// * `right_dir` - The directory with the second files of each pair.
+/// * `options` - The directory comparison options.
+///
+/// # Returns
+///
+/// The `DirSummary`, or an error if a directory cannot be read, the pattern is invalid
+/// or the summary cannot be written. Failures of individual pairs are recorded in the
+/// summary instead of aborting the run, except a cancellation through
+/// `options.compare.progress`, which stops the run.
+pub fn compare_dirs(left_dir: &str, right_dir: &str, options: &DirCompareOptions) -> Result<DirSummary, Box<dyn Error>> {
+    let pattern = options.pattern.as_deref().map(Regex::new).transpose()?;
+    fs::create_dir_all(&options.output_dir)?;
+    let output_dir = fs::canonicalize(&options.output_dir)?;
+    let left = index_dir(Path::new(left_dir), pattern.as_ref(), &output_dir)?;
+    let right = index_dir(Path::new(right_dir), pattern.as_ref(), &output_dir)?;
+
+    let mut summary = DirSummary {
+        left_dir: left_dir.to_string(),
+        right_dir: right_dir.to_string(),
+        ..DirSummary::default()
+    };
+
+    // Visit the union of keys in sorted order.
+    let mut keys: Vec<&String> = left.keys().chain(right.keys()).collect();
+    keys.sort();
+    keys.dedup();
+
+    for key in keys {
+        let mut result = PairResult {
+            key: key.clone(),
+            left: left.get(key).map(|p| p.display().to_string()),
+            right: right.get(key).map(|p| p.display().to_string()),
+            ..PairResult::default()
+        };
+
+        match (&result.left, &result.right) {
+            (Some(l), Some(r)) => {
+                let output = Path::new(&options.output_dir).join(output_path(key, "modified"));
+                if let Some(parent) = output.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                let compare = CompareOptions {
+                    output_file: Some(output.display().to_string()),
+                    ..options.compare.clone()
+                };
+                let compared = compare_with_options(l, r, &compare).and_then(|report| {
+                    // Look for rows only the right file has when the left one has none.
+                    if report.same_rows || report.unique_rows > 0 {
+                        return Ok((report, None));
+                    }
+                    let missing = Path::new(&options.output_dir).join(output_path(key, "missing"));
+                    let reverse = CompareOptions {
+                        output_file: Some(missing.display().to_string()),
+                        ..options.compare.clone()
+                    };
+                    let reverse = compare_with_options(r, l, &reverse)?;
+                    if reverse.unique_rows == 0 {
+                        fs::remove_file(&reverse.output_file)?;
+                    }
+                    Ok((report, Some(reverse)))
+                });
+                match compared {
+                    Ok((report, reverse)) => {
+                        result.missing_rows = reverse.as_ref().map_or(0, |r| r.unique_rows);
+                        let same = report.same_rows;
+                        result.status = if same { "match" } else { "differs" }.to_string();
+                        result.rows_left = report.rows_file1;
+                        result.rows_right = report.rows_file2;
+                        result.unique_rows = report.unique_rows;
+                        result.output = Some(report.output_file);
+                        result.missing_output = reverse.filter(|r| r.unique_rows > 0).map(|r| r.output_file);
+                        if same { summary.matched += 1 } else { summary.differing += 1 }
+                    }
+                    Err(e) if e.is::<Cancelled>() => return Err(e),
+                    Err(e) => {
+                        result.status = "error".to_string();
+                        result.error = Some(e.to_string());
+                        summary.errors += 1;
+                    }
+                }
+            }
+            (Some(_), None) => {
+                result.status = "left_only".to_string();
+                summary.left_only += 1;
+            }
+            _ => {
+                result.status = "right_only".to_string();
+                summary.right_only += 1;
+            }
+        }
+        summary.pairs.push(result);
+    }
+
+    let summary_path = options.summary.clone()
+        .unwrap_or_else(|| Path::new(&options.output_dir).join("summary.csv").display().to_string());
+    write_summary(&summary_path, &summary)?;
+    summary.summary_file = summary_path;
+
+    Ok(summary)
+}
+
+/// Lists the CSV files under `dir` (recursively), keyed by their pairing key.
+///
+/// Without a pattern the key is the path relative to `dir`, using `/` as separator.
+/// With a pattern, only files whose relative path matches it are included, and the key
+/// is the first capture group (or the whole match if the pattern has no groups). For
+/// example `orders_(.*)_\d{8}\.csv` pairs `orders_eu_20240101.csv` with `orders_eu_20240108.csv`.
+/// Files under `skip` (the canonical output directory) are left out.
+fn index_dir(dir: &Path, pattern: Option<&Regex>, skip: &Path) -> Result<BTreeMap<String, PathBuf>, Box<dyn Error>> {
+    let mut files = Vec::new();
+    collect_files(dir, skip, &mut HashSet::new(), &mut files)?;
+
+    let mut index = BTreeMap::new();
+    for path in files {
+        let relative = path.strip_prefix(dir)?
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        let key = match pattern {
+            Some(re) => match re.captures(&relative) {
+                Some(caps) => caps.get(1).or_else(|| caps.get(0)).map_or(String::new(), |m| m.as_str().to_string()),
+                None => continue,
+            },
+            None if relative.to_lowercase().ends_with(".csv") => relative,
+            None => continue,
+        };
+        if let Some(previous) = index.insert(key.clone(), path.clone()) {
+            return Err(format!("Files '{}' and '{}' both map to key '{}'", previous.display(), path.display(), key).into());
+        }
+    }
+
+    Ok(index)
+}
+
+/// Recursively collects all regular files under `dir`.
+///
+/// Symbolic links are followed, but each directory is visited only once (by canonical
+/// path) so link cycles end, and subdirectories whose canonical path is `skip` are ignored.
+fn collect_files(dir: &Path, skip: &Path, visited: &mut HashSet<PathBuf>, files: &mut Vec<PathBuf>) -> Result<(), Box<dyn Error>> {
+    if !visited.insert(fs::canonicalize(dir)?) {
+        return Ok(());
+    }
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            if fs::canonicalize(&path)? != skip {
+                collect_files(&path, skip, visited, files)?;
+            }
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Writes the directory summary as JSON (for a `.json` path) or CSV.
+fn write_summary(path: &str, summary: &DirSummary) -> Result<(), Box<dyn Error>> {
+    if path.to_lowercase().ends_with(".json") {
+        fs::write(path, serde_json::to_string_pretty(summary)?)?;
+        return Ok(());
+    }
+
+    let mut writer = WriterBuilder::new().from_path(path)?;
+    writer.write_record(["key", "status", "left", "right", "rows_left", "rows_right", "unique_rows", "missing_rows", "output", "missing_output", "error"])?;
+    for pair in &summary.pairs {
+        writer.write_record([
+            pair.key.as_str(),
+            pair.status.as_str(),
+            pair.left.as_deref().unwrap_or(""),
+            pair.right.as_deref().unwrap_or(""),
+            &pair.rows_left.to_string(),
+            &pair.rows_right.to_string(),
+            &pair.unique_rows.to_string(),
+            &pair.missing_rows.to_string(),
+            pair.output.as_deref().unwrap_or(""),
+            pair.missing_output.as_deref().unwrap_or(""),
+            pair.error.as_deref().unwrap_or(""),
+        ])?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    fn options(dir: &TempDir) -> DirCompareOptions {
+        DirCompareOptions { output_dir: dir.path("out"), ..DirCompareOptions::default() }
+    }
+
+    fn statuses(summary: &DirSummary) -> Vec<(&str, &str)> {
+        summary.pairs.iter().map(|p| (p.key.as_str(), p.status.as_str())).collect()
+    }
+
+    #[test]
+    fn pairs_files_by_relative_path() {
+        let dir = TempDir::new();
+        dir.write("left/same.csv", "id\n1\n2\n");
+        dir.write("right/same.csv", "id\n2\n1\n");
+        dir.write("left/sub/changed.csv", "id\n1\n2\n");
+        dir.write("right/sub/changed.csv", "id\n1\n");
+        dir.write("left/old.csv", "id\n1\n");
+        dir.write("right/new.csv", "id\n1\n");
+        dir.write("left/notes.txt", "not a csv");
+
+        let summary = compare_dirs(&dir.path("left"), &dir.path("right"), &options(&dir)).unwrap();
+        assert_eq!(
+            statuses(&summary),
+            [("new.csv", "right_only"), ("old.csv", "left_only"), ("same.csv", "match"), ("sub/changed.csv", "differs")]
+        );
+        assert_eq!((summary.matched, summary.differing, summary.left_only, summary.right_only), (1, 1, 1, 1));
+        assert_eq!(summary.summary_file, dir.path("out/summary.csv"));
+        assert_eq!(dir.read("out/sub/changed_modified.csv"), "id\n2\n");
+    }
+
+    #[test]
+    fn duplicate_rows_do_not_hide_a_difference() {
+        let dir = TempDir::new();
+        dir.write("left/a.csv", "id\nA\nA\n");
+        dir.write("right/a.csv", "id\nA\nB\n");
+
+        let summary = compare_dirs(&dir.path("left"), &dir.path("right"), &options(&dir)).unwrap();
+        let pair = &summary.pairs[0];
+        assert_eq!((pair.status.as_str(), pair.unique_rows, pair.missing_rows), ("differs", 0, 1));
+        assert_eq!(pair.missing_output.as_deref(), Some(dir.path("out/a_missing.csv").as_str()));
+        assert_eq!(dir.read("out/a_missing.csv"), "id\nB\n");
+
+        // The same rows, but not the same number of times.
+        dir.write("left/a.csv", "id\nA\nA\nB\n");
+        dir.write("right/a.csv", "id\nA\nB\nB\n");
+        let summary = compare_dirs(&dir.path("left"), &dir.path("right"), &options(&dir)).unwrap();
+        let pair = &summary.pairs[0];
+        assert_eq!((pair.status.as_str(), pair.unique_rows, pair.missing_rows), ("differs", 0, 0));
+        assert_eq!(pair.missing_output, None);
+        assert!(!Path::new(&dir.path("out/a_missing.csv")).exists());
+    }
+
+    #[test]
+    fn pairs_files_by_pattern() {
+        let dir = TempDir::new();
+        dir.write("left/orders_eu_20240101.csv", "id\n1\n");
+        dir.write("right/orders_eu_20240108.csv", "id\n1\n");
+        dir.write("right/customers.csv", "id\n1\n");
+
+        let options = DirCompareOptions { pattern: Some(r"orders_(.*)_\d{8}\.csv".to_string()), ..options(&dir) };
+        let summary = compare_dirs(&dir.path("left"), &dir.path("right"), &options).unwrap();
+        assert_eq!(statuses(&summary), [("eu", "match")]);
+        assert!(!Path::new(&dir.path("out/eu_missing.csv")).exists());
+
+        dir.write("left/orders_eu_20240102.csv", "id\n1\n");
+        let error = compare_dirs(&dir.path("left"), &dir.path("right"), &options).unwrap_err();
+        assert!(error.to_string().ends_with("both map to key 'eu'"), "{}", error);
+    }
+
+    #[test]
+    fn skips_the_output_directory_inside_the_input() {
+        let dir = TempDir::new();
+        dir.write("left/a.csv", "id\n1\n");
+        dir.write("right/a.csv", "id\n2\n");
+        let options = DirCompareOptions { output_dir: dir.path("left/out"), ..DirCompareOptions::default() };
+
+        compare_dirs(&dir.path("left"), &dir.path("right"), &options).unwrap();
+        let summary = compare_dirs(&dir.path("left"), &dir.path("right"), &options).unwrap();
+        assert_eq!(statuses(&summary), [("a.csv", "differs")]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn follows_symbolic_link_cycles_once() {
+        let dir = TempDir::new();
+        dir.write("left/sub/a.csv", "id\n1\n");
+        dir.write("right/sub/a.csv", "id\n1\n");
+        std::os::unix::fs::symlink(dir.path("left"), dir.path("left/sub/loop")).unwrap();
+
+        let summary = compare_dirs(&dir.path("left"), &dir.path("right"), &options(&dir)).unwrap();
+        assert_eq!(statuses(&summary), [("sub/a.csv", "match")]);
+    }
+}
@@ -1,15 +1,395 @@
 use std::{
+    borrow::Cow,
+    error::Error,
+    fmt,
     fs::File,
-    io::{self, BufReader, Read},
+    io::{self, BufRead, BufReader, Read, Seek, SeekFrom},
     path::Path,
+    str::FromStr,
 };
-use chardet::detect;
+use chardet::{detect, UniversalDetector};
+use encoding_rs_io::{DecodeReaderBytes, DecodeReaderBytesBuilder};
+use serde::{Deserialize, Serialize};
 
 /// Enum to represent file encoding options.
-#[derive(Debug)]
+///
+/// This is the one mapping between encodings and their names: `name` is what the
+/// command line, profiles and manifests use, and `label` is what the GUI shows and
+/// `detect_encoding` reports. Parsing accepts either, as well as the usual aliases
+/// (e.g. `cp1252` or `sjis`).
+///
+/// Every encoding can be read and written; see `transcode` for how output files are
+/// encoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Encoding {
+    #[default]
     Utf8,
     Utf8Bom,
+    Utf16Le,
+    Utf16Be,
+    Latin1,
+    Windows1250,
+    Windows1251,
+    Windows1252,
+    ShiftJis,
+    Gbk,
+    EucKr,
+}
+
+impl Encoding {
+    /// Every supported encoding, in the order menus list them.
+    pub const ALL: [Encoding; 11] = [
+        Encoding::Utf8,
+        Encoding::Utf8Bom,
+        Encoding::Utf16Le,
+        Encoding::Utf16Be,
+        Encoding::Latin1,
+        Encoding::Windows1250,
+        Encoding::Windows1251,
+        Encoding::Windows1252,
+        Encoding::ShiftJis,
+        Encoding::Gbk,
+        Encoding::EucKr,
+    ];
+
+    /// Returns the name used on the command line and in profiles, e.g. `utf8bom`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Encoding::Utf8 => "utf8",
+            Encoding::Utf8Bom => "utf8bom",
+            Encoding::Utf16Le => "utf16le",
+            Encoding::Utf16Be => "utf16be",
+            Encoding::Latin1 => "latin1",
+            Encoding::Windows1250 => "windows1250",
+            Encoding::Windows1251 => "windows1251",
+            Encoding::Windows1252 => "windows1252",
+            Encoding::ShiftJis => "shiftjis",
+            Encoding::Gbk => "gbk",
+            Encoding::EucKr => "euckr",
+        }
+    }
+
+    /// Returns the name shown to users, e.g. `UTF-8 with BOM`.
+    pub fn label(self) -> &'static str {
+        match self {
+            Encoding::Utf8 => "UTF-8",
+            Encoding::Utf8Bom => "UTF-8 with BOM",
+            Encoding::Utf16Le => "UTF-16LE",
+            Encoding::Utf16Be => "UTF-16BE",
+            Encoding::Latin1 => "ISO-8859-1",
+            Encoding::Windows1250 => "Windows-1250",
+            Encoding::Windows1251 => "Windows-1251",
+            Encoding::Windows1252 => "Windows-1252",
+            Encoding::ShiftJis => "Shift_JIS",
+            Encoding::Gbk => "GBK",
+            Encoding::EucKr => "EUC-KR",
+        }
+    }
+
+    /// Returns the `encoding_rs` codec closest to this encoding.
+    ///
+    /// `encoding_rs` only knows ISO-8859-1 as Windows-1252, which gives the bytes 0x80
+    /// to 0x9F characters such as `€` instead of the control characters they are in
+    /// ISO-8859-1. Use `Encoding::decode` or `decode_reader` to decode it byte for byte.
+    pub fn codec(self) -> &'static encoding_rs::Encoding {
+        match self {
+            Encoding::Utf8 | Encoding::Utf8Bom => encoding_rs::UTF_8,
+            Encoding::Utf16Le => encoding_rs::UTF_16LE,
+            Encoding::Utf16Be => encoding_rs::UTF_16BE,
+            Encoding::Latin1 | Encoding::Windows1252 => encoding_rs::WINDOWS_1252,
+            Encoding::Windows1250 => encoding_rs::WINDOWS_1250,
+            Encoding::Windows1251 => encoding_rs::WINDOWS_1251,
+            Encoding::ShiftJis => encoding_rs::SHIFT_JIS,
+            Encoding::Gbk => encoding_rs::GBK,
+            Encoding::EucKr => encoding_rs::EUC_KR,
+        }
+    }
+
+    /// Decodes a buffer without byte-order mark handling, replacing invalid sequences.
+    ///
+    /// # Returns
+    ///
+    /// The text, and `true` if anything had to be replaced.
+    pub fn decode(self, bytes: &[u8]) -> (Cow<'_, str>, bool) {
+        match self {
+            Encoding::Latin1 => (bytes.iter().map(|&b| char::from(b)).collect(), false),
+            _ => self.codec().decode_without_bom_handling(bytes),
+        }
+    }
+
+    /// Returns `true` for the UTF-8 and UTF-16 encodings, which can represent every character.
+    pub fn is_unicode(self) -> bool {
+        matches!(self, Encoding::Utf8 | Encoding::Utf8Bom | Encoding::Utf16Le | Encoding::Utf16Be)
+    }
+
+    /// Returns the byte-order mark written at the start of output files in this encoding.
+    ///
+    /// UTF-16 output always starts with a BOM, so that tools such as Excel recognize it.
+    pub fn bom(self) -> Option<Bom> {
+        match self {
+            Encoding::Utf8Bom => Some(Bom::Utf8),
+            Encoding::Utf16Le => Some(Bom::Utf16Le),
+            Encoding::Utf16Be => Some(Bom::Utf16Be),
+            _ => None,
+        }
+    }
+
+    /// Maps an encoding name reported by `detect_encoding` to the `Encoding` it can be
+    /// read as, or `None` if it is not supported. ASCII files are read as UTF-8.
+    pub fn from_detected(name: &str) -> Option<Self> {
+        if name.eq_ignore_ascii_case("ascii") {
+            return Some(Encoding::Utf8);
+        }
+        name.parse().ok()
+    }
+}
+
+impl fmt::Display for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.label())
+    }
+}
+
+impl FromStr for Encoding {
+    type Err = String;
+
+    /// Parses an encoding name such as `utf8` or `windows1252`, or a label such as
+    /// `UTF-8 with BOM`. Case, spaces, dashes and underscores are ignored. Other
+    /// standard labels (e.g. `cp1252`, `sjis` or `gb2312`) are accepted when they name
+    /// one of the supported encodings.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let normalize = |s: &str| s.chars().filter(|c| c.is_alphanumeric()).collect::<String>().to_lowercase();
+        let wanted = normalize(s);
+        Encoding::ALL.into_iter()
+            .find(|e| normalize(e.name()) == wanted || normalize(e.label()) == wanted)
+            .or_else(|| {
+                let codec = encoding_rs::Encoding::for_label(s.trim().as_bytes())?;
+                Encoding::ALL.into_iter().find(|e| e.label().eq_ignore_ascii_case(codec.name()))
+            })
+            .ok_or_else(|| {
+                let names: Vec<&str> = Encoding::ALL.iter().map(|e| e.name()).collect();
+                format!("Invalid encoding '{}'. Use one of {}.", s, names.join(", "))
+            })
+    }
+}
+
+/// How the encoding of an input file is chosen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(into = "String", try_from = "String")]
+pub enum InputEncoding {
+    /// Read the file as this encoding.
+    Fixed(Encoding),
+    /// Detect the encoding with `detect_encoding` before reading the file.
+    Auto,
+}
+
+/// The default reads the file as UTF-8 without checking it first.
+impl Default for InputEncoding {
+    fn default() -> Self {
+        InputEncoding::Fixed(Encoding::Utf8)
+    }
+}
+
+impl InputEncoding {
+    /// Returns every choice: auto-detection, then each encoding.
+    pub fn all() -> Vec<InputEncoding> {
+        std::iter::once(InputEncoding::Auto)
+            .chain(Encoding::ALL.map(InputEncoding::Fixed))
+            .collect()
+    }
+
+    /// Returns the name used on the command line and in profiles, e.g. `auto`.
+    pub fn name(self) -> &'static str {
+        match self {
+            InputEncoding::Fixed(encoding) => encoding.name(),
+            InputEncoding::Auto => "auto",
+        }
+    }
+
+    /// Returns the name shown to users, e.g. `Auto-detect`.
+    pub fn label(self) -> &'static str {
+        match self {
+            InputEncoding::Fixed(encoding) => encoding.label(),
+            InputEncoding::Auto => "Auto-detect",
+        }
+    }
+}
+
+impl FromStr for InputEncoding {
+    type Err = String;
+
+    /// Parses `auto`, or an encoding as `Encoding` does.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.trim().eq_ignore_ascii_case("auto") || s.trim().eq_ignore_ascii_case("auto-detect") {
+            return Ok(InputEncoding::Auto);
+        }
+        s.parse::<Encoding>()
+            .map(InputEncoding::Fixed)
+            .map_err(|e| format!("{} Use 'auto' to detect it.", e))
+    }
+}
+
+impl From<InputEncoding> for String {
+    fn from(input: InputEncoding) -> Self {
+        input.name().to_string()
+    }
+}
+
+impl TryFrom<String> for InputEncoding {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+/// Determines the encoding an input file is read with.
+///
+/// A fixed encoding is returned as is. With `InputEncoding::Auto`, the file's encoding
+/// is detected with `detect_encoding`, and must be one that can be read. Either way,
+/// files with a UTF-32 byte-order mark are rejected, as UTF-32 cannot be read.
+///
+/// # Arguments
+///
+/// * `path` - The path to the input file.
+/// * `input` - How the encoding is chosen.
+///
+/// # Returns
+///
+/// The encoding to read the file with, or an error if the file cannot be read or its
+/// detected encoding is not supported.
+pub fn resolve_input_encoding(path: &Path, input: InputEncoding) -> Result<Encoding, Box<dyn Error>> {
+    let mut start = Vec::with_capacity(4);
+    File::open(path)?.take(4).read_to_end(&mut start)?;
+    reject_utf32(path, &start)?;
+    match input {
+        InputEncoding::Fixed(encoding) => Ok(encoding),
+        InputEncoding::Auto => {
+            let (detected, confidence) = detect_encoding(path)?;
+            Encoding::from_detected(&detected).ok_or_else(|| {
+                format!("'{}' appears to be {} ({:.0}% confidence), which is not a supported encoding",
+                    path.display(), detected, confidence * 100.0).into()
+            })
+        }
+    }
+}
+
+/// A reader whose bytes are decoded to UTF-8 as they are read.
+pub type DecodedReader<R> = DecodeReaderBytes<Latin1Reader<R>, Vec<u8>>;
+
+/// A file decoded to UTF-8 as it is read.
+pub type DecodedFile = DecodedReader<File>;
+
+/// Opens a file for reading as UTF-8 text, decoding it from `encoding` on the fly.
+///
+/// A byte-order mark at the start of the file is removed, and takes precedence over
+/// `encoding` (so a UTF-16 file with a BOM is read correctly whatever is specified).
+/// UTF-8 files are passed through unchanged, so invalid UTF-8 is still reported by the
+/// CSV reader; in other encodings, invalid sequences become replacement characters.
+///
+/// # Arguments
+///
+/// * `path` - The path to the file.
+/// * `encoding` - The encoding of the file.
+///
+/// # Returns
+///
+/// A reader yielding the file's content as UTF-8, or an error if the file cannot be
+/// opened or starts with a UTF-32 byte-order mark.
+pub fn open_decoded(path: &Path, encoding: Encoding) -> io::Result<DecodedFile> {
+    let mut file = File::open(path)?;
+    let mut start = Vec::with_capacity(4);
+    (&mut file).take(4).read_to_end(&mut start)?;
+    reject_utf32(path, &start)?;
+    file.rewind()?;
+    Ok(decode_reader(file, encoding))
+}
+
+/// Fails if `start`, the first bytes of the file at `path`, is a UTF-32 byte-order mark.
+///
+/// UTF-32 cannot be read, and `decode_reader` would take the UTF-32LE mark for the
+/// UTF-16LE one it starts with.
+pub fn reject_utf32(path: &Path, start: &[u8]) -> io::Result<()> {
+    match Bom::detect(start) {
+        Some(bom @ (Bom::Utf32Le | Bom::Utf32Be)) => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("'{}' starts with a {}: UTF-32 input is not supported", path.display(), bom),
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Wraps a reader so that its bytes are decoded from `encoding` to UTF-8, like
+/// `open_decoded` does for a file.
+pub fn decode_reader<R: Read>(reader: R, encoding: Encoding) -> DecodedReader<R> {
+    // An explicit UTF-8 codec would replace invalid sequences, so UTF-8 input is left
+    // undecoded unless its byte-order mark names another encoding. A UTF-8 mark only
+    // skips decoding for UTF-8 input; in other encodings it switches to UTF-8.
+    // ISO-8859-1 arrives here already converted to UTF-8 by `Latin1Reader`.
+    let utf8 = matches!(encoding, Encoding::Utf8 | Encoding::Utf8Bom | Encoding::Latin1);
+    DecodeReaderBytesBuilder::new()
+        .encoding((!utf8).then(|| encoding.codec()))
+        .utf8_passthru(utf8)
+        .bom_override(true)
+        .strip_bom(true)
+        .build(Latin1Reader::new(reader, encoding == Encoding::Latin1))
+}
+
+/// A reader that converts ISO-8859-1 to UTF-8, each byte being the code point of the
+/// same value, and passes other input through unchanged.
+///
+/// Input that starts with a byte-order mark is passed through as well, so the mark
+/// still takes precedence in `decode_reader`.
+pub struct Latin1Reader<R> {
+    inner: R,              // The raw input
+    convert: Option<bool>, // Whether to convert, `None` until the start has been checked
+    pending: Vec<u8>,      // Output not yet returned
+    raw: Vec<u8>,          // Buffer reused for each chunk of input
+}
+
+impl<R: Read> Latin1Reader<R> {
+    /// Wraps `inner`, converting it from ISO-8859-1 if `latin1` is `true`.
+    pub fn new(inner: R, latin1: bool) -> Self {
+        Self { inner, convert: (!latin1).then_some(false), pending: Vec::new(), raw: Vec::new() }
+    }
+}
+
+impl<R: Read> Read for Latin1Reader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pending.is_empty() {
+            match self.convert {
+                Some(false) => return self.inner.read(buf),
+                Some(true) => {
+                    // Every byte takes at most two bytes of UTF-8.
+                    self.raw.resize((buf.len() / 2).max(1), 0);
+                    let n = self.inner.read(&mut self.raw)?;
+                    self.pending.extend(self.raw[..n].iter().map(|&b| char::from(b)).collect::<String>().bytes());
+                }
+                None => {
+                    let mut start = [0; 4];
+                    let mut len = 0;
+                    while len < start.len() {
+                        match self.inner.read(&mut start[len..])? {
+                            0 => break,
+                            n => len += n,
+                        }
+                    }
+                    let convert = Bom::detect(&start[..len]).is_none();
+                    self.pending = if convert {
+                        start[..len].iter().map(|&b| char::from(b)).collect::<String>().into_bytes()
+                    } else {
+                        start[..len].to_vec()
+                    };
+                    self.convert = Some(convert);
+                }
+            }
+        }
+        let n = buf.len().min(self.pending.len());
+        buf[..n].copy_from_slice(&self.pending[..n]);
+        self.pending.drain(..n);
+        Ok(n)
+    }
 }
 
 /// Checks if the provided byte slice is valid UTF-8.
@@ -29,11 +409,166 @@ pub fn is_valid_utf8(bytes: &[u8]) -> bool {
     std::str::from_utf8(bytes).is_ok()
 }
 
+/// A Unicode byte-order mark at the start of a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Bom {
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    Utf32Le,
+    Utf32Be,
+}
+
+impl Bom {
+    /// Recognizes the byte-order mark a buffer starts with, if any.
+    ///
+    /// UTF-32LE is checked before UTF-16LE, whose mark (`FF FE`) is a prefix of it.
+    pub fn detect(bytes: &[u8]) -> Option<Bom> {
+        match bytes {
+            [0xEF, 0xBB, 0xBF, ..] => Some(Bom::Utf8),
+            [0xFF, 0xFE, 0x00, 0x00, ..] => Some(Bom::Utf32Le),
+            [0x00, 0x00, 0xFE, 0xFF, ..] => Some(Bom::Utf32Be),
+            [0xFF, 0xFE, ..] => Some(Bom::Utf16Le),
+            [0xFE, 0xFF, ..] => Some(Bom::Utf16Be),
+            _ => None,
+        }
+    }
+
+    /// Returns the bytes of the mark.
+    pub fn bytes(self) -> &'static [u8] {
+        match self {
+            Bom::Utf8 => b"\xEF\xBB\xBF",
+            Bom::Utf16Le => b"\xFF\xFE",
+            Bom::Utf16Be => b"\xFE\xFF",
+            Bom::Utf32Le => b"\xFF\xFE\x00\x00",
+            Bom::Utf32Be => b"\x00\x00\xFE\xFF",
+        }
+    }
+
+    /// Returns the name of the encoding the mark announces, e.g. `UTF-16LE`.
+    pub fn encoding(self) -> &'static str {
+        match self {
+            Bom::Utf8 => "UTF-8",
+            Bom::Utf16Le => "UTF-16LE",
+            Bom::Utf16Be => "UTF-16BE",
+            Bom::Utf32Le => "UTF-32LE",
+            Bom::Utf32Be => "UTF-32BE",
+        }
+    }
+}
+
+impl fmt::Display for Bom {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} BOM", self.encoding())
+    }
+}
+
+/// The encoding guessed for a buffer, with the byte-order mark it starts with.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EncodingGuess {
+    pub encoding: String,  // Guessed encoding, e.g. `UTF-8`, `UTF-16LE` or `windows-1252`
+    pub confidence: f64,   // Confidence of the guess (0.0 to 1.0)
+    pub bom: Option<Bom>,  // Byte-order mark at the start of the buffer, if any
+}
+
+impl EncodingGuess {
+    /// Returns the encoding as `detect_encoding` reports it: the encoding name, except
+    /// for UTF-8 with a byte-order mark, which is `UTF-8 with BOM`.
+    pub fn label(&self) -> String {
+        match self.bom {
+            Some(Bom::Utf8) if self.encoding == Bom::Utf8.encoding() => "UTF-8 with BOM".to_string(),
+            _ => self.encoding.clone(),
+        }
+    }
+}
+
+/// Default number of bytes examined by the sampled detection modes.
+pub const DEFAULT_SAMPLE_BYTES: u64 = 1024 * 1024;
+
+/// Size of the chunks read at a time by a full scan.
+const SCAN_CHUNK_BYTES: usize = 64 * 1024;
+
+/// How much of a file `detect_encoding_with` examines.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DetectionMode {
+    /// The first `n` bytes of the file.
+    Head(u64),
+    /// `n` bytes in total, taken in equal parts from the start, the middle and the end of the file.
+    Spread(u64),
+    /// The whole file, streamed in chunks, with UTF-8 validated over its entire content.
+    Full,
+}
+
+/// The default examines `DEFAULT_SAMPLE_BYTES` from the start, middle and end of the file.
+impl Default for DetectionMode {
+    fn default() -> Self {
+        DetectionMode::Spread(DEFAULT_SAMPLE_BYTES)
+    }
+}
+
+impl DetectionMode {
+    /// Builds a mode from its name (`head`, `spread` or `full`) and the number of bytes
+    /// examined by the sampled modes.
+    pub fn from_name(name: &str, sample_bytes: u64) -> Result<Self, String> {
+        match name.trim().to_lowercase().as_str() {
+            "head" => Ok(DetectionMode::Head(sample_bytes)),
+            "spread" | "sample" => Ok(DetectionMode::Spread(sample_bytes)),
+            "full" => Ok(DetectionMode::Full),
+            other => Err(format!("Unknown detection mode '{}'. Use head, spread or full.", other)),
+        }
+    }
+
+    /// Returns the name of the mode: `head`, `spread` or `full`.
+    pub fn name(self) -> &'static str {
+        match self {
+            DetectionMode::Head(_) => "head",
+            DetectionMode::Spread(_) => "spread",
+            DetectionMode::Full => "full",
+        }
+    }
+}
+
+impl fmt::Display for DetectionMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DetectionMode::Head(n) => write!(f, "first {} bytes", n),
+            DetectionMode::Spread(n) => write!(f, "{} bytes from the start, middle and end", n),
+            DetectionMode::Full => f.write_str("full scan"),
+        }
+    }
+}
+
+/// The outcome of detecting the encoding of a file.
+#[derive(Debug, Clone, Serialize)]
+pub struct Detection {
+    pub encoding: String,          // Detected encoding, e.g. `UTF-8`, `UTF-16LE` or `windows-1252`
+    pub confidence: f64,           // Confidence of the detection (0.0 to 1.0)
+    pub bom: Option<Bom>,          // Byte-order mark at the start of the file, if any
+    pub mode: DetectionMode,       // How much of the file was to be examined
+    pub bytes_examined: u64,       // Number of bytes actually examined
+    pub file_size: u64,            // Size of the file in bytes
+    pub valid_utf8: Option<bool>,  // Whether the whole file is valid UTF-8, if it was all examined
+}
+
+impl Detection {
+    /// Returns the encoding as `detect_encoding` reports it (see `EncodingGuess::label`).
+    pub fn label(&self) -> String {
+        EncodingGuess { encoding: self.encoding.clone(), confidence: self.confidence, bom: self.bom }.label()
+    }
+
+    /// Returns `true` if the whole file was examined (always the case for small files).
+    pub fn is_complete(&self) -> bool {
+        self.bytes_examined >= self.file_size
+    }
+}
+
 /// Detects the encoding of a file at the given path.
 ///
-/// This function first checks if the file exists, then reads the file into a buffer.
-/// It attempts to detect the file encoding using the `chardet` library and also checks
-/// for a UTF-8 BOM. The function returns both the detected encoding and a confidence score.
+/// This is `detect_encoding_with` in the default mode, which examines up to
+/// `DEFAULT_SAMPLE_BYTES` taken from the start, middle and end of the file, so large
+/// files are never read entirely.
 ///
 /// # Arguments
 ///
@@ -48,45 +583,678 @@ pub fn is_valid_utf8(bytes: &[u8]) -> bool {
 ///
 /// # Errors
 ///
-/// Returns an `io::Error` if the file cannot be opened or read.
-///
-/// This function can fail for several reasons, such as:
-/// * The file does not exist.
-/// * The file cannot be read due to permission issues or I/O errors.
-/// * The file is empty or corrupt.
+/// Returns an `io::Error` if the file does not exist or cannot be read.
 pub fn detect_encoding(path: &Path) -> Result<(String, f64), io::Error> {
+    let detection = detect_encoding_with(path, DetectionMode::default())?;
+    Ok((detection.label(), detection.confidence))
+}
+
+/// Detects the encoding of a file, examining as much of it as `mode` says.
+///
+/// A byte-order mark identifies the encoding outright, and is reported separately in
+/// `Detection::bom`. UTF-16 text without a mark is recognized from the NUL bytes of its
+/// ASCII characters; both are looked for at the start of the file only. Otherwise, the
+/// sampled modes read at most their sample size (the whole file when it is no larger),
+/// cut at character boundaries where a UTF-8 character would otherwise be split.
+/// `DetectionMode::Full` streams the whole file in chunks through the detector while
+/// validating UTF-8 incrementally, so a file that is valid UTF-8 throughout is
+/// reported as UTF-8 with full confidence. A UTF-8 byte-order mark followed by invalid
+/// UTF-8 does not settle the encoding: the content after it is guessed instead.
+///
+/// # Arguments
+///
+/// * `path` - The path to the file whose encoding is to be detected.
+/// * `mode` - How much of the file to examine.
+///
+/// # Returns
+///
+/// The `Detection`, including the mode used and the number of bytes examined.
+///
+/// # Errors
+///
+/// Returns an `io::Error` if the file does not exist or cannot be read.
+pub fn detect_encoding_with(path: &Path, mode: DetectionMode) -> io::Result<Detection> {
     // Check if the file exists before attempting to open it.
     if !path.exists() {
         return Err(io::Error::new(io::ErrorKind::NotFound, "File not found."));
     }
+    let mut file = File::open(path)?;
+    let file_size = file.metadata()?.len();
 
-    // Open the file for reading.
-    let file = File::open(path)?;
-    let mut reader = BufReader::new(file);
-    
-    // Buffer to store the first few bytes for BOM detection.
-    let mut bom_check = [0; 3];
-    let bom_bytes_read = reader.read(&mut bom_check)?;
+    let sample = match mode {
+        DetectionMode::Full => return scan_encoding(BufReader::new(file), file_size),
+        DetectionMode::Head(n) | DetectionMode::Spread(n) if n >= file_size => read_part(&mut file, 0, file_size)?,
+        DetectionMode::Head(n) => read_sample(&mut file, file_size, n, false)?,
+        DetectionMode::Spread(n) => read_sample(&mut file, file_size, n, true)?,
+    };
 
-    // Check if the first three bytes match the UTF-8 BOM signature (0xEF, 0xBB, 0xBF).
-    let is_utf8_bom = bom_bytes_read == 3 && &bom_check == b"\xEF\xBB\xBF";
+    let guess = guess_encoding(&sample);
+    let complete = sample.len() as u64 >= file_size;
+    Ok(Detection {
+        encoding: guess.encoding,
+        confidence: guess.confidence,
+        bom: guess.bom,
+        mode,
+        bytes_examined: sample.len() as u64,
+        file_size,
+        valid_utf8: complete.then(|| is_valid_utf8(&sample)),
+    })
+}
+
+/// Reads the sample of a file larger than `n` bytes: its first `n` bytes, or with
+/// `spread`, a third of that from its start, middle and end.
+fn read_sample(file: &mut File, file_size: u64, n: u64, spread: bool) -> io::Result<Vec<u8>> {
+    let part = if spread { n / 3 } else { n };
+    let head = read_part(file, 0, part)?;
+    // Byte-order marks and UTF-16 show at the start of the file, and cutting UTF-16
+    // text at UTF-8 character boundaries would garble it.
+    if Bom::detect(&head).is_some() || utf16_from_nuls(&head).is_some() {
+        return Ok(head);
+    }
+    let mut sample = char_aligned(&head).to_vec();
+    if spread {
+        for offset in [(file_size - part) / 2, file_size - part] {
+            sample.extend_from_slice(char_aligned(&read_part(file, offset, part)?));
+        }
+    }
+    Ok(sample)
+}
+
+/// Reads `len` bytes of a file starting at `offset` (fewer at the end of the file).
+fn read_part(file: &mut File, offset: u64, len: u64) -> io::Result<Vec<u8>> {
+    file.seek(SeekFrom::Start(offset))?;
+    let mut part = Vec::with_capacity(len as usize);
+    file.take(len).read_to_end(&mut part)?;
+    Ok(part)
+}
+
+/// Drops the bytes of UTF-8 characters split at the start or end of a sample, so the
+/// cut does not look like invalid UTF-8 to the detector.
+fn char_aligned(part: &[u8]) -> &[u8] {
+    let start = part.iter().take(3).take_while(|&&b| b & 0xC0 == 0x80).count();
+    let part = &part[start..];
+    let tail = part.len().saturating_sub(4);
+    match (tail..part.len()).rev().find(|&i| part[i] & 0xC0 != 0x80) {
+        Some(i) => {
+            let needed = match part[i] {
+                0xC0..=0xDF => 2,
+                0xE0..=0xEF => 3,
+                0xF0..=0xF7 => 4,
+                _ => 1,
+            };
+            if part.len() - i < needed { &part[..i] } else { part }
+        }
+        None => part,
+    }
+}
+
+/// Streams a whole file through the detector, validating UTF-8 as it goes.
+///
+/// UTF-16 and UTF-32 files, which cannot be UTF-8, are recognized from their first
+/// chunk without reading further.
+fn scan_encoding(mut reader: impl Read, file_size: u64) -> io::Result<Detection> {
+    let mut detector = UniversalDetector::new();
+    let mut chunk = vec![0; SCAN_CHUNK_BYTES];
+    let mut pending = Vec::new(); // Start of a UTF-8 character split across chunks
+    let mut valid_utf8 = true;
+    let mut ascii = true;
+    let mut bom = None;
+    let mut total: u64 = 0;
+
+    loop {
+        let n = reader.read(&mut chunk)?;
+        if n == 0 {
+            break;
+        }
+        let bytes = &chunk[..n];
+        if total == 0 {
+            let guess = guess_encoding(bytes);
+            if guess.bom != Some(Bom::Utf8) && (guess.bom.is_some() || utf16_from_nuls(bytes).is_some()) {
+                return Ok(Detection {
+                    encoding: guess.encoding,
+                    confidence: guess.confidence,
+                    bom: guess.bom,
+                    mode: DetectionMode::Full,
+                    bytes_examined: n as u64,
+                    file_size,
+                    valid_utf8: None,
+                });
+            }
+            bom = guess.bom;
+        }
+        // The detector takes a UTF-8 mark at its word, so only the content is fed to it.
+        detector.feed(if total == 0 && bom.is_some() { &bytes[3..] } else { bytes });
+        total += n as u64;
+
+        if valid_utf8 {
+            ascii &= bytes.is_ascii();
+            pending.extend_from_slice(bytes);
+            match std::str::from_utf8(&pending) {
+                Ok(_) => pending.clear(),
+                Err(e) if e.error_len().is_none() => {
+                    pending.drain(..e.valid_up_to());
+                }
+                Err(_) => valid_utf8 = false,
+            }
+        }
+    }
+    valid_utf8 &= pending.is_empty();
+
+    // A file that is valid UTF-8 throughout, with or without a mark, needs no guessing.
+    let (encoding, confidence) = match (valid_utf8, bom, ascii) {
+        (true, Some(bom), _) => (bom.encoding().to_string(), 1.0),
+        (true, None, true) => ("ascii".to_string(), 1.0),
+        (true, None, false) => ("UTF-8".to_string(), 1.0),
+        (false, _, _) => {
+            let (encoding, confidence, _) = detector.close();
+            (encoding, confidence.into())
+        }
+    };
+    Ok(Detection { encoding, confidence, bom, mode: DetectionMode::Full, bytes_examined: total, file_size, valid_utf8: Some(valid_utf8) })
+}
+
+/// Detects the encoding of a byte buffer, such as the beginning of a file.
+///
+/// This is `guess_encoding`, with the encoding reported as by `detect_encoding`.
+///
+/// # Returns
+///
+/// A tuple containing the detected encoding as a `String` and the confidence level as a `f64`.
+pub fn detect_encoding_of(bytes: &[u8]) -> (String, f64) {
+    let guess = guess_encoding(bytes);
+    (guess.label(), guess.confidence)
+}
 
-    // Read the rest of the file into a buffer for encoding detection.
-    let mut buffer = Vec::new();
-    reader.read_to_end(&mut buffer)?;
+/// Guesses the encoding of a byte buffer, such as the beginning of a file.
+///
+/// A leading byte-order mark (UTF-8, UTF-16LE/BE or UTF-32LE/BE) identifies the
+/// encoding with full confidence, unless a UTF-8 mark is followed by invalid UTF-8, in
+/// which case the content after it is guessed. Without one, UTF-16 is recognized from
+/// the NUL bytes of its ASCII characters, and other encodings are guessed by the
+/// `chardet` library.
+///
+/// # Arguments
+///
+/// * `bytes` - The buffer to examine.
+///
+/// # Returns
+///
+/// The `EncodingGuess`, with the byte-order mark reported separately from the encoding.
+pub fn guess_encoding(bytes: &[u8]) -> EncodingGuess {
+    if let Some(bom) = Bom::detect(bytes) {
+        // A character cut at the end of the buffer does not make the content invalid.
+        let content = &bytes[3.min(bytes.len())..];
+        if bom == Bom::Utf8 && std::str::from_utf8(content).is_err_and(|e| e.error_len().is_some()) {
+            let (encoding, confidence, _) = detect(content);
+            return EncodingGuess { encoding, confidence: confidence.into(), bom: Some(bom) };
+        }
+        return EncodingGuess { encoding: bom.encoding().to_string(), confidence: 1.0, bom: Some(bom) };
+    }
+    if let Some((encoding, confidence)) = utf16_from_nuls(bytes) {
+        return EncodingGuess { encoding: encoding.label().to_string(), confidence, bom: None };
+    }
 
     // Detect encoding using the `chardet` library.
-    let detection = detect(&buffer);
-    let encoding = detection.0;  // Detected encoding as a string.
-    let confidence = detection.1; // Confidence level of detection as a float.
+    let (encoding, confidence, _) = detect(bytes);
+    EncodingGuess { encoding, confidence: confidence.into(), bom: None }
+}
 
-    // If the BOM is detected, explicitly specify "UTF-8 with BOM".
-    let encoding = if is_utf8_bom {
-        "UTF-8 with BOM".to_string()
+/// Recognizes UTF-16 text without a byte-order mark from the pattern of its NUL bytes.
+///
+/// Characters below U+0100, such as the delimiters and digits of a CSV file, have a NUL
+/// high byte: at odd offsets in UTF-16LE and at even offsets in UTF-16BE. Text is taken
+/// as UTF-16 when at least a third of the code units have a NUL on one side and almost
+/// none on the other (which also rules out UTF-32 and binary data).
+///
+/// # Returns
+///
+/// The UTF-16 variant and the confidence of the guess, or `None` if the pattern is absent.
+fn utf16_from_nuls(bytes: &[u8]) -> Option<(Encoding, f64)> {
+    let units = bytes.len() / 2;
+    if units < 2 {
+        return None;
+    }
+    let nuls = |parity: usize| bytes[..units * 2].iter().skip(parity).step_by(2).filter(|&&b| b == 0).count() as f64 / units as f64;
+    let (even, odd) = (nuls(0), nuls(1));
+    let (encoding, ratio, other) = if odd > even { (Encoding::Utf16Le, odd, even) } else { (Encoding::Utf16Be, even, odd) };
+    if ratio >= 1.0 / 3.0 && other <= 0.02 {
+        // Mostly-ASCII text has a NUL in almost every unit; scale the confidence with it.
+        Some((encoding, (0.5 + ratio / 2.0).min(0.99)))
     } else {
-        encoding.to_string()
+        None
+    }
+}
+
+/// Number of characters of text shown on each side of an invalid UTF-8 sequence.
+const CONTEXT_CHARS: usize = 20;
+
+/// Maximum number of bytes of the lines with invalid sequences used to guess their encoding.
+const GUESS_SAMPLE_BYTES: usize = 64 * 1024;
+
+/// An invalid UTF-8 sequence found in a file.
+#[derive(Debug, Clone, Serialize)]
+pub struct InvalidSequence {
+    pub offset: u64,                 // Byte offset of the sequence in the file (0-based)
+    pub line: u64,                   // Line number (1-based)
+    pub column: u64,                 // Character position in the line (1-based)
+    pub bytes: String,               // The offending bytes in hex, e.g. `E9` or `93 FA`
+    pub context: String,             // Text around the sequence, which is shown as `\u{FFFD}`
+    pub decoded: Option<String>,     // The bytes decoded in `Utf8Validation::legacy_encoding`
+    #[serde(skip)]
+    raw: Vec<u8>,                    // The offending bytes
+    #[serde(skip)]
+    next: Option<u8>,                // The byte after them in the line, if any
+}
+
+/// The outcome of checking a file for invalid UTF-8.
+#[derive(Debug, Clone, Serialize)]
+pub struct Utf8Validation {
+    pub bytes_scanned: u64,                 // Size of the file
+    pub invalid_count: u64,                 // Number of invalid sequences in the whole file
+    pub sequences: Vec<InvalidSequence>,    // The first invalid sequences, up to the limit
+    pub legacy_encoding: Option<Encoding>,  // Encoding the sequences appear to be in
+}
+
+impl Utf8Validation {
+    /// Returns `true` if the file is valid UTF-8.
+    pub fn is_valid(&self) -> bool {
+        self.invalid_count == 0
+    }
+}
+
+/// Checks that a file is valid UTF-8 and locates its invalid sequences.
+///
+/// The file is streamed line by line. Consecutive invalid bytes count as one sequence.
+/// All sequences are counted, and the first `limit` are listed with their position,
+/// bytes and context. Their encoding is guessed from the lines that contain them, and
+/// each sequence is decoded in it (Windows-1252 when no other legacy encoding is
+/// recognized), which usually shows the character that was meant.
+///
+/// # Arguments
+///
+/// * `path` - The path to the file to check.
+/// * `limit` - The maximum number of sequences to list.
+///
+/// # Returns
+///
+/// The `Utf8Validation`, or an `io::Error` if the file cannot be read.
+pub fn validate_utf8(path: &Path, limit: usize) -> io::Result<Utf8Validation> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut validation = Utf8Validation { bytes_scanned: 0, invalid_count: 0, sequences: Vec::new(), legacy_encoding: None };
+    let mut guess_sample = Vec::new(); // Lines with invalid sequences, to guess their encoding
+    let mut line = Vec::new();
+    let mut line_number = 0;
+
+    loop {
+        line.clear();
+        if reader.read_until(b'\n', &mut line)? == 0 {
+            break;
+        }
+        line_number += 1;
+        let line_offset = validation.bytes_scanned;
+        validation.bytes_scanned += line.len() as u64;
+
+        let mut pos = 0; // Byte position in the line
+        let mut column = 1; // Character position of `pos` in the line
+        let mut found = false;
+        while let Err(e) = std::str::from_utf8(&line[pos..]) {
+            let start = pos + e.valid_up_to();
+            column += std::str::from_utf8(&line[pos..start]).map_or(0, |valid| valid.chars().count()) as u64;
+            // Take the whole run of invalid bytes as one sequence.
+            let mut end = start + e.error_len().unwrap_or(line.len() - start);
+            while let Err(e) = std::str::from_utf8(&line[end..]) {
+                if e.valid_up_to() > 0 {
+                    break;
+                }
+                end += e.error_len().unwrap_or(line.len() - end);
+            }
+
+            validation.invalid_count += 1;
+            found = true;
+            if validation.sequences.len() < limit {
+                validation.sequences.push(InvalidSequence {
+                    offset: line_offset + start as u64,
+                    line: line_number,
+                    column,
+                    bytes: line[start..end].iter().map(|b| format!("{:02X}", b)).collect::<Vec<_>>().join(" "),
+                    context: context(&line, start, end),
+                    decoded: None,
+                    raw: line[start..end].to_vec(),
+                    next: line.get(end).copied(),
+                });
+            }
+            column += 1;
+            pos = end;
+        }
+        if found && guess_sample.len() < GUESS_SAMPLE_BYTES {
+            guess_sample.extend_from_slice(&line);
+        }
+    }
+
+    // Decode the listed sequences in the encoding their lines appear to be in.
+    if validation.invalid_count > 0 {
+        let legacy = Encoding::from_detected(&guess_encoding(&guess_sample).encoding)
+            .filter(|encoding| !encoding.is_unicode())
+            .unwrap_or(Encoding::Windows1252);
+        for sequence in &mut validation.sequences {
+            sequence.decoded = decode_sequence(legacy, &sequence.raw, sequence.next);
+        }
+        validation.legacy_encoding = Some(legacy);
+    }
+    Ok(validation)
+}
+
+/// Returns the text around the bytes `start..end` of a line, with those bytes shown as
+/// a single replacement character.
+fn context(line: &[u8], start: usize, end: usize) -> String {
+    let before = String::from_utf8_lossy(&line[..start]);
+    let after = String::from_utf8_lossy(&line[end..]);
+    let skip = before.chars().count().saturating_sub(CONTEXT_CHARS);
+    let before: String = before.chars().skip(skip).collect();
+    let after: String = after.trim_end_matches(['\r', '\n']).chars().take(CONTEXT_CHARS).collect();
+    format!("{}\u{FFFD}{}", before, after)
+}
+
+/// Decodes an invalid sequence in a legacy encoding.
+///
+/// Multi-byte encodings such as Shift_JIS have trail bytes in the ASCII range, which
+/// UTF-8 accepts, so the `next` byte is included when the sequence alone does not decode.
+fn decode_sequence(encoding: Encoding, raw: &[u8], next: Option<u8>) -> Option<String> {
+    let decode = |bytes: &[u8]| {
+        let (text, had_errors) = encoding.decode(bytes);
+        (!had_errors).then(|| text.into_owned())
     };
+    decode(raw).or_else(|| {
+        let mut extended = raw.to_vec();
+        extended.push(next?);
+        decode(&extended)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    fn read_decoded(path: &str, encoding: Encoding) -> String {
+        let mut text = String::new();
+        open_decoded(Path::new(path), encoding).unwrap().read_to_string(&mut text).unwrap();
+        text
+    }
+
+    #[test]
+    fn parses_names_labels_and_aliases() {
+        for encoding in Encoding::ALL {
+            assert_eq!(encoding.name().parse(), Ok(encoding));
+            assert_eq!(encoding.label().parse(), Ok(encoding));
+        }
+        assert_eq!("UTF_16le".parse(), Ok(Encoding::Utf16Le));
+        assert_eq!(" Windows 1252 ".parse(), Ok(Encoding::Windows1252));
+        assert_eq!("cp1252".parse(), Ok(Encoding::Windows1252));
+        assert_eq!("sjis".parse(), Ok(Encoding::ShiftJis));
+        assert_eq!("gb2312".parse(), Ok(Encoding::Gbk));
+        assert_eq!("ks_c_5601-1987".parse(), Ok(Encoding::EucKr));
 
-    // Return the detected encoding and the confidence level.
-    Ok((encoding, confidence.into()))
-}
\ No newline at end of file
+        let error = "koi8-r".parse::<Encoding>().unwrap_err();
+        assert!(error.starts_with("Invalid encoding 'koi8-r'. Use one of utf8, utf8bom,"), "{}", error);
+    }
+
+    #[test]
+    fn parses_input_encodings() {
+        assert_eq!("auto".parse(), Ok(InputEncoding::Auto));
+        assert_eq!("Auto-detect".parse(), Ok(InputEncoding::Auto));
+        assert_eq!("latin1".parse(), Ok(InputEncoding::Fixed(Encoding::Latin1)));
+        assert!("ebcdic".parse::<InputEncoding>().unwrap_err().ends_with("Use 'auto' to detect it."));
+        for input in InputEncoding::all() {
+            assert_eq!(String::from(input).parse(), Ok(input));
+        }
+    }
+
+    #[test]
+    fn maps_detected_names_to_encodings() {
+        assert_eq!(Encoding::from_detected("ascii"), Some(Encoding::Utf8));
+        assert_eq!(Encoding::from_detected("UTF-8 with BOM"), Some(Encoding::Utf8Bom));
+        assert_eq!(Encoding::from_detected("windows-1251"), Some(Encoding::Windows1251));
+        assert_eq!(Encoding::from_detected("SHIFT_JIS"), Some(Encoding::ShiftJis));
+        assert_eq!(Encoding::from_detected("EUC-TW"), None);
+    }
+
+    #[test]
+    fn decodes_legacy_input() {
+        let dir = TempDir::new();
+        let cp1252 = dir.write("cp1252.csv", b"name\ncaf\xe9 \x80\n");
+        assert_eq!(read_decoded(&cp1252, Encoding::Windows1252), "name\ncafé €\n");
+        let sjis = dir.write("sjis.csv", b"name\n\x93\xfa\x96\x7b\n");
+        assert_eq!(read_decoded(&sjis, Encoding::ShiftJis), "name\n日本\n");
+        let cp1251 = dir.write("cp1251.csv", b"name\n\xcc\xee\xf1\xea\xe2\xe0\n");
+        assert_eq!(read_decoded(&cp1251, Encoding::Windows1251), "name\nМосква\n");
+    }
+
+    #[test]
+    fn decodes_latin1_byte_for_byte() {
+        let bytes: Vec<u8> = (0x20..=0xFF).collect();
+        let expected: String = (0x20..=0xFFu8).map(char::from).collect();
+        assert_eq!(Encoding::Latin1.decode(&bytes), (Cow::Owned(expected.clone()), false));
+
+        let mut text = String::new();
+        decode_reader(bytes.as_slice(), Encoding::Latin1).read_to_string(&mut text).unwrap();
+        assert_eq!(text, expected);
+
+        // One byte at a time, every character takes two reads.
+        let mut reader = Latin1Reader::new(&b"\x80\xe9"[..], true);
+        let mut byte = [0];
+        let mut utf8 = Vec::new();
+        while reader.read(&mut byte).unwrap() == 1 {
+            utf8.push(byte[0]);
+        }
+        assert_eq!(String::from_utf8(utf8).unwrap(), "\u{80}é");
+
+        let dir = TempDir::new();
+        let utf8 = dir.write("utf8bom.csv", "\u{feff}café\n");
+        assert_eq!(read_decoded(&utf8, Encoding::Latin1), "café\n");
+    }
+
+    #[test]
+    fn byte_order_mark_takes_precedence_when_decoding() {
+        let dir = TempDir::new();
+        let utf16 = dir.write("utf16.csv", b"\xff\xfei\x00d\x00\n\x00");
+        assert_eq!(read_decoded(&utf16, Encoding::Windows1252), "id\n");
+        assert_eq!(read_decoded(&utf16, Encoding::Utf8), "id\n");
+        let utf8 = dir.write("utf8bom.csv", "\u{feff}café\n");
+        assert_eq!(read_decoded(&utf8, Encoding::Utf8), "café\n");
+        assert_eq!(read_decoded(&utf8, Encoding::ShiftJis), "café\n");
+    }
+
+    #[test]
+    fn aligns_samples_to_utf8_characters() {
+        let text = "é日😀".as_bytes(); // 2, 3 and 4 bytes
+        assert_eq!(char_aligned(text), text);
+        assert_eq!(char_aligned(&text[1..]), "日😀".as_bytes());
+        assert_eq!(char_aligned(&text[3..]), "😀".as_bytes());
+        assert_eq!(char_aligned(&text[..text.len() - 1]), "é日".as_bytes());
+        assert_eq!(char_aligned(&text[..4]), "é".as_bytes());
+        assert_eq!(char_aligned(&text[..5]), "é日".as_bytes());
+        // A final byte that looks like the start of a character is dropped whatever it is.
+        assert_eq!(char_aligned(b"caf\xe9"), b"caf");
+        assert_eq!(char_aligned(b"\x80\x80\x80\x80a"), b"\x80a");
+        assert_eq!(char_aligned(b""), b"");
+    }
+
+    #[test]
+    fn sampled_modes_cut_at_character_boundaries() {
+        let dir = TempDir::new();
+        let path = dir.write("sample.csv", "é".repeat(3000));
+
+        let detection = detect_encoding_with(Path::new(&path), DetectionMode::Head(1001)).unwrap();
+        assert_eq!((detection.encoding.as_str(), detection.bytes_examined, detection.valid_utf8), ("utf-8", 1000, None));
+        let detection = detect_encoding_with(Path::new(&path), DetectionMode::Spread(999)).unwrap();
+        assert_eq!(detection.bytes_examined, 3 * 332);
+        let detection = detect_encoding_with(Path::new(&path), DetectionMode::Head(6000)).unwrap();
+        assert_eq!((detection.bytes_examined, detection.valid_utf8, detection.is_complete()), (6000, Some(true), true));
+    }
+
+    #[test]
+    fn full_scan_validates_characters_split_across_chunks() {
+        let mut text = "a".repeat(SCAN_CHUNK_BYTES - 1) + "é\n";
+        text.push_str(&"b".repeat(SCAN_CHUNK_BYTES - 2));
+        text.push_str("日\n");
+        let detection = scan_encoding(text.as_bytes(), text.len() as u64).unwrap();
+        assert_eq!((detection.encoding.as_str(), detection.confidence), ("UTF-8", 1.0));
+        assert_eq!((detection.bytes_examined, detection.valid_utf8), (text.len() as u64, Some(true)));
+
+        // A character cut off by the end of the file is invalid.
+        let bytes = &text.as_bytes()[..text.len() - 2];
+        let detection = scan_encoding(bytes, bytes.len() as u64).unwrap();
+        assert_eq!(detection.valid_utf8, Some(false));
+
+        let mut bytes = vec![b'a'; SCAN_CHUNK_BYTES * 2];
+        bytes[SCAN_CHUNK_BYTES + 10] = 0xE9;
+        let detection = scan_encoding(bytes.as_slice(), bytes.len() as u64).unwrap();
+        assert_eq!(detection.valid_utf8, Some(false));
+        assert_ne!(detection.encoding, "UTF-8");
+    }
+
+    #[test]
+    fn full_scan_reports_ascii_and_byte_order_marks() {
+        let ascii = "id,name\n".repeat(20_000);
+        let detection = scan_encoding(ascii.as_bytes(), ascii.len() as u64).unwrap();
+        assert_eq!((detection.encoding.as_str(), detection.valid_utf8), ("ascii", Some(true)));
+
+        let utf8 = format!("\u{feff}{}é", ascii);
+        let detection = scan_encoding(utf8.as_bytes(), utf8.len() as u64).unwrap();
+        assert_eq!((detection.label().as_str(), detection.bom, detection.is_complete()), ("UTF-8 with BOM", Some(Bom::Utf8), true));
+
+        // A UTF-8 mark followed by invalid UTF-8 is not taken at its word.
+        let mislabelled = [b"\xef\xbb\xbf".as_slice(), ascii.as_bytes(), b"caf\xe9 cr\xe8me br\xfbl\xe9e\n"].concat();
+        let detection = scan_encoding(mislabelled.as_slice(), mislabelled.len() as u64).unwrap();
+        assert_eq!((detection.bom, detection.valid_utf8), (Some(Bom::Utf8), Some(false)));
+        assert_ne!(detection.encoding, "UTF-8");
+        assert!(detection.confidence < 1.0);
+        assert_eq!(detection.label(), detection.encoding);
+        let guess = guess_encoding(&mislabelled);
+        assert_eq!((guess.bom, guess.label()), (Some(Bom::Utf8), guess.encoding.clone()));
+        assert!(guess.confidence < 1.0);
+        assert_eq!(guess_encoding(b"\xef\xbb\xbfcaf\xc3").label(), "UTF-8 with BOM");
+
+        // UTF-16 is recognized from the first chunk alone.
+        let utf16: Vec<u8> = ascii.encode_utf16().flat_map(u16::to_be_bytes).collect();
+        let detection = scan_encoding(utf16.as_slice(), utf16.len() as u64).unwrap();
+        assert_eq!((detection.encoding.as_str(), detection.bom, detection.valid_utf8), ("UTF-16BE", None, None));
+        assert_eq!(detection.bytes_examined, SCAN_CHUNK_BYTES as u64);
+    }
+
+    #[test]
+    fn detects_byte_order_marks() {
+        assert_eq!(Bom::detect(b"\xef\xbb\xbfid"), Some(Bom::Utf8));
+        // The UTF-32LE mark starts with the UTF-16LE one, and must win over it.
+        assert_eq!(Bom::detect(b"\xff\xfe\x00\x00i\x00\x00\x00"), Some(Bom::Utf32Le));
+        assert_eq!(Bom::detect(b"\xff\xfei\x00d\x00"), Some(Bom::Utf16Le));
+        assert_eq!(Bom::detect(b"\xff\xfe\x00"), Some(Bom::Utf16Le));
+        assert_eq!(Bom::detect(b"\x00\x00\xfe\xff"), Some(Bom::Utf32Be));
+        assert_eq!(Bom::detect(b"\xfe\xff\x00i"), Some(Bom::Utf16Be));
+        assert_eq!(Bom::detect(b"\xef\xbb"), None);
+        for bom in [Bom::Utf8, Bom::Utf16Le, Bom::Utf16Be, Bom::Utf32Le, Bom::Utf32Be] {
+            assert_eq!(Bom::detect(bom.bytes()), Some(bom));
+            assert_eq!(guess_encoding(bom.bytes()), EncodingGuess { encoding: bom.encoding().to_string(), confidence: 1.0, bom: Some(bom) });
+        }
+    }
+
+    #[test]
+    fn recognizes_utf16_from_nul_bytes() {
+        let le: Vec<u8> = "id,name".encode_utf16().flat_map(u16::to_le_bytes).collect();
+        let be: Vec<u8> = "id,name".encode_utf16().flat_map(u16::to_be_bytes).collect();
+        assert_eq!(utf16_from_nuls(&le), Some((Encoding::Utf16Le, 0.99)));
+        assert_eq!(utf16_from_nuls(&be), Some((Encoding::Utf16Be, 0.99)));
+        assert_eq!(utf16_from_nuls(b"a\x00"), None); // Too short to tell
+        assert_eq!(utf16_from_nuls(b"id,name"), None);
+
+        // At least a third of the units need a NUL byte.
+        let third: Vec<u8> = "a日本".encode_utf16().flat_map(u16::to_le_bytes).collect();
+        assert_eq!(utf16_from_nuls(&third), Some((Encoding::Utf16Le, 0.5 + 1.0 / 6.0)));
+        let quarter: Vec<u8> = "a日本語".encode_utf16().flat_map(u16::to_le_bytes).collect();
+        assert_eq!(utf16_from_nuls(&quarter), None);
+
+        // And at most 2% of them may have one on the other side.
+        let mut units = "a".repeat(50).encode_utf16().flat_map(u16::to_le_bytes).collect::<Vec<u8>>();
+        units[0] = 0;
+        assert_eq!(utf16_from_nuls(&units).map(|(encoding, _)| encoding), Some(Encoding::Utf16Le));
+        units[2] = 0;
+        assert_eq!(utf16_from_nuls(&units), None);
+
+        // UTF-32 has NUL bytes on both sides.
+        let utf32: Vec<u8> = "id,name".chars().flat_map(|c| (c as u32).to_le_bytes()).collect();
+        assert_eq!(utf16_from_nuls(&utf32), None);
+    }
+
+    #[test]
+    fn rejects_utf32_input() {
+        let dir = TempDir::new();
+        let le: Vec<u8> = "\u{feff}id\n".chars().flat_map(|c| (c as u32).to_le_bytes()).collect();
+        let be: Vec<u8> = "\u{feff}id\n".chars().flat_map(|c| (c as u32).to_be_bytes()).collect();
+        for (name, bytes, bom) in [("le.csv", le, "UTF-32LE"), ("be.csv", be, "UTF-32BE")] {
+            let path = dir.write(name, bytes);
+            let expected = format!("'{}' starts with a {} BOM: UTF-32 input is not supported", path, bom);
+            for input in [InputEncoding::Auto, InputEncoding::Fixed(Encoding::Utf16Le), InputEncoding::Fixed(Encoding::Utf8)] {
+                assert_eq!(resolve_input_encoding(Path::new(&path), input).unwrap_err().to_string(), expected);
+            }
+            assert_eq!(open_decoded(Path::new(&path), Encoding::Utf16Le).err().unwrap().to_string(), expected);
+        }
+
+        let path = dir.write("utf16.csv", b"\xff\xfei\x00d\x00");
+        assert_eq!(resolve_input_encoding(Path::new(&path), InputEncoding::Auto).unwrap(), Encoding::Utf16Le);
+        assert_eq!(read_decoded(&path, Encoding::Utf8), "id");
+    }
+
+    #[test]
+    fn locates_invalid_utf8_sequences() {
+        let dir = TempDir::new();
+        let path = dir.write("invalid.csv", b"id,name\ncaf\xc3\xa9,\xe9t\xe9\n\xe6\x97\xa5\xe6\x9c\xac,\xe9\xe8x\n");
+        let validation = validate_utf8(Path::new(&path), 10).unwrap();
+        assert!(!validation.is_valid());
+        assert_eq!((validation.bytes_scanned, validation.invalid_count), (29, 3));
+        let found: Vec<_> = validation.sequences.iter()
+            .map(|s| (s.offset, s.line, s.column, s.bytes.as_str(), s.context.as_str()))
+            .collect();
+        assert_eq!(found, [
+            (14, 2, 6, "E9", "café,\u{fffd}t\u{fffd}"),
+            (16, 2, 8, "E9", "café,\u{fffd}t\u{fffd}"),
+            (25, 3, 4, "E9 E8", "日本,\u{fffd}x"),
+        ]);
+        assert!(validation.legacy_encoding.is_some_and(|encoding| !encoding.is_unicode()));
+        assert!(validation.sequences.iter().all(|s| s.decoded.is_some()));
+    }
+
+    #[test]
+    fn lists_sequences_up_to_the_limit() {
+        let dir = TempDir::new();
+        let path = dir.write("invalid.csv", b"\xe9\n".repeat(5));
+        let validation = validate_utf8(Path::new(&path), 2).unwrap();
+        assert_eq!((validation.invalid_count, validation.sequences.len()), (5, 2));
+        assert_eq!(validation.sequences[1].line, 2);
+
+        let path = dir.write("valid.csv", "id,name\n1,café\n");
+        let validation = validate_utf8(Path::new(&path), 2).unwrap();
+        assert!(validation.is_valid());
+        assert_eq!((validation.bytes_scanned, validation.legacy_encoding), (16, None));
+    }
+
+    #[test]
+    fn decodes_sequences_in_the_legacy_encoding() {
+        assert_eq!(decode_sequence(Encoding::Windows1252, b"\xe9", Some(b't')), Some("é".to_string()));
+        assert_eq!(decode_sequence(Encoding::Windows1252, b"\x80", None), Some("€".to_string()));
+        assert_eq!(decode_sequence(Encoding::Latin1, b"\x80", None), Some("\u{80}".to_string()));
+        // The trail byte of the last Shift_JIS character is ASCII, so it comes from `next`.
+        assert_eq!(decode_sequence(Encoding::ShiftJis, b"\x93\xfa\x96", Some(0x7B)), Some("日本".to_string()));
+        assert_eq!(decode_sequence(Encoding::ShiftJis, b"\x93\xfa\x96", None), None);
+        assert_eq!(decode_sequence(Encoding::Gbk, b"\x81", Some(b'\n')), None);
+    }
+
+    #[test]
+    fn passes_invalid_utf8_through() {
+        let dir = TempDir::new();
+        let path = dir.write("invalid.csv", b"caf\xe9\n");
+        let mut bytes = Vec::new();
+        open_decoded(Path::new(&path), Encoding::Utf8).unwrap().read_to_end(&mut bytes).unwrap();
+        assert_eq!(bytes, b"caf\xe9\n");
+    }
+}
@@ -2,140 +2,1069 @@
 
 use eframe::egui;
 use rfd::FileDialog;
-use rust_csv_scripts::encoding::{detect_encoding, Encoding};
-use rust_csv_scripts::compare_csv;
+use rust_csv_scripts::encoding::{detect_encoding_with, Detection, DetectionMode, Encoding, InputEncoding};
+use rust_csv_scripts::transcode::UnencodablePolicy;
+use rust_csv_scripts::csv_compare::{compare_with_options, read_headers, CompareOptions};
+use rust_csv_scripts::dialect::Dialect;
+use rust_csv_scripts::diff::diff_files;
+use rust_csv_scripts::preview::{preview_file, FilePreview};
+use rust_csv_scripts::config::{load_profiles, save_profile};
+use rust_csv_scripts::progress::{Cancelled, Progress};
+use serde::{Deserialize, Serialize};
 use std::path::Path;
+use std::process::Command;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use crate::diff_view::DiffView;
+use crate::gui_state::{
+    delimiter_name, ColumnList, CompareResult, Effect, FileSlot, History, Message, Status, TabState,
+};
 
 /// Entry point to run the graphical user interface (GUI) for the CSV comparison tool.
 pub fn run_gui() {
     let options = eframe::NativeOptions::default();
-    eframe::run_native("CSV Compare", options, Box::new(|_cc| Box::new(MyApp::default())))
+    eframe::run_native("CSV Compare", options, Box::new(|cc| Box::new(MyApp::new(cc))))
         .expect("Failed to launch the GUI");
 }
 
+const PREVIEW_ROWS: usize = 10; // Number of rows shown in the file previews
+
+/// The files, input encodings and dialect a tab's columns and previews were read with.
+type ColumnsSource = ([String; 2], [InputEncoding; 2], Dialect);
+
+/// The previews and headers of a tab's two files, read on a worker thread.
+struct FileInfo {
+    previews: [Option<Result<FilePreview, String>>; 2], // Preview of each selected file
+    headers: [Vec<String>; 2],                          // Header row of each file (empty if unreadable)
+}
+
 /// The main application structure for the GUI.
+///
+/// Each comparison lives in its own tab; tabs run independently, so several comparisons
+/// can be in progress at once. The behavior of a tab lives in `gui_state::TabState`;
+/// this file draws it and runs the work it asks for. The tabs' settings, recent
+/// comparisons and presets are saved between sessions through eframe's storage; fields
+/// marked `skip` only live for the current session.
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
 struct MyApp {
-    file1: String,              // File path for the first CSV file
-    file2: String,              // File path for the second CSV file
-    encoding: String,           // Encoding type for file comparison (e.g., UTF-8, UTF-8 with BOM)
-    ignore_columns: String,     // Comma-separated list of columns to ignore during comparison
+    tabs: Vec<ComparisonTab>,   // The open comparison tabs (never empty)
+    selected_tab: usize,        // Index in `tabs` of the tab shown
     encoding_file: String,      // File path for the file to check its encoding
+    full_scan: bool,            // Examine the whole file instead of samples of it
+    #[serde(skip)]
     encoding_result: String,    // Result of encoding check (detected encoding and confidence level)
+    #[serde(skip)]
+    encoding_task: Option<Receiver<Result<Detection, String>>>, // Encoding check running in the background
+    #[serde(skip)]
+    encoding_queued: bool,      // A file was dropped during the check, to be checked once it ends
+    #[serde(skip)]
+    profiles: Vec<String>,      // Names of the profiles found in csvscripts.toml files
+    selected_profile: String,   // Profile chosen in the profile selector
+    #[serde(skip)]
+    profile_name: String,       // Name under which the current options are saved
+    #[serde(skip)]
+    profile_status: String,     // Result of the last profile load or save
+    history: History,           // Recent comparisons and presets
+    selected_preset: String,    // Preset chosen in the preset selector
+    #[serde(skip)]
+    preset_name: String,        // Name under which the current comparison is saved as a preset
+    #[serde(skip)]
+    status: Option<Status>,     // Message shown in the status bar
+    #[serde(skip)]
+    drop_zones: DropZones,      // Where dropped files go, as laid out in the last frame
+}
+
+/// A comparison tab: its state, and what the window shows and runs for it.
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+struct ComparisonTab {
+    state: TabState,            // Files, options and results, changed through messages
+    #[serde(skip)]
+    columns: Vec<ColumnInfo>,   // Columns found in the headers of the two files
+    #[serde(skip)]
+    columns_source: Option<ColumnsSource>, // Files, encodings and dialect the columns and previews were read with
+    #[serde(skip)]
+    previews: [Option<Result<FilePreview, String>>; 2], // Preview of each selected file
+    #[serde(skip)]
+    file_info_task: Option<Receiver<FileInfo>>, // Previews and headers being read in the background
+    #[serde(skip)]
+    column_filter: String,      // Search text narrowing the column checklist
+    #[serde(skip)]
+    task: Option<Task>,         // Comparison or diff running in the background
+    #[serde(skip)]
+    diff_view: Option<DiffView>, // Side-by-side view of the differences, while open
+}
+
+/// The areas of the window that accept dropped files.
+#[derive(Default)]
+struct DropZones {
+    file1: Option<egui::Rect>,     // The File 1 selection of the shown tab
+    file2: Option<egui::Rect>,     // The File 2 selection of the shown tab
+    encoding: Option<egui::Rect>,  // The encoding check section
+}
+
+/// Where a dropped file goes.
+#[derive(Clone, Copy, PartialEq)]
+enum DropTarget {
+    File(FileSlot),
+    Encoding,
+}
+
+impl DropZones {
+    /// Returns the area under `pos` and its target, if any.
+    fn at(&self, pos: egui::Pos2) -> Option<(DropTarget, egui::Rect)> {
+        [
+            (DropTarget::File(FileSlot::First), self.file1),
+            (DropTarget::File(FileSlot::Second), self.file2),
+            (DropTarget::Encoding, self.encoding),
+        ]
+            .into_iter()
+            .find_map(|(target, rect)| rect.filter(|r| r.contains(pos)).map(|r| (target, r)))
+    }
+}
+
+/// A column of the selected files, as listed in the column checklist.
+struct ColumnInfo {
+    name: String,    // Header of the column
+    in_file1: bool,  // Whether the first file has this column
+    in_file2: bool,  // Whether the second file has this column
+}
+
+/// A comparison or diff running on a worker thread.
+struct Task {
+    progress: Arc<Progress>,      // Counters updated by the worker, and its cancellation flag
+    receiver: Receiver<Message>,  // Receives the outcome once the worker is done
+}
+
+impl Task {
+    /// Returns the outcome sent by the worker once it is done, or `None` while it runs.
+    fn poll(&self) -> Option<Message> {
+        match self.receiver.try_recv() {
+            Ok(message) => Some(message),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => Some(Message::Failed("The background task stopped unexpectedly".to_string())),
+        }
+    }
+
+    /// Draws the progress of the task, with a button to stop it.
+    fn progress_bar(&self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            let progress = &self.progress;
+            let text = format!("{} rows, {:.1} of {:.1} MB read", progress.rows(),
+                progress.bytes_read() as f64 / 1e6, progress.total_bytes() as f64 / 1e6);
+            match progress.fraction() {
+                Some(fraction) => ui.add(egui::ProgressBar::new(fraction).text(text)),
+                None => ui.add(egui::Spinner::new()),
+            };
+            if ui.button("Cancel").clicked() {
+                progress.cancel();
+            }
+        });
+    }
 }
 
 /// Default implementation for `MyApp` to initialize all fields with default values.
 impl Default for MyApp {
     fn default() -> Self {
         Self {
-            file1: String::new(),
-            file2: String::new(),
-            encoding: String::from("UTF-8"),
-            ignore_columns: String::new(),
+            tabs: vec![ComparisonTab::default()],
+            selected_tab: 0,
             encoding_file: String::new(),
+            full_scan: false,
             encoding_result: String::new(),
+            encoding_task: None,
+            encoding_queued: false,
+            profiles: Vec::new(),
+            selected_profile: String::new(),
+            profile_name: String::new(),
+            profile_status: String::new(),
+            status: None,
+            history: History::default(),
+            selected_preset: String::new(),
+            preset_name: String::new(),
+            drop_zones: DropZones::default(),
+        }
+    }
+}
+
+impl MyApp {
+    /// Creates the application with the settings of the previous session, and loads the
+    /// available profiles.
+    fn new(cc: &eframe::CreationContext<'_>) -> Self {
+        let mut app: Self = cc.storage
+            .and_then(|storage| eframe::get_value(storage, eframe::APP_KEY))
+            .unwrap_or_default();
+        if app.tabs.is_empty() {
+            app.tabs.push(ComparisonTab::default());
+        }
+        app.selected_tab = app.selected_tab.min(app.tabs.len() - 1);
+        app.reload_profiles();
+        app
+    }
+
+    /// Returns the tab shown in the window.
+    fn tab(&mut self) -> &mut ComparisonTab {
+        &mut self.tabs[self.selected_tab]
+    }
+
+    /// Draws one button per tab, and the buttons to add, duplicate and close tabs.
+    fn tab_bar(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal_wrapped(|ui| {
+            for (i, tab) in self.tabs.iter().enumerate() {
+                let mut title = tab.state.title();
+                if tab.state.is_busy() {
+                    title.push_str(" (running)");
+                }
+                if ui.selectable_label(i == self.selected_tab, title).clicked() {
+                    self.selected_tab = i;
+                }
+            }
+            ui.separator();
+            if ui.button("New Tab").clicked() {
+                self.tabs.push(ComparisonTab::default());
+                self.selected_tab = self.tabs.len() - 1;
+            }
+            // The copy has the same files and options, but none of the results.
+            if ui.button("Duplicate Tab").clicked() {
+                let copy = TabState::from_saved(&self.tab().state.saved_comparison());
+                self.selected_tab += 1;
+                self.tabs.insert(self.selected_tab, ComparisonTab { state: copy, ..ComparisonTab::default() });
+            }
+            if ui.add_enabled(self.tabs.len() > 1, egui::Button::new("Close Tab")).clicked() {
+                let closed = self.tabs.remove(self.selected_tab);
+                if let Some(task) = closed.task {
+                    task.progress.cancel(); // Nobody is left to see the result
+                }
+                self.selected_tab = self.selected_tab.min(self.tabs.len() - 1);
+            }
+        });
+    }
+
+    /// Draws the recent comparisons, each with a button to run it again in the shown tab,
+    /// and the presets.
+    fn history_panel(&mut self, ui: &mut egui::Ui) {
+        let busy = self.tab().state.is_busy();
+        let mut rerun = None;
+
+        egui::CollapsingHeader::new("Recent Comparisons").show(ui, |ui| {
+            if self.history.recents.is_empty() {
+                ui.label("No comparisons yet");
+            }
+            for (i, recent) in self.history.recents.iter().enumerate() {
+                ui.horizontal(|ui| {
+                    if ui.add_enabled(!busy, egui::Button::new("Run Again")).clicked() {
+                        rerun = Some(i);
+                    }
+                    ui.label(format!("{} vs {}", recent.file1, recent.file2));
+                });
+            }
+        });
+        if let Some(i) = rerun {
+            let saved = self.history.recents[i].clone();
+            self.tab().send(Message::SavedLoaded(saved));
+            self.start_compare();
+        }
+
+        // Presets: named file pairs with their options, kept with the GUI settings
+        ui.label("Preset:");
+        ui.horizontal(|ui| {
+            egui::ComboBox::from_id_source("preset")
+                .selected_text(&self.selected_preset)
+                .show_ui(ui, |ui| {
+                    for name in self.history.presets.keys() {
+                        ui.selectable_value(&mut self.selected_preset, name.clone(), name);
+                    }
+                });
+            if ui.button("Load Preset").clicked() {
+                match self.history.presets.get(&self.selected_preset).cloned() {
+                    Some(preset) => self.tab().send(Message::SavedLoaded(preset)),
+                    None => self.set_status("Select a preset to load".to_string(), false),
+                }
+            }
+            if ui.button("Delete Preset").clicked() && self.history.presets.remove(&self.selected_preset).is_some() {
+                self.selected_preset.clear();
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.text_edit_singleline(&mut self.preset_name);
+            if ui.button("Save Preset").clicked() {
+                let saved = self.tab().state.saved_comparison();
+                if let Some(name) = self.history.save_preset(&self.preset_name, saved) {
+                    self.set_status(format!("Saved preset '{}'", name), false);
+                    self.selected_preset = name;
+                }
+            }
+        });
+    }
+
+    /// Draws the profile selector, which loads options into the shown tab or saves them
+    /// to csvscripts.toml.
+    fn profile_panel(&mut self, ui: &mut egui::Ui) {
+        ui.label("Profile:");
+        ui.horizontal(|ui| {
+            egui::ComboBox::from_id_source("profile")
+                .selected_text(&self.selected_profile)
+                .show_ui(ui, |ui| {
+                    for name in &self.profiles {
+                        ui.selectable_value(&mut self.selected_profile, name.clone(), name);
+                    }
+                });
+            if ui.button("Load Profile").clicked() {
+                match load_profiles(Path::new(".")) {
+                    Ok(config) => match config.profiles.get(&self.selected_profile) {
+                        Some(spec) => {
+                            self.tabs[self.selected_tab].send(Message::ProfileLoaded(spec.clone()));
+                            self.profile_status = format!("Loaded profile '{}'", self.selected_profile);
+                        }
+                        None => self.profile_status = "Select a profile to load".to_string(),
+                    },
+                    Err(e) => self.profile_status = format!("Error loading profiles: {}", e),
+                }
+            }
+            if ui.button("Reload").clicked() {
+                self.reload_profiles();
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.text_edit_singleline(&mut self.profile_name);
+            if ui.button("Save Profile").clicked() && !self.profile_name.trim().is_empty() {
+                // Keep any options of an existing profile that the GUI does not edit.
+                let name = self.profile_name.trim().to_string();
+                let existing = load_profiles(Path::new("."))
+                    .ok()
+                    .and_then(|config| config.profiles.get(&name).cloned())
+                    .unwrap_or_default();
+                let spec = existing.merged_with(&self.tab().state.current_spec());
+                match save_profile(&name, &spec, Path::new(".")) {
+                    Ok(path) => {
+                        self.profile_status = format!("Saved profile '{}' to {}", name, path.display());
+                        self.reload_profiles();
+                    }
+                    Err(e) => self.profile_status = format!("Error saving profile: {}", e),
+                }
+            }
+        });
+        if !self.profile_status.is_empty() {
+            ui.label(&self.profile_status);
+        }
+    }
+
+    /// Re-reads the profile names from the user and project configuration files.
+    fn reload_profiles(&mut self) {
+        match load_profiles(Path::new(".")) {
+            Ok(config) => self.profiles = config.profiles.into_keys().collect(),
+            Err(e) => self.profile_status = format!("Error loading profiles: {}", e),
+        }
+    }
+
+    /// Starts the comparison of the shown tab and adds it to the recent comparisons.
+    fn start_compare(&mut self) {
+        let busy_outputs = self.tabs.iter()
+            .filter_map(|tab| tab.state.busy_output().map(str::to_string))
+            .collect();
+        let tab = self.tab();
+        tab.send(Message::CompareClicked { busy_outputs });
+        if tab.state.is_busy() {
+            let saved = tab.state.saved_comparison();
+            self.history.remember(saved);
+        }
+    }
+
+    /// Starts detecting the encoding of the selected file on a worker thread.
+    fn start_encoding_check(&mut self) {
+        let (sender, receiver) = mpsc::channel();
+        let file = self.encoding_file.clone();
+        let mode = if self.full_scan { DetectionMode::Full } else { DetectionMode::default() };
+        thread::spawn(move || {
+            let result = detect_encoding_with(Path::new(&file), mode).map_err(|e| e.to_string());
+            let _ = sender.send(result);
+        });
+        self.encoding_result.clear();
+        self.set_status(format!("Checking the encoding of '{}'...", self.encoding_file), false);
+        self.encoding_task = Some(receiver);
+    }
+
+    /// Collects the result of the encoding check, if it has finished.
+    fn poll_encoding_task(&mut self) {
+        let Some(receiver) = &self.encoding_task else {
+            return;
+        };
+        let result = match receiver.try_recv() {
+            Ok(result) => result,
+            Err(TryRecvError::Empty) => return,
+            Err(TryRecvError::Disconnected) => Err("The background task stopped unexpectedly".to_string()),
+        };
+        self.encoding_task = None;
+
+        // A file dropped during the check replaced the one it was for, so check it instead.
+        if std::mem::take(&mut self.encoding_queued) {
+            self.start_encoding_check();
+            return;
+        }
+
+        match result {
+            // If encoding detection succeeds, store the result in `encoding_result`
+            Ok(detection) => {
+                let examined = if detection.is_complete() { "whole file".to_string() } else { detection.mode.to_string() };
+                let bom = detection.bom.map_or("no BOM".to_string(), |bom| bom.to_string());
+                self.encoding_result = format!("Encoding: {}, {}, Confidence: {:.2}% (examined: {})",
+                    detection.encoding, bom, detection.confidence * 100.0, examined);
+                self.status = None;
+            }
+            // If encoding detection fails, report it in the status bar
+            Err(e) => self.set_status(format!("Error detecting encoding: {}", e), true),
+        }
+    }
+
+    /// Highlights the drop area while files are dragged over the window, and puts dropped
+    /// files in the slot they were dropped on.
+    ///
+    /// Two or more files dropped anywhere fill both comparison slots of the shown tab. A
+    /// single file fills the slot under the pointer (or the first empty one); dropped on
+    /// the encoding section, its encoding is checked right away, or once the running
+    /// check ends. Files to compare must be CSV files, and two dropped files fill neither
+    /// slot unless both are.
+    fn handle_dropped_files(&mut self, ctx: &egui::Context) {
+        let pointer = ctx.input(|i| i.pointer.hover_pos());
+        let target = pointer.and_then(|pos| self.drop_zones.at(pos));
+
+        // While dragging, dim the window and outline the area the file would go to.
+        if ctx.input(|i| !i.raw.hovered_files.is_empty()) {
+            let painter = ctx.layer_painter(egui::LayerId::new(egui::Order::Foreground, egui::Id::new("drop_overlay")));
+            let screen = ctx.screen_rect();
+            painter.rect_filled(screen, 0.0, egui::Color32::from_black_alpha(96));
+            if let Some((_, rect)) = target {
+                painter.rect_stroke(rect.expand(4.0), 4.0, egui::Stroke::new(2.0, ctx.style().visuals.selection.stroke.color));
+            }
+            painter.text(screen.center(), egui::Align2::CENTER_CENTER, "Drop files here",
+                egui::TextStyle::Heading.resolve(&ctx.style()), egui::Color32::WHITE);
+        }
+
+        let dropped: Vec<String> = ctx.input(|i| i.raw.dropped_files.iter()
+            .filter_map(|f| f.path.as_ref().map(|p| p.display().to_string()))
+            .collect());
+        match dropped.as_slice() {
+            [] => {}
+            [file] => match target.map(|(t, _)| t) {
+                Some(DropTarget::Encoding) => {
+                    self.set_dropped(DropTarget::Encoding, file);
+                    if self.encoding_task.is_none() {
+                        self.start_encoding_check();
+                    } else {
+                        self.encoding_queued = true;
+                        self.set_status(format!("'{}' will be checked once the running encoding check ends", file), false);
+                    }
+                }
+                Some(target) => self.set_dropped(target, file),
+                None => {
+                    let state = &self.tab().state;
+                    let slot = if state.file1.is_empty() || !state.file2.is_empty() { FileSlot::First } else { FileSlot::Second };
+                    self.set_dropped(DropTarget::File(slot), file);
+                }
+            },
+            [first, second, rest @ ..] => {
+                if let Some(file) = [first, second].into_iter().find(|file| !is_csv_file(file)) {
+                    self.set_status(format!("'{}' is not a CSV file", file), true);
+                    return;
+                }
+                self.set_dropped(DropTarget::File(FileSlot::First), first);
+                self.set_dropped(DropTarget::File(FileSlot::Second), second);
+                if !rest.is_empty() {
+                    self.set_status(format!("Only two files can be compared; {} other file(s) ignored", rest.len()), true);
+                }
+            }
+        }
+    }
+
+    /// Puts a dropped file in its slot. Files to compare must be CSV files, while the
+    /// encoding of any file can be checked.
+    fn set_dropped(&mut self, target: DropTarget, file: &str) {
+        match target {
+            DropTarget::File(_) if !is_csv_file(file) => self.set_status(format!("'{}' is not a CSV file", file), true),
+            DropTarget::File(slot) => self.tab().send(Message::FilePicked(slot, file.to_string())),
+            DropTarget::Encoding => self.encoding_file = file.to_string(),
+        }
+    }
+
+    /// Shows a message in the status bar.
+    fn set_status(&mut self, text: String, is_error: bool) {
+        self.status = Some(Status { text, is_error });
+    }
+}
+
+impl ComparisonTab {
+    /// Passes a message to the tab's state and carries out the work it calls for.
+    fn send(&mut self, message: Message) {
+        let Some(effect) = self.state.update(message) else {
+            return;
+        };
+        match effect {
+            Effect::Compare { file1, file2, mut options } => {
+                let progress = Arc::new(Progress::new());
+                options.progress = Some(progress.clone());
+                self.spawn(progress, move || {
+                    let started = Instant::now();
+                    match compare_with_options(&file1, &file2, &options) {
+                        Ok(report) => Message::CompareFinished(Ok(Box::new(CompareResult { elapsed: started.elapsed(), file1, file2, report, options }))),
+                        Err(e) if e.is::<Cancelled>() => Message::Cancelled,
+                        Err(e) => Message::CompareFinished(Err(e.to_string())),
+                    }
+                });
+            }
+            Effect::Diff { file1, file2, options } => {
+                let progress = Arc::new(Progress::new());
+                let options = CompareOptions { progress: Some(progress.clone()), ..options };
+                self.spawn(progress, move || match diff_files(&file1, &file2, &options) {
+                    Err(e) if e.is::<Cancelled>() => Message::Cancelled,
+                    result => Message::DiffFinished(result.map_err(|e| e.to_string())),
+                });
+            }
+            Effect::ShowDiff { model, title } => self.diff_view = Some(DiffView::new(model, title)),
+        }
+    }
+
+    /// Runs `work` on a worker thread, whose outcome `poll_task` passes back to the state.
+    fn spawn(&mut self, progress: Arc<Progress>, work: impl FnOnce() -> Message + Send + 'static) {
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let _ = sender.send(work()); // The window or tab may have been closed
+        });
+        self.task = Some(Task { progress, receiver });
+    }
+
+    /// Collects the previews and headers read in the background, if they are ready.
+    fn poll_file_info(&mut self) {
+        let Some(receiver) = &self.file_info_task else {
+            return;
+        };
+        let info = match receiver.try_recv() {
+            Ok(info) => info,
+            Err(TryRecvError::Empty) => return,
+            Err(TryRecvError::Disconnected) => {
+                self.file_info_task = None;
+                return;
+            }
+        };
+        self.file_info_task = None;
+
+        // Columns of the first file come first, followed by those found only in the second.
+        let [headers1, headers2] = info.headers;
+        self.columns = headers1.iter()
+            .map(|name| ColumnInfo { name: name.clone(), in_file1: true, in_file2: headers2.contains(name) })
+            .collect();
+        for name in headers2.iter().filter(|name| !headers1.contains(name)) {
+            self.columns.push(ColumnInfo { name: name.clone(), in_file1: false, in_file2: true });
+        }
+        self.previews = info.previews;
+    }
+
+    /// Collects the outcome of the tab's background task, if it has finished.
+    fn poll_task(&mut self) {
+        let Some(message) = self.task.as_ref().and_then(Task::poll) else {
+            return;
+        };
+        self.task = None;
+        self.send(message);
+    }
+
+    /// Draws the file selections and comparison options of the tab, recording where
+    /// files can be dropped.
+    fn options_panel(&mut self, ui: &mut egui::Ui, drop_zones: &mut DropZones) {
+        // File 1 and File 2 selections (each also accepts a dropped file)
+        drop_zones.file1 = Some(self.file_picker(ui, FileSlot::First));
+        drop_zones.file2 = Some(self.file_picker(ui, FileSlot::Second));
+
+        // Output encoding selection, from the supported encodings
+        ui.label("Output Encoding:");
+        let mut encoding = self.state.encoding;
+        let mut policy = self.state.unencodable;
+        ui.horizontal(|ui| {
+            egui::ComboBox::from_id_source("output_encoding")
+                .selected_text(encoding.label())
+                .show_ui(ui, |ui| {
+                    for choice in Encoding::ALL {
+                        ui.selectable_value(&mut encoding, choice, choice.label());
+                    }
+                });
+            // Only legacy code pages can meet characters they cannot represent.
+            if !encoding.is_unicode() {
+                ui.label("Unrepresentable characters:");
+                egui::ComboBox::from_id_source("unencodable")
+                    .selected_text(policy.name())
+                    .show_ui(ui, |ui| {
+                        for choice in UnencodablePolicy::ALL {
+                            ui.selectable_value(&mut policy, choice, choice.name());
+                        }
+                    });
+            }
+        });
+        if encoding != self.state.encoding {
+            self.send(Message::EncodingChosen(encoding));
+        }
+        if policy != self.state.unencodable {
+            self.send(Message::UnencodableChosen(policy));
+        }
+
+        // Ignore columns input
+        ui.label("Ignore Columns (comma-separated):");
+        // Text field for specifying columns to ignore during CSV comparison
+        if let Some(columns) = edited(ui, &self.state.ignore_columns.text) {
+            self.send(Message::ColumnsEdited(ColumnList::Ignore, columns));
+        }
+
+        // Key columns input, to tell changed rows from added ones
+        ui.label("Key Columns (comma-separated):");
+        if let Some(columns) = edited(ui, &self.state.key_columns.text) {
+            self.send(Message::ColumnsEdited(ColumnList::Key, columns));
+        }
+
+        // Checklist of the columns read from the selected files
+        self.refresh_file_info();
+        self.preview_panel(ui);
+        self.column_picker(ui);
+    }
+
+    /// Draws the selection of one file: a browse button, the path and the encoding to
+    /// read it with. Returns its area.
+    fn file_picker(&mut self, ui: &mut egui::Ui, slot: FileSlot) -> egui::Rect {
+        let (n, path, input_encoding) = match slot {
+            FileSlot::First => (1, &self.state.file1, self.state.input_encoding1),
+            FileSlot::Second => (2, &self.state.file2, self.state.input_encoding2),
+        };
+        let mut picked = None;
+        let mut encoding = input_encoding;
+        let rect = ui.scope(|ui| {
+            ui.label(format!("File {}:", n));
+
+            // Button to open a file dialog for selecting the CSV file
+            if ui.button(format!("Browse File {}", n)).clicked() {
+                picked = FileDialog::new().pick_file().map(|path| path.display().to_string());
+            }
+            // Text field displaying the file path
+            if let Some(edit) = edited(ui, path) {
+                picked = Some(edit);
+            }
+            // Encoding of the file; auto-detection checks it before comparing
+            ui.horizontal(|ui| {
+                ui.label("Read as:");
+                egui::ComboBox::from_id_source(("input_encoding", n))
+                    .selected_text(encoding.label())
+                    .show_ui(ui, |ui| {
+                        for choice in InputEncoding::all() {
+                            ui.selectable_value(&mut encoding, choice, choice.label());
+                        }
+                    });
+            });
+        }).response.rect;
+        if let Some(path) = picked {
+            self.send(Message::FilePicked(slot, path));
+        }
+        if encoding != input_encoding {
+            self.send(Message::InputEncodingChosen(slot, encoding));
+        }
+        rect
+    }
+
+    /// Draws the progress of the running task and the outcome of the last run.
+    fn run_panel(&mut self, ui: &mut egui::Ui) {
+        if let Some(task) = &self.task {
+            task.progress_bar(ui);
+        }
+        if let Some(status) = &self.state.status {
+            show_status(ui, status);
+        }
+
+        // Counts and output files of the last comparison
+        self.results_panel(ui);
+    }
+
+    /// Opens a file or folder with the default application, reporting failures in the tab.
+    fn open(&mut self, path: &Path) {
+        if let Err(e) = open_path(path) {
+            self.state.set_status(format!("Could not open '{}': {}", path.display(), e), true);
+        }
+    }
+
+    /// Starts re-reading the column list and the previews on a worker thread when the
+    /// selected files, their encodings or the dialect have changed.
+    ///
+    /// The previous columns and previews stay on screen until the new ones arrive; a
+    /// change made meanwhile (e.g. the next keystroke in a path) replaces the pending read.
+    fn refresh_file_info(&mut self) {
+        let source = (
+            [self.state.file1.clone(), self.state.file2.clone()],
+            [self.state.input_encoding1, self.state.input_encoding2],
+            self.state.dialect(),
+        );
+        if self.columns_source.as_ref() == Some(&source) {
+            return;
+        }
+        self.columns_source = Some(source.clone());
+
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let _ = sender.send(read_file_info(&source));
+        });
+        self.file_info_task = Some(receiver);
+    }
+
+    /// Draws the preview of each selected file: format, headers and first rows.
+    fn preview_panel(&mut self, ui: &mut egui::Ui) {
+        let current = self.state.dialect().delimiter;
+        let mut use_delimiter = None;
+
+        for (n, preview) in self.previews.iter().enumerate() {
+            let Some(preview) = preview else {
+                continue;
+            };
+            egui::CollapsingHeader::new(format!("Preview of File {}", n + 1))
+                .default_open(true)
+                .show(ui, |ui| {
+                    let preview = match preview {
+                        Ok(preview) => preview,
+                        Err(e) => {
+                            ui.colored_label(ui.visuals().error_fg_color, format!("Cannot read the file: {}", e));
+                            return;
+                        }
+                    };
+                    let about = if preview.exact_count { "" } else { "about " };
+                    let encoding = if preview.detected {
+                        format!("{} ({:.0}% confidence)", preview.encoding, preview.confidence * 100.0)
+                    } else {
+                        format!("{} (chosen)", preview.encoding)
+                    };
+                    ui.label(format!("Encoding: {}, delimiter: {}, {}{} row(s), {} column(s)",
+                        encoding, delimiter_name(preview.dialect.delimiter),
+                        about, preview.estimated_rows, preview.headers.len()));
+
+                    // Warn when the file does not look like it uses the comparison's delimiter.
+                    if preview.dialect.delimiter != current {
+                        ui.horizontal(|ui| {
+                            ui.colored_label(ui.visuals().warn_fg_color, format!("The comparison uses {} as delimiter", delimiter_name(current)));
+                            if ui.button("Use Detected Delimiter").clicked() {
+                                use_delimiter = Some(preview.dialect.delimiter);
+                            }
+                        });
+                    }
+                    ui.label(format!("Headers: {}", preview.headers.join(", ")));
+
+                    egui::ScrollArea::horizontal().id_source(("preview", n)).show(ui, |ui| {
+                        egui::Grid::new(("preview_rows", n)).striped(true).show(ui, |ui| {
+                            for header in &preview.headers {
+                                ui.strong(header);
+                            }
+                            ui.end_row();
+                            for row in &preview.rows {
+                                for cell in row {
+                                    ui.label(cell);
+                                }
+                                ui.end_row();
+                            }
+                        });
+                    });
+                });
+        }
+
+        if let Some(delimiter) = use_delimiter {
+            self.send(Message::DelimiterChosen(delimiter));
+        }
+    }
+
+    /// Draws the checklist of columns to ignore or use as keys, with search and select-all.
+    fn column_picker(&mut self, ui: &mut egui::Ui) {
+        if self.columns.is_empty() {
+            return;
+        }
+        let mut toggled = Vec::new();
+        egui::CollapsingHeader::new(format!("Columns ({})", self.columns.len()))
+            .default_open(true)
+            .show(ui, |ui| {
+                let filter = self.column_filter.to_lowercase();
+                let visible: Vec<&ColumnInfo> = self.columns.iter()
+                    .filter(|c| c.name.to_lowercase().contains(&filter))
+                    .collect();
+
+                ui.horizontal(|ui| {
+                    ui.label("Search:");
+                    ui.text_edit_singleline(&mut self.column_filter);
+                    // Select-all and clear apply to the columns matching the search.
+                    for (label, listed) in [("Ignore All", true), ("Ignore None", false)] {
+                        if ui.button(label).clicked() {
+                            toggled.extend(visible.iter().map(|c| (ColumnList::Ignore, c.name.clone(), listed)));
+                        }
+                    }
+                });
+
+                let ignored = self.state.ignore_columns.columns.clone();
+                let keys = self.state.key_columns.columns.clone();
+                egui::ScrollArea::vertical().max_height(200.0).show(ui, |ui| {
+                    egui::Grid::new("columns").num_columns(4).striped(true).show(ui, |ui| {
+                        ui.strong("Column");
+                        ui.strong("Ignore");
+                        ui.strong("Key");
+                        ui.strong("");
+                        ui.end_row();
+                        for column in &visible {
+                            ui.label(&column.name);
+                            for (list, listed) in [(ColumnList::Ignore, &ignored), (ColumnList::Key, &keys)] {
+                                let mut checked = listed.contains(&column.name);
+                                if ui.checkbox(&mut checked, "").changed() {
+                                    toggled.push((list, column.name.clone(), checked));
+                                }
+                            }
+                            // Flag columns that only one file has.
+                            match (column.in_file1, column.in_file2) {
+                                (true, false) => ui.colored_label(ui.visuals().warn_fg_color, "only in file 1"),
+                                (false, true) => ui.colored_label(ui.visuals().warn_fg_color, "only in file 2"),
+                                _ => ui.label(""),
+                            };
+                            ui.end_row();
+                        }
+                    });
+                });
+            });
+
+        for (list, column, listed) in toggled {
+            self.send(Message::ColumnToggled { list, column, listed });
+        }
+    }
+
+    /// Draws the counts and output files of the last comparison.
+    fn results_panel(&mut self, ui: &mut egui::Ui) {
+        let Some(result) = &self.state.last_result else {
+            return;
+        };
+        let report = &result.report;
+        let busy = self.state.is_busy();
+        let mut to_open = None;
+        let mut show_diff = false;
+
+        ui.group(|ui| {
+            ui.heading("Results");
+            egui::Grid::new("results").num_columns(2).show(ui, |ui| {
+                ui.label("Rows in file 1:");
+                ui.label(format!("{} ({})", report.rows_file1, result.file1));
+                ui.end_row();
+                ui.label("Rows in file 2:");
+                ui.label(format!("{} ({})", report.rows_file2, result.file2));
+                ui.end_row();
+                ui.label("Read as:");
+                ui.label(format!("{} / {}", report.input_encoding1, report.input_encoding2));
+                ui.end_row();
+                ui.label("Unique rows:");
+                ui.label(report.unique_rows.to_string());
+                ui.end_row();
+                if report.changed_rows + report.added_rows > 0 {
+                    ui.label("Changed / added:");
+                    ui.label(format!("{} / {}", report.changed_rows, report.added_rows));
+                    ui.end_row();
+                }
+                ui.label("Elapsed time:");
+                ui.label(format!("{:.2} s", result.elapsed.as_secs_f64()));
+                ui.end_row();
+                ui.label("Output file:");
+                ui.label(&report.output_file);
+                ui.end_row();
+                if let Some(path) = &report.number_failures_file {
+                    ui.label("Number errors:");
+                    ui.label(format!("{} in {}", report.number_failures.len(), path));
+                    ui.end_row();
+                }
+                if let Some(path) = &report.rejects_file {
+                    ui.label("Rejected / repaired:");
+                    ui.label(format!("{} / {} in {}", report.rejected_rows, report.repaired_rows, path));
+                    ui.end_row();
+                }
+                if let Some(path) = &report.unencodable_file {
+                    ui.label("Unrepresentable cells:");
+                    ui.label(format!("{} in {}", report.unencodable_cells.len(), path));
+                    ui.end_row();
+                }
+            });
+            ui.horizontal(|ui| {
+                let output = Path::new(&report.output_file);
+                if ui.button("Open Output File").clicked() {
+                    to_open = Some(output.to_path_buf());
+                }
+                if ui.button("Open Folder").clicked() {
+                    // A bare file name lives in the working directory.
+                    let folder = output.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new("."));
+                    to_open = Some(folder.to_path_buf());
+                }
+                if ui.add_enabled(!busy, egui::Button::new("Show Differences")).clicked() {
+                    show_diff = true;
+                }
+            });
+        });
+
+        if let Some(path) = to_open {
+            self.open(&path);
+        }
+        if show_diff {
+            self.send(Message::DiffClicked);
         }
     }
 }
 
 /// Implementation of the `eframe::App` trait to define how the GUI is rendered and updated.
 impl eframe::App for MyApp {
+    /// Saves the tabs, settings, recent comparisons and presets for the next session.
+    fn save(&mut self, storage: &mut dyn eframe::Storage) {
+        eframe::set_value(storage, eframe::APP_KEY, self);
+    }
+
     /// The main update loop for the GUI. It defines the layout and behavior of the interface.
     ///
     /// # Parameters
     /// * `ctx` - The UI context used to draw and update elements.
     /// * `_frame` - The frame passed by `eframe` (unused here).
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        egui::CentralPanel::default().show(ctx, |ui| {
-            
-            // File 1 selection
-            ui.label("File 1:");
-
-            // Button to open a file dialog for selecting the first CSV file
-            if ui.button("Browse File 1").clicked() {
-                if let Some(path) = FileDialog::new().pick_file() {
-                    self.file1 = path.display().to_string(); // Store selected path
-                }
-            }
-            // Text field displaying the file path for the first file
-            ui.text_edit_singleline(&mut self.file1);
+        // Tabs in the background keep running, so collect the results of all of them.
+        for tab in &mut self.tabs {
+            tab.poll_task();
+            tab.poll_file_info();
+        }
+        self.poll_encoding_task();
 
-            // File 2 selection
-            ui.label("File 2:");
+        // Tab bar to switch between, add, duplicate and close comparisons
+        egui::TopBottomPanel::top("tabs").show(ctx, |ui| self.tab_bar(ui));
 
-            // Button to open a file dialog for selecting the second CSV file
-            if ui.button("Browse File 2").clicked() {
-                if let Some(path) = FileDialog::new().pick_file() {
-                    self.file2 = path.display().to_string(); // Store selected path
+        // Status bar showing application messages and errors
+        egui::TopBottomPanel::bottom("status").show(ctx, |ui| {
+            match &self.status {
+                Some(status) => show_status(ui, status),
+                None => {
+                    ui.label("Ready");
                 }
             }
-            // Text field displaying the file path for the second file
-            ui.text_edit_singleline(&mut self.file2);
-
-            // Encoding selection
-            ui.label("Encoding:");
-            // Text field for specifying the encoding type (e.g., UTF-8, UTF-8-BOM)
-            ui.text_edit_singleline(&mut self.encoding);
+        });
 
-            // Ignore columns input
-            ui.label("Ignore Columns (comma-separated):");
-            // Text field for specifying columns to ignore during CSV comparison
-            ui.text_edit_singleline(&mut self.ignore_columns);
+        egui::CentralPanel::default().show(ctx, |ui| egui::ScrollArea::vertical().show(ui, |ui| {
+            // Files and options of the shown comparison
+            let tab = &mut self.tabs[self.selected_tab];
+            tab.options_panel(ui, &mut self.drop_zones);
 
-            // CSV comparison button
-            if ui.button("Compare CSVs").clicked() {
-                // Parse the ignore columns string into a vector of strings
-                let ignore_columns: Vec<&str> = self.ignore_columns.split(',')
-                    .map(|s| s.trim())
-                    .collect();
+            // Profile selection: load options from, or save them to, csvscripts.toml
+            self.profile_panel(ui);
 
-                // Match the encoding string to the appropriate enum variant
-                let encoding_enum = match self.encoding.as_str() {
-                    "UTF-8" => Encoding::Utf8,
-                    "UTF-8 with BOM" => Encoding::Utf8Bom,
-                    _ => {
-                        eprintln!("Unsupported encoding: {}", self.encoding);
-                        return;
-                    }
-                };
+            // Recent comparisons and presets
+            self.history_panel(ui);
 
-                // Call the CSV comparison function with the selected files and parameters
-                if let Err(e) = compare_csv(&self.file1, &self.file2, encoding_enum, &ignore_columns) {
-                    eprintln!("Error comparing files: {}", e); // Log error if comparison fails
-                }
+            // CSV comparison button, disabled while the tab is running a task
+            let busy = self.tab().state.is_busy();
+            if ui.add_enabled(!busy, egui::Button::new("Compare CSVs")).clicked() {
+                self.start_compare();
             }
 
+            // Progress, outcome and results of the shown comparison
+            self.tab().run_panel(ui);
+
             // Separator for a new section to check file encoding
             ui.separator();
-            ui.label("Check File Encoding:");
+            // Encoding check section (a file dropped here is checked right away)
+            let checking = self.encoding_task.is_some();
+            self.drop_zones.encoding = Some(ui.scope(|ui| {
+                ui.label("Check File Encoding:");
 
-            // Button to open a file dialog for selecting a file to check its encoding
-            if ui.button("Browse File to Check Encoding").clicked() {
-                if let Some(path) = FileDialog::new().pick_file() {
-                    self.encoding_file = path.display().to_string(); // Store selected file path
+                // Button to open a file dialog for selecting a file to check its encoding
+                if ui.button("Browse File to Check Encoding").clicked() {
+                    if let Some(path) = FileDialog::new().pick_file() {
+                        self.encoding_file = path.display().to_string(); // Store selected file path
+                    }
                 }
-            }
-            // Text field displaying the file path for encoding check
-            ui.text_edit_singleline(&mut self.encoding_file);
+                // Text field displaying the file path for encoding check
+                ui.text_edit_singleline(&mut self.encoding_file);
 
-            // Button to detect the encoding of the selected file
-            if ui.button("Check Encoding").clicked() {
-                let path = Path::new(&self.encoding_file);
-                match detect_encoding(path) {
-                    // If encoding detection succeeds, store the result in `encoding_result`
-                    Ok((encoding, confidence)) => {
-                        self.encoding_result = format!("Encoding: {}, Confidence: {:.2}%", encoding, confidence * 100.0);
-                    }
-                    // If encoding detection fails, store the error message
-                    Err(e) => {
-                        self.encoding_result = format!("Error detecting encoding: {}", e);
-                    }
+                // Sampling keeps the check fast on large files; a full scan validates all of it
+                ui.checkbox(&mut self.full_scan, "Scan whole file");
+
+                // Button to detect the encoding of the selected file on a worker thread
+                if ui.add_enabled(!checking, egui::Button::new("Check Encoding")).clicked() {
+                    self.start_encoding_check();
                 }
-            }
+            }).response.rect);
 
             // Display the encoding detection result if available
             if !self.encoding_result.is_empty() {
                 ui.label(&self.encoding_result); // Show the result in the UI
             }
-        });
+        }));
+
+        // Keep redrawing while any worker runs, so progress and results show up.
+        if self.encoding_task.is_some() || self.tabs.iter().any(|tab| tab.task.is_some() || tab.file_info_task.is_some()) {
+            ctx.request_repaint_after(Duration::from_millis(100));
+        }
+
+        // Files dragged over or dropped onto the window
+        self.handle_dropped_files(ctx);
+
+        // Side-by-side diff window of the shown tab, until the user closes it
+        let tab = self.tab();
+        if let Some(view) = &mut tab.diff_view {
+            if !view.show(ctx) {
+                tab.diff_view = None;
+            }
+        }
+    }
+}
+
+/// Draws a status message, highlighting errors.
+fn show_status(ui: &mut egui::Ui, status: &Status) {
+    if status.is_error {
+        ui.colored_label(ui.visuals().error_fg_color, &status.text);
+    } else {
+        ui.label(&status.text);
     }
-}
\ No newline at end of file
+}
+
+/// Draws a text field showing `value`. Returns the new text if the user edited it.
+fn edited(ui: &mut egui::Ui, value: &str) -> Option<String> {
+    let mut text = value.to_string();
+    ui.text_edit_singleline(&mut text).changed().then_some(text)
+}
+
+/// Reads the previews and headers of the files in `source`.
+///
+/// Files that cannot be read (e.g. while a path is being typed) contribute no columns.
+/// Auto-detected files are read in the encoding their preview found.
+fn read_file_info((files, encodings, dialect): &ColumnsSource) -> FileInfo {
+    let previews = [0, 1].map(|n| {
+        if files[n].trim().is_empty() {
+            None
+        } else {
+            Some(preview_file(&files[n], PREVIEW_ROWS, encodings[n]).map_err(|e| e.to_string()))
+        }
+    });
+    let headers = [0, 1].map(|n| {
+        let encoding = match encodings[n] {
+            InputEncoding::Fixed(encoding) => encoding,
+            InputEncoding::Auto => match &previews[n] {
+                Some(Ok(preview)) => Encoding::from_detected(&preview.encoding).unwrap_or_default(),
+                _ => Encoding::Utf8,
+            },
+        };
+        read_headers(&files[n], dialect, encoding).unwrap_or_default()
+    });
+    FileInfo { previews, headers }
+}
+
+/// Returns `true` if a file looks like CSV from its extension (`.csv`, `.tsv` or `.txt`).
+fn is_csv_file(file: &str) -> bool {
+    let extension = Path::new(file).extension().map(|e| e.to_string_lossy().to_lowercase());
+    matches!(extension.as_deref(), Some("csv" | "tsv" | "txt"))
+}
+
+/// Opens a file or folder with the default application of the platform.
+fn open_path(path: &Path) -> std::io::Result<()> {
+    let program = if cfg!(target_os = "windows") {
+        "explorer"
+    } else if cfg!(target_os = "macos") {
+        "open"
+    } else {
+        "xdg-open"
+    };
+    Command::new(program).arg(path).spawn().map(|_| ())
+}
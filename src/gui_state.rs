@@ -0,0 +1,651 @@
+// src/gui_state.rs
+
+//! State of the GUI, kept apart from the egui rendering in `gui.rs`.
+//!
+//! A comparison tab is a `TabState` changed only through `TabState::update`: the
+//! rendering layer turns clicks, edits and worker results into `Message`s, and runs the
+//! `Effect`s that `update` returns (starting a worker thread, opening the diff window).
+//! Nothing here touches egui, threads or files, so the behavior can be tested headlessly.
+
+use rust_csv_scripts::csv_compare::{output_path, CompareOptions, CompareReport};
+use rust_csv_scripts::dialect::Dialect;
+use rust_csv_scripts::diff::DiffModel;
+use rust_csv_scripts::encoding::{Encoding, InputEncoding};
+use rust_csv_scripts::transcode::UnencodablePolicy;
+use rust_csv_scripts::spec::{join_list, split_list, CompareSpec};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Duration;
+
+pub const MAX_RECENTS: usize = 10; // Number of recent comparisons kept
+
+/// One of the two files of a comparison.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileSlot {
+    First,
+    Second,
+}
+
+/// One of the comma-separated column lists of a tab.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnList {
+    Ignore,
+    Key,
+}
+
+/// A column list of a tab, with the text shown for it in its text field.
+///
+/// The names are kept as they appear in the headers, so a column such as `Amount, USD`
+/// survives being checked in the column checklist; only the text field splits on commas
+/// (a name can be quoted there, as in `"Amount, USD", id`).
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ColumnListField {
+    pub columns: Vec<String>, // The column names
+    pub text: String,         // The list as shown and edited in the text field
+}
+
+impl ColumnListField {
+    /// Sets the list from the text typed in the text field.
+    fn edit(&mut self, text: String) {
+        self.columns = split_list(&text);
+        self.text = text;
+    }
+
+    /// Replaces the list, rewriting the text field to match.
+    fn set(&mut self, columns: Vec<String>) {
+        self.text = join_list(&columns);
+        self.columns = columns;
+    }
+}
+
+/// Something the user did in a tab, or the outcome of its background work.
+#[derive(Debug)]
+pub enum Message {
+    /// A file was browsed to, typed or dropped.
+    FilePicked(FileSlot, String),
+    /// An output encoding was chosen.
+    EncodingChosen(Encoding),
+    /// A policy for characters the output encoding cannot represent was chosen.
+    UnencodableChosen(UnencodablePolicy),
+    /// An encoding, or auto-detection, was chosen for one of the files.
+    InputEncodingChosen(FileSlot, InputEncoding),
+    /// A column list field was edited.
+    ColumnsEdited(ColumnList, String),
+    /// A column was checked or unchecked in the column checklist.
+    ColumnToggled { list: ColumnList, column: String, listed: bool },
+    /// The delimiter detected in a preview was chosen for the comparison.
+    DelimiterChosen(char),
+    /// A profile was loaded into the tab.
+    ProfileLoaded(CompareSpec),
+    /// A recent comparison or preset was loaded into the tab.
+    SavedLoaded(SavedComparison),
+    /// The Compare button was pressed. `busy_outputs` are the files other tabs are writing.
+    CompareClicked { busy_outputs: Vec<String> },
+    /// The comparison finished.
+    CompareFinished(Result<Box<CompareResult>, String>),
+    /// The Show Differences button was pressed.
+    DiffClicked,
+    /// The diff of the last comparison was built.
+    DiffFinished(Result<DiffModel, String>),
+    /// The background work stopped after the user cancelled it.
+    Cancelled,
+    /// The background work stopped without a result.
+    Failed(String),
+}
+
+/// Work for the rendering layer to carry out after an update.
+#[derive(Debug)]
+pub enum Effect {
+    /// Compare two files on a worker thread and send back `CompareFinished`.
+    Compare { file1: String, file2: String, options: CompareOptions },
+    /// Build the diff of two files on a worker thread and send back `DiffFinished`.
+    Diff { file1: String, file2: String, options: CompareOptions },
+    /// Open the side-by-side diff window.
+    ShowDiff { model: DiffModel, title: String },
+}
+
+/// The background work a tab is waiting for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Running {
+    Compare { output_file: String }, // A comparison, writing this file
+    Diff,                            // The diff of the last comparison
+}
+
+/// A message shown in the status bar or under a tab's Compare button.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Status {
+    pub text: String,    // The message
+    pub is_error: bool,  // Errors are highlighted
+}
+
+/// A finished comparison, shown in the results panel.
+#[derive(Debug)]
+pub struct CompareResult {
+    pub file1: String,          // The first file of the comparison
+    pub file2: String,          // The second file of the comparison
+    pub report: CompareReport,  // Counts and output files of the comparison
+    pub elapsed: Duration,      // Time the comparison took
+    pub options: CompareOptions, // Options the comparison ran with, reused by the diff view
+}
+
+/// A pair of files with the options to compare them, kept as a recent comparison or preset.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SavedComparison {
+    pub file1: String,      // The first file
+    pub file2: String,      // The second file
+    pub spec: CompareSpec,  // All options, including those edited in the window
+}
+
+/// The files and options of one comparison tab, and the outcome of its runs.
+///
+/// Fields marked `skip` only live for the current session.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TabState {
+    pub file1: String,              // File path for the first CSV file
+    pub file2: String,              // File path for the second CSV file
+    pub encoding: Encoding,         // Encoding of the output file
+    pub unencodable: UnencodablePolicy, // What happens to characters the output encoding cannot represent
+    pub input_encoding1: InputEncoding, // Encoding of the first file, or auto-detection
+    pub input_encoding2: InputEncoding, // Encoding of the second file, or auto-detection
+    pub ignore_columns: ColumnListField, // Columns to ignore during comparison
+    pub key_columns: ColumnListField,    // Columns identifying a row
+    pub active_spec: CompareSpec,   // Options of the loaded profile, including those not shown in the window
+    #[serde(skip)]
+    pub status: Option<Status>,     // Outcome of the last run of this tab
+    #[serde(skip)]
+    pub last_result: Option<CompareResult>, // Outcome of the last successful comparison
+    #[serde(skip)]
+    pub running: Option<Running>,   // Background work in progress
+}
+
+/// Default implementation for `TabState`, an empty comparison writing UTF-8 and detecting
+/// the encoding of its inputs.
+impl Default for TabState {
+    fn default() -> Self {
+        Self {
+            file1: String::new(),
+            file2: String::new(),
+            encoding: Encoding::Utf8,
+            unencodable: UnencodablePolicy::Error,
+            input_encoding1: InputEncoding::Auto,
+            input_encoding2: InputEncoding::Auto,
+            ignore_columns: ColumnListField::default(),
+            key_columns: ColumnListField::default(),
+            active_spec: CompareSpec::default(),
+            status: None,
+            last_result: None,
+            running: None,
+        }
+    }
+}
+
+impl TabState {
+    /// Creates a tab from a recent comparison or preset.
+    pub fn from_saved(saved: &SavedComparison) -> Self {
+        let mut state = Self::default();
+        state.load_saved(saved);
+        state
+    }
+
+    /// Applies a message and returns the work it calls for, if any.
+    pub fn update(&mut self, message: Message) -> Option<Effect> {
+        match message {
+            Message::FilePicked(FileSlot::First, path) => self.file1 = path,
+            Message::FilePicked(FileSlot::Second, path) => self.file2 = path,
+            Message::EncodingChosen(encoding) => self.encoding = encoding,
+            Message::UnencodableChosen(policy) => self.unencodable = policy,
+            Message::InputEncodingChosen(FileSlot::First, encoding) => self.input_encoding1 = encoding,
+            Message::InputEncodingChosen(FileSlot::Second, encoding) => self.input_encoding2 = encoding,
+            Message::ColumnsEdited(list, value) => self.list_mut(list).edit(value),
+            Message::ColumnToggled { list, column, listed } => set_listed(self.list_mut(list), &column, listed),
+            Message::DelimiterChosen(delimiter) => self.active_spec.delimiter = Some(delimiter_name(delimiter)),
+            Message::ProfileLoaded(spec) => {
+                self.apply_spec(&spec);
+                self.active_spec = spec;
+            }
+            Message::SavedLoaded(saved) => self.load_saved(&saved),
+            Message::CompareClicked { busy_outputs } => return self.compare(&busy_outputs),
+            Message::CompareFinished(Ok(result)) => {
+                self.running = None;
+                self.set_status(format!("Comparison finished in {:.1} s", result.elapsed.as_secs_f64()), false);
+                self.last_result = Some(*result);
+            }
+            Message::CompareFinished(Err(e)) => {
+                self.running = None;
+                self.set_status(format!("Error comparing files: {}", e), true);
+            }
+            Message::DiffClicked => return self.diff(),
+            Message::DiffFinished(Ok(model)) => {
+                self.running = None;
+                self.set_status(format!("{} differing row(s)", model.rows.len()), false);
+                let result = self.last_result.as_ref()?;
+                let title = format!("Differences: {} vs {}", result.file1, result.file2);
+                return Some(Effect::ShowDiff { model, title });
+            }
+            Message::DiffFinished(Err(e)) => {
+                self.running = None;
+                self.set_status(format!("Error building the diff: {}", e), true);
+            }
+            Message::Cancelled => {
+                self.running = None;
+                self.set_status("Cancelled".to_string(), false);
+            }
+            Message::Failed(e) => {
+                self.running = None;
+                self.set_status(e, true);
+            }
+        }
+        None
+    }
+
+    /// Returns `true` while the tab waits for background work.
+    pub fn is_busy(&self) -> bool {
+        self.running.is_some()
+    }
+
+    /// Returns the file the running comparison writes, if any.
+    pub fn busy_output(&self) -> Option<&str> {
+        match &self.running {
+            Some(Running::Compare { output_file }) => Some(output_file),
+            _ => None,
+        }
+    }
+
+    /// Names the tab after the file names of its comparison.
+    pub fn title(&self) -> String {
+        let name = |path: &str| Path::new(path).file_name().map(|n| n.to_string_lossy().into_owned());
+        match (name(&self.file1), name(&self.file2)) {
+            (Some(file1), Some(file2)) => format!("{} vs {}", file1, file2),
+            (Some(file), None) | (None, Some(file)) => file,
+            (None, None) => "New Comparison".to_string(),
+        }
+    }
+
+    /// Returns the dialect the files are read with.
+    pub fn dialect(&self) -> Dialect {
+        self.active_spec.to_options().map(|o| o.dialect).unwrap_or_default()
+    }
+
+    /// Describes the selected files and all current options, for recents and presets.
+    pub fn saved_comparison(&self) -> SavedComparison {
+        SavedComparison {
+            file1: self.file1.clone(),
+            file2: self.file2.clone(),
+            spec: self.active_spec.merged_with(&self.current_spec()),
+        }
+    }
+
+    /// Describes the current ignore columns, key columns and encodings as a profile.
+    pub fn current_spec(&self) -> CompareSpec {
+        let ignore_columns = self.ignore_columns.columns.clone();
+        let key_columns = self.key_columns.columns.clone();
+        CompareSpec {
+            ignore_columns: Some(ignore_columns),
+            key_columns: if key_columns.is_empty() { None } else { Some(key_columns) },
+            encoding: Some(self.encoding.name().to_string()),
+            unencodable: Some(self.unencodable.name().to_string()),
+            input_encoding1: Some(self.input_encoding1.name().to_string()),
+            input_encoding2: Some(self.input_encoding2.name().to_string()),
+            ..CompareSpec::default()
+        }
+    }
+
+    /// Returns the column list field for `list`.
+    fn list_mut(&mut self, list: ColumnList) -> &mut ColumnListField {
+        match list {
+            ColumnList::Ignore => &mut self.ignore_columns,
+            ColumnList::Key => &mut self.key_columns,
+        }
+    }
+
+    /// Fills the tab from a recent comparison or preset.
+    fn load_saved(&mut self, saved: &SavedComparison) {
+        self.file1 = saved.file1.clone();
+        self.file2 = saved.file2.clone();
+        self.ignore_columns = ColumnListField::default();
+        self.key_columns = ColumnListField::default();
+        self.apply_spec(&saved.spec);
+        self.active_spec = saved.spec.clone();
+    }
+
+    /// Fills the ignore columns, key columns and encoding fields from a profile.
+    ///
+    /// Encodings that do not parse are reported and left as they are.
+    fn apply_spec(&mut self, spec: &CompareSpec) {
+        if let Some(columns) = &spec.ignore_columns {
+            self.ignore_columns.set(columns.clone());
+        }
+        if let Some(columns) = &spec.key_columns {
+            self.key_columns.set(columns.clone());
+        }
+        let mut errors = Vec::new();
+        parse_into(&spec.encoding, &mut self.encoding, &mut errors);
+        parse_into(&spec.unencodable, &mut self.unencodable, &mut errors);
+        parse_into(&spec.input_encoding1, &mut self.input_encoding1, &mut errors);
+        parse_into(&spec.input_encoding2, &mut self.input_encoding2, &mut errors);
+        if !errors.is_empty() {
+            self.set_status(errors.join(" "), true);
+        }
+    }
+
+    /// Builds the options of a comparison, unless one is already running, the options
+    /// are invalid or another tab is writing the same output file.
+    fn compare(&mut self, busy_outputs: &[String]) -> Option<Effect> {
+        if self.is_busy() {
+            return None;
+        }
+        // Combine the loaded profile with the options edited in the window
+        let spec = self.active_spec.merged_with(&self.current_spec());
+        let options = match spec.to_options() {
+            Ok(options) => options,
+            Err(e) => {
+                self.set_status(format!("Invalid profile options: {}", e), true);
+                return None;
+            }
+        };
+
+        // Two tabs writing the same output file at once would corrupt it.
+        let output_file = options.output_file.clone().unwrap_or_else(|| output_path(&self.file1, "modified"));
+        if busy_outputs.contains(&output_file) {
+            self.set_status(format!("Another tab is writing '{}'; wait for it to finish", output_file), true);
+            return None;
+        }
+
+        self.set_status(format!("Comparing '{}' with '{}'...", self.file1, self.file2), false);
+        self.running = Some(Running::Compare { output_file });
+        Some(Effect::Compare { file1: self.file1.clone(), file2: self.file2.clone(), options })
+    }
+
+    /// Asks for the diff of the last comparison, unless the tab is busy.
+    fn diff(&mut self) -> Option<Effect> {
+        if self.is_busy() {
+            return None;
+        }
+        let result = self.last_result.as_ref()?;
+        // Read the files as the comparison did, rather than detecting their encodings again.
+        let mut options = result.options.clone();
+        options.input_encoding1 = InputEncoding::Fixed(result.report.input_encoding1);
+        options.input_encoding2 = InputEncoding::Fixed(result.report.input_encoding2);
+        let effect = Effect::Diff { file1: result.file1.clone(), file2: result.file2.clone(), options };
+        self.set_status("Building the diff...".to_string(), false);
+        self.running = Some(Running::Diff);
+        Some(effect)
+    }
+
+    /// Shows a message under the tab's Compare button.
+    pub fn set_status(&mut self, text: String, is_error: bool) {
+        self.status = Some(Status { text, is_error });
+    }
+}
+
+/// The recent comparisons and the named presets, kept between sessions.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct History {
+    pub recents: Vec<SavedComparison>,                // The last comparisons run, most recent first
+    pub presets: BTreeMap<String, SavedComparison>,   // Comparisons saved under a name
+}
+
+impl History {
+    /// Moves a comparison to the top of the recent list, keeping at most `MAX_RECENTS`.
+    pub fn remember(&mut self, saved: SavedComparison) {
+        self.recents.retain(|recent| *recent != saved);
+        self.recents.insert(0, saved);
+        self.recents.truncate(MAX_RECENTS);
+    }
+
+    /// Saves a comparison as a preset. Returns the trimmed name, or `None` if it is blank.
+    pub fn save_preset(&mut self, name: &str, saved: SavedComparison) -> Option<String> {
+        let name = name.trim();
+        if name.is_empty() {
+            return None;
+        }
+        self.presets.insert(name.to_string(), saved);
+        Some(name.to_string())
+    }
+}
+
+/// Names a delimiter the way profiles and the command line spell it.
+pub fn delimiter_name(delimiter: char) -> String {
+    match delimiter {
+        ',' => "comma".to_string(),
+        ';' => "semicolon".to_string(),
+        '\t' => "tab".to_string(),
+        '|' => "pipe".to_string(),
+        other => other.to_string(),
+    }
+}
+
+/// Parses an optional profile value into `target`, collecting the error if it is invalid.
+fn parse_into<T: FromStr<Err = String>>(value: &Option<String>, target: &mut T, errors: &mut Vec<String>) {
+    match value.as_deref().map(str::parse) {
+        Some(Ok(parsed)) => *target = parsed,
+        Some(Err(e)) => errors.push(e),
+        None => {}
+    }
+}
+
+/// Adds `name` to, or removes it from, a column list.
+fn set_listed(list: &mut ColumnListField, name: &str, listed: bool) {
+    let mut items = list.columns.clone();
+    let present = items.iter().any(|item| item == name);
+    if listed && !present {
+        items.push(name.to_string());
+    } else if !listed && present {
+        items.retain(|item| item != name);
+    } else {
+        return;
+    }
+    list.set(items);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A tab comparing `a.csv` with `b.csv`.
+    fn tab() -> TabState {
+        let mut state = TabState::default();
+        state.update(Message::FilePicked(FileSlot::First, "a.csv".to_string()));
+        state.update(Message::FilePicked(FileSlot::Second, "b.csv".to_string()));
+        state
+    }
+
+    /// Clicks Compare with no other tab running and returns the options to compare with.
+    fn compare(state: &mut TabState) -> CompareOptions {
+        match state.update(Message::CompareClicked { busy_outputs: Vec::new() }) {
+            Some(Effect::Compare { file1, file2, options }) => {
+                assert_eq!((file1.as_str(), file2.as_str()), ("a.csv", "b.csv"));
+                options
+            }
+            other => panic!("expected a comparison, got {:?}", other),
+        }
+    }
+
+    /// A finished comparison of `a.csv` with `b.csv`.
+    fn finished() -> Message {
+        Message::CompareFinished(Ok(Box::new(CompareResult {
+            file1: "a.csv".to_string(),
+            file2: "b.csv".to_string(),
+            report: CompareReport::default(),
+            elapsed: Duration::from_millis(1500),
+            options: CompareOptions::default(),
+        })))
+    }
+
+    #[test]
+    fn compare_clicked_parses_the_fields() {
+        let mut state = tab();
+        state.update(Message::ColumnsEdited(ColumnList::Ignore, " id , ,updated_at,".to_string()));
+        state.update(Message::ColumnsEdited(ColumnList::Key, "id".to_string()));
+        state.update(Message::EncodingChosen(Encoding::Utf8Bom));
+        state.update(Message::UnencodableChosen(UnencodablePolicy::Transliterate));
+        state.update(Message::InputEncodingChosen(FileSlot::Second, InputEncoding::Fixed(Encoding::Utf8)));
+
+        let options = compare(&mut state);
+        let mut ignored: Vec<&String> = options.ignore_columns.iter().collect();
+        ignored.sort();
+        assert_eq!(ignored, ["id", "updated_at"]);
+        assert_eq!(options.key_columns, ["id"]);
+        assert_eq!(options.encoding, Encoding::Utf8Bom);
+        assert_eq!(options.unencodable, UnencodablePolicy::Transliterate);
+        assert_eq!(options.input_encoding1, InputEncoding::Auto);
+        assert_eq!(options.input_encoding2, InputEncoding::Fixed(Encoding::Utf8));
+        assert_eq!(state.busy_output(), Some(output_path("a.csv", "modified").as_str()));
+        assert!(!state.status.as_ref().unwrap().is_error);
+    }
+
+    #[test]
+    fn profile_encodings_are_parsed() {
+        let mut state = tab();
+        let spec = CompareSpec { encoding: Some("UTF-8 with BOM".to_string()), input_encoding2: Some("utf8".to_string()), ..CompareSpec::default() };
+        state.update(Message::ProfileLoaded(spec));
+        assert_eq!(state.encoding, Encoding::Utf8Bom);
+        assert_eq!(state.input_encoding1, InputEncoding::Auto);
+        assert_eq!(state.input_encoding2, InputEncoding::Fixed(Encoding::Utf8));
+    }
+
+    #[test]
+    fn unsupported_profile_encoding_is_reported() {
+        let mut state = tab();
+        state.update(Message::ProfileLoaded(CompareSpec { encoding: Some("latin9".to_string()), ..CompareSpec::default() }));
+        assert_eq!(state.encoding, Encoding::Utf8);
+        let status = state.status.unwrap();
+        assert!(status.is_error);
+        assert!(status.text.starts_with("Invalid encoding 'latin9'. Use one of utf8, utf8bom,"));
+    }
+
+    #[test]
+    fn invalid_profile_options_are_reported() {
+        let mut state = tab();
+        state.update(Message::ProfileLoaded(CompareSpec { ragged: Some("sideways".to_string()), ..CompareSpec::default() }));
+        assert!(state.update(Message::CompareClicked { busy_outputs: Vec::new() }).is_none());
+        let status = state.status.unwrap();
+        assert!(status.is_error);
+        assert!(status.text.starts_with("Invalid profile options"), "{}", status.text);
+    }
+
+    #[test]
+    fn compare_waits_for_the_running_task() {
+        let mut state = tab();
+        compare(&mut state);
+        assert!(state.update(Message::CompareClicked { busy_outputs: Vec::new() }).is_none());
+        assert!(state.update(Message::DiffClicked).is_none());
+    }
+
+    #[test]
+    fn compare_refuses_an_output_another_tab_writes() {
+        let mut state = tab();
+        let busy_outputs = vec![output_path("a.csv", "modified")];
+        assert!(state.update(Message::CompareClicked { busy_outputs }).is_none());
+        assert!(state.status.as_ref().unwrap().is_error);
+        assert!(!state.is_busy());
+    }
+
+    #[test]
+    fn finished_comparison_is_kept() {
+        let mut state = tab();
+        compare(&mut state);
+        assert!(state.update(finished()).is_none());
+        assert!(!state.is_busy());
+        assert_eq!(state.status.as_ref().unwrap().text, "Comparison finished in 1.5 s");
+        assert_eq!(state.last_result.as_ref().unwrap().file1, "a.csv");
+    }
+
+    #[test]
+    fn failures_and_cancellation_free_the_tab() {
+        let mut state = tab();
+        compare(&mut state);
+        state.update(Message::CompareFinished(Err("No such file".to_string())));
+        assert_eq!(state.status.as_ref().unwrap(), &Status { text: "Error comparing files: No such file".to_string(), is_error: true });
+        assert!(!state.is_busy());
+
+        compare(&mut state);
+        state.update(Message::Cancelled);
+        assert_eq!(state.status.as_ref().unwrap(), &Status { text: "Cancelled".to_string(), is_error: false });
+        assert!(!state.is_busy());
+        assert!(state.last_result.is_none());
+    }
+
+    #[test]
+    fn diff_of_the_last_comparison_is_shown() {
+        let mut state = tab();
+        assert!(state.update(Message::DiffClicked).is_none(), "nothing to diff yet");
+
+        compare(&mut state);
+        state.update(finished());
+        assert!(matches!(state.update(Message::DiffClicked), Some(Effect::Diff { .. })));
+        assert_eq!(state.running, Some(Running::Diff));
+
+        match state.update(Message::DiffFinished(Ok(DiffModel::default()))) {
+            Some(Effect::ShowDiff { title, .. }) => assert_eq!(title, "Differences: a.csv vs b.csv"),
+            other => panic!("expected the diff window, got {:?}", other),
+        }
+        assert!(!state.is_busy());
+    }
+
+    #[test]
+    fn column_toggles_edit_the_lists() {
+        let mut state = tab();
+        state.update(Message::ColumnsEdited(ColumnList::Ignore, "a, b".to_string()));
+        state.update(Message::ColumnToggled { list: ColumnList::Ignore, column: "c".to_string(), listed: true });
+        state.update(Message::ColumnToggled { list: ColumnList::Ignore, column: "a".to_string(), listed: false });
+        state.update(Message::ColumnToggled { list: ColumnList::Key, column: "id".to_string(), listed: true });
+        assert_eq!(state.ignore_columns.columns, ["b", "c"]);
+        assert_eq!(state.ignore_columns.text, "b, c");
+        assert_eq!(state.key_columns.text, "id");
+    }
+
+    #[test]
+    fn column_names_with_commas_survive_toggles() {
+        let mut state = tab();
+        state.update(Message::ColumnToggled { list: ColumnList::Ignore, column: "Amount, USD".to_string(), listed: true });
+        state.update(Message::ColumnToggled { list: ColumnList::Ignore, column: " padded ".to_string(), listed: true });
+        assert_eq!(state.ignore_columns.columns, ["Amount, USD", " padded "]);
+        assert_eq!(state.ignore_columns.text, r#""Amount, USD", " padded ""#);
+
+        let mut ignored: Vec<String> = compare(&mut state).ignore_columns.into_iter().collect();
+        ignored.sort();
+        assert_eq!(ignored, [" padded ", "Amount, USD"]);
+
+        let mut copy = tab();
+        copy.update(Message::ColumnsEdited(ColumnList::Ignore, state.ignore_columns.text.clone()));
+        assert_eq!(copy.ignore_columns.columns, state.ignore_columns.columns);
+    }
+
+    #[test]
+    fn saved_comparisons_round_trip() {
+        let mut state = tab();
+        state.update(Message::ColumnsEdited(ColumnList::Ignore, "x".to_string()));
+        state.update(Message::DelimiterChosen(';'));
+        let saved = state.saved_comparison();
+        assert_eq!(saved.spec.delimiter.as_deref(), Some("semicolon"));
+
+        let copy = TabState::from_saved(&saved);
+        assert_eq!(copy.saved_comparison(), saved);
+        assert_eq!(copy.dialect().delimiter, ';');
+        assert_eq!(copy.title(), "a.csv vs b.csv");
+        assert_eq!(TabState::default().title(), "New Comparison");
+    }
+
+    #[test]
+    fn history_keeps_recent_comparisons_once() {
+        let mut history = History::default();
+        let saved = |n: usize| SavedComparison { file1: format!("{}.csv", n), file2: "b.csv".to_string(), spec: CompareSpec::default() };
+        for n in 0..MAX_RECENTS + 2 {
+            history.remember(saved(n));
+        }
+        history.remember(saved(5));
+        assert_eq!(history.recents.len(), MAX_RECENTS);
+        assert_eq!(history.recents[0], saved(5));
+        assert_eq!(history.recents.iter().filter(|r| **r == saved(5)).count(), 1);
+
+        assert_eq!(history.save_preset("  ", saved(1)), None);
+        assert_eq!(history.save_preset(" monthly ", saved(1)).as_deref(), Some("monthly"));
+        assert_eq!(history.presets["monthly"], saved(1));
+    }
+}
@@ -0,0 +1,367 @@
+use std::error::Error;
+use std::fs;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Instant;
+use csv::WriterBuilder;
+use serde::{Deserialize, Serialize};
+use crate::csv_compare::{compare_with_options, written_files};
+use crate::spec::CompareSpec;
+
+/// A manifest describing a batch of comparisons.
+///
+/// Manifests can be written in TOML or YAML, for example:
+///
+/// ```toml
+/// parallel = 4
+/// report = "weekly_report.csv"
+///
+/// [[job]]
+/// name = "orders"
+/// file1 = "exports/orders_new.csv"
+/// file2 = "exports/orders_old.csv"
+/// key_columns = ["order_id"]
+/// ignore_columns = ["exported_at"]
+/// delimiter = "semicolon"
+/// encoding = "utf8bom"
+/// output = "diffs/orders.csv"
+/// ```
+///
+/// Relative paths are resolved against the directory containing the manifest.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Manifest {
+    pub parallel: Option<usize>,  // Number of jobs run at the same time (default 1)
+    pub report: Option<String>,   // Consolidated report path (`.json` for JSON, else CSV)
+    #[serde(rename = "job", alias = "jobs")]
+    pub jobs: Vec<JobSpec>,       // The comparisons to run, in order
+}
+
+/// One comparison in a manifest.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct JobSpec {
+    pub name: String,             // Name of the job in the report
+    pub file1: String,            // The first CSV file
+    pub file2: String,            // The second CSV file
+    pub output: Option<String>,   // Output path (default `<file1>_modified.csv`)
+    #[serde(flatten)]
+    pub options: CompareSpec,     // Comparison options (ignore columns, dialect, encoding, ...)
+}
+
+/// Outcome of one job, as written to the consolidated report.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct JobResult {
+    pub name: String,            // Name of the job
+    pub status: String,          // `ok` or `error`
+    pub file1: String,           // Resolved path of the first file
+    pub file2: String,           // Resolved path of the second file
+    pub rows_file1: u64,         // Data rows read from the first file
+    pub rows_file2: u64,         // Data rows read from the second file
+    pub unique_rows: u64,        // Rows of the first file not found in the second
+    pub changed_rows: u64,       // Unique rows whose key exists in the second file
+    pub added_rows: u64,         // Unique rows whose key is absent from the second file
+    pub output: Option<String>,  // Path of the unique rows file
+    pub error: Option<String>,   // Error message if the job failed
+    pub elapsed_ms: u128,        // Wall-clock duration of the job
+}
+
+/// Loads a job manifest from a TOML (`.toml`) or YAML (`.yaml`/`.yml`) file.
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be read, has another extension, or does not
+/// match the manifest format.
+pub fn load_manifest(path: &Path) -> Result<Manifest, Box<dyn Error>> {
+    let text = fs::read_to_string(path)?;
+    let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("").to_lowercase();
+    let manifest = match extension.as_str() {
+        "toml" => toml::from_str(&text)?,
+        "yaml" | "yml" => serde_yaml::from_str(&text)?,
+        _ => return Err(format!("Unsupported manifest format '{}'. Use .toml, .yaml or .yml.", path.display()).into()),
+    };
+    Ok(manifest)
+}
+
+/// Runs every job of a manifest and returns their results in manifest order.
+///
+/// Jobs run on up to `parallel` threads. A failing job is recorded in its result and
+/// does not stop the others. A job that would write the same output or report file as
+/// an earlier job, or write over the input file of any job, fails without running.
+///
+/// # Arguments
+///
+/// * `manifest` - The jobs to run.
+/// * `base_dir` - Directory against which relative paths in the manifest are resolved.
+/// * `parallel` - Maximum number of jobs running at the same time (at least 1).
+pub fn run_jobs(manifest: &Manifest, base_dir: &Path, parallel: usize) -> Vec<JobResult> {
+    let next = AtomicUsize::new(0);
+    let results: Mutex<Vec<Option<JobResult>>> = Mutex::new(vec![None; manifest.jobs.len()]);
+    let workers = parallel.clamp(1, manifest.jobs.len().max(1));
+    let collisions = find_collisions(manifest, base_dir);
+
+    // Each worker takes the next unstarted job until none are left.
+    std::thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::SeqCst);
+                let Some(job) = manifest.jobs.get(index) else { break };
+                let result = run_job(job, index, base_dir, collisions[index].as_deref());
+                results.lock().expect("job results lock poisoned")[index] = Some(result);
+            });
+        }
+    });
+
+    results.into_inner()
+        .expect("job results lock poisoned")
+        .into_iter()
+        .flatten()
+        .collect()
+}
+
+/// Finds, for each job, a file it would write that is read by any job or written by
+/// an earlier one.
+///
+/// # Returns
+///
+/// One entry per job: `None` if its files are its own, or the error reported for it.
+fn find_collisions(manifest: &Manifest, base_dir: &Path) -> Vec<Option<String>> {
+    // Jobs run in parallel, so an input is at risk whichever job reads it.
+    let mut readers: HashMap<String, usize> = HashMap::new();
+    for (index, job) in manifest.jobs.iter().enumerate() {
+        for file in [&job.file1, &job.file2] {
+            readers.entry(resolve(base_dir, file)).or_insert(index);
+        }
+    }
+
+    let mut owners: HashMap<String, usize> = HashMap::new();
+    manifest.jobs.iter().enumerate()
+        .map(|(index, job)| {
+            let output = job.output.as_deref().map(|o| resolve(base_dir, o));
+            let files = written_files(&resolve(base_dir, &job.file1), output.as_deref());
+            let collision = files.iter().find_map(|file| {
+                if let Some(&reader) = readers.get(file) {
+                    return Some(format!("'{}' is an input of {}", file, job_name(&manifest.jobs[reader], reader)));
+                }
+                owners.get(file).map(|&owner| {
+                    format!("'{}' is also written by {}", file, job_name(&manifest.jobs[owner], owner))
+                })
+            });
+            if collision.is_none() {
+                for file in files {
+                    owners.insert(file, index);
+                }
+            }
+            collision
+        })
+        .collect()
+}
+
+/// Returns the name of a job in the report, numbering unnamed jobs from 1.
+fn job_name(job: &JobSpec, index: usize) -> String {
+    if job.name.is_empty() { format!("job {}", index + 1) } else { job.name.clone() }
+}
+
+/// Runs a single job, converting any failure (or a `collision` with an earlier job)
+/// into an `error` result.
+fn run_job(job: &JobSpec, index: usize, base_dir: &Path, collision: Option<&str>) -> JobResult {
+    let started = Instant::now();
+    let file1 = resolve(base_dir, &job.file1);
+    let file2 = resolve(base_dir, &job.file2);
+    let mut result = JobResult {
+        name: job_name(job, index),
+        file1: file1.clone(),
+        file2: file2.clone(),
+        ..JobResult::default()
+    };
+
+    let options = match collision {
+        Some(collision) => Err(collision.into()),
+        None => job.options.to_options().map_err(|e| e.into()),
+    };
+    let outcome = options.and_then(|mut options| {
+        options.output_file = job.output.as_deref().map(|o| resolve(base_dir, o));
+        if let Some(parent) = options.output_file.as_deref().and_then(|o| Path::new(o).parent()) {
+            fs::create_dir_all(parent)?;
+        }
+        compare_with_options(&file1, &file2, &options)
+    });
+
+    match outcome {
+        Ok(report) => {
+            result.status = "ok".to_string();
+            result.rows_file1 = report.rows_file1;
+            result.rows_file2 = report.rows_file2;
+            result.unique_rows = report.unique_rows;
+            result.changed_rows = report.changed_rows;
+            result.added_rows = report.added_rows;
+            result.output = Some(report.output_file);
+        }
+        Err(e) => {
+            result.status = "error".to_string();
+            result.error = Some(e.to_string());
+        }
+    }
+    result.elapsed_ms = started.elapsed().as_millis();
+    result
+}
+
+/// Resolves a manifest path against the manifest directory.
+fn resolve(base_dir: &Path, path: &str) -> String {
+    let path = PathBuf::from(path);
+    if path.is_absolute() { path } else { base_dir.join(path) }.display().to_string()
+}
+
+/// Writes the consolidated report as JSON (for a `.json` path) or CSV.
+pub fn write_job_report(path: &str, results: &[JobResult]) -> Result<(), Box<dyn Error>> {
+    if path.to_lowercase().ends_with(".json") {
+        fs::write(path, serde_json::to_string_pretty(results)?)?;
+        return Ok(());
+    }
+
+    let mut writer = WriterBuilder::new().from_path(path)?;
+    writer.write_record(["name", "status", "file1", "file2", "rows_file1", "rows_file2", "unique_rows", "changed_rows", "added_rows", "output", "error", "elapsed_ms"])?;
+    for r in results {
+        writer.write_record([
+            r.name.clone(),
+            r.status.clone(),
+            r.file1.clone(),
+            r.file2.clone(),
+            r.rows_file1.to_string(),
+            r.rows_file2.to_string(),
+            r.unique_rows.to_string(),
+            r.changed_rows.to_string(),
+            r.added_rows.to_string(),
+            r.output.clone().unwrap_or_default(),
+            r.error.clone().unwrap_or_default(),
+            r.elapsed_ms.to_string(),
+        ])?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    const TOML: &str = r#"
+parallel = 2
+report = "report.csv"
+
+[[job]]
+name = "orders"
+file1 = "new.csv"
+file2 = "old.csv"
+key_columns = ["id"]
+delimiter = "semicolon"
+output = "diffs/orders.csv"
+
+[[job]]
+file1 = "new.csv"
+file2 = "missing.csv"
+"#;
+
+    #[test]
+    fn loads_toml_and_yaml_manifests() {
+        let dir = TempDir::new();
+        let manifest = load_manifest(Path::new(&dir.write("weekly.toml", TOML))).unwrap();
+        assert_eq!((manifest.parallel, manifest.report.as_deref()), (Some(2), Some("report.csv")));
+        assert_eq!(manifest.jobs.len(), 2);
+        assert_eq!(manifest.jobs[0].options.key_columns, Some(vec!["id".to_string()]));
+        assert_eq!(manifest.jobs[0].options.delimiter.as_deref(), Some("semicolon"));
+        assert_eq!(manifest.jobs[0].output.as_deref(), Some("diffs/orders.csv"));
+        assert_eq!(manifest.jobs[1].name, "");
+
+        let yaml = "jobs:\n  - name: orders\n    file1: a.csv\n    file2: b.csv\n    ignore_columns: [ts]\n";
+        let manifest = load_manifest(Path::new(&dir.write("weekly.yml", yaml))).unwrap();
+        assert_eq!(manifest.parallel, None);
+        assert_eq!(manifest.jobs[0].options.ignore_columns, Some(vec!["ts".to_string()]));
+
+        let error = load_manifest(Path::new(&dir.write("weekly.json", "{}"))).unwrap_err();
+        assert!(error.to_string().starts_with("Unsupported manifest format"));
+        assert!(load_manifest(Path::new(&dir.write("bad.toml", "[[job]]\nfile1 = 3\n"))).is_err());
+    }
+
+    #[test]
+    fn runs_jobs_relative_to_the_manifest() {
+        let dir = TempDir::new();
+        dir.write("new.csv", "id;name\n1;a\n2;b\n");
+        dir.write("old.csv", "id;name\n1;a\n2;c\n");
+        let manifest = load_manifest(Path::new(&dir.write("weekly.toml", TOML))).unwrap();
+
+        let results = run_jobs(&manifest, Path::new(&dir.path("")), 2);
+        assert_eq!(results.len(), 2);
+        assert_eq!((results[0].status.as_str(), results[0].unique_rows, results[0].changed_rows), ("ok", 1, 1));
+        assert_eq!(dir.read("diffs/orders.csv"), "id;name\n2;b\n");
+        assert_eq!((results[1].name.as_str(), results[1].status.as_str()), ("job 2", "error"));
+
+        write_job_report(&dir.path("report.csv"), &results).unwrap();
+        assert!(dir.read("report.csv").starts_with("name,status,file1,file2,"));
+    }
+
+    #[test]
+    fn rejects_jobs_writing_the_same_files() {
+        let dir = TempDir::new();
+        dir.write("a.csv", "id\n1\n");
+        dir.write("b.csv", "id\n2\n");
+        let job = |name: &str, file2: &str, output: Option<&str>| JobSpec {
+            name: name.to_string(),
+            file1: "a.csv".to_string(),
+            file2: file2.to_string(),
+            output: output.map(str::to_string),
+            ..JobSpec::default()
+        };
+        let manifest = Manifest {
+            jobs: vec![
+                job("first", "b.csv", None),
+                job("second", "a.csv", None),
+                job("third", "a.csv", Some("a_modified.csv")),
+                job("fourth", "a.csv", Some("a_other.csv")),
+            ],
+            ..Manifest::default()
+        };
+
+        let results = run_jobs(&manifest, Path::new(&dir.path("")), 4);
+        let statuses: Vec<&str> = results.iter().map(|r| r.status.as_str()).collect();
+        assert_eq!(statuses, ["ok", "error", "error", "ok"]);
+        let error = results[1].error.as_deref().unwrap();
+        assert!(error.ends_with("a_modified.csv' is also written by first"), "{}", error);
+        assert_eq!(dir.read("a_modified.csv"), "id\n1\n");
+    }
+
+    #[test]
+    fn rejects_jobs_writing_over_an_input() {
+        let dir = TempDir::new();
+        dir.write("a.csv", "id\n1\n");
+        dir.write("b.csv", "id\n2\n");
+        dir.write("c.csv", "id\n3\n");
+        let job = |name: &str, file1: &str, file2: &str, output: Option<&str>| JobSpec {
+            name: name.to_string(),
+            file1: file1.to_string(),
+            file2: file2.to_string(),
+            output: output.map(str::to_string),
+            ..JobSpec::default()
+        };
+        let manifest = Manifest {
+            jobs: vec![
+                job("first", "a.csv", "b.csv", Some("c.csv")),
+                job("second", "b.csv", "c.csv", None),
+                job("third", "c.csv", "a.csv", Some("c.csv")),
+            ],
+            ..Manifest::default()
+        };
+
+        let results = run_jobs(&manifest, Path::new(&dir.path("")), 1);
+        let statuses: Vec<&str> = results.iter().map(|r| r.status.as_str()).collect();
+        assert_eq!(statuses, ["error", "ok", "error"]);
+        let error = results[0].error.as_deref().unwrap();
+        assert!(error.ends_with("c.csv' is an input of second"), "{}", error);
+        let error = results[2].error.as_deref().unwrap();
+        assert!(error.ends_with("c.csv' is an input of second"), "{}", error);
+        assert_eq!(dir.read("c.csv"), "id\n3\n");
+    }
+}
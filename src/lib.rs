@@ -7,13 +7,45 @@
 //! ## Modules
 //! 
 //! - `encoding`: Contains functions to detect file encoding and validate UTF-8.
+//! - `transcode`: Contains the encoding of output files, with the policy for unrepresentable characters.
 //! - `csv_compare`: Contains functions for comparing CSV files and writing unique rows.
+//! - `dialect`: Contains the CSV dialect (delimiter and quoting) settings.
+//! - `number`: Contains locale-aware parsing of numeric cells.
+//! - `nulls`: Contains the configurable sets of tokens treated as missing values.
+//! - `ragged`: Contains the handling of records with an unexpected number of fields.
+//! - `merge`: Contains the three-way merge of CSV files sharing a common base.
+//! - `nway`: Contains the comparison of any number of CSV files with a presence matrix.
+//! - `dir_compare`: Contains the pairwise comparison of two directories of CSV files.
+//! - `spec`: Contains the serializable description of comparison options.
+//! - `jobs`: Contains the job manifest format and the batch comparison runner.
+//! - `config`: Contains the discovery and loading of named comparison profiles.
+//! - `preview`: Contains the quick look at the beginning of a CSV file.
+//! - `diff`: Contains the side-by-side diff model of two CSV files.
+//! - `progress`: Contains the progress counters and cancellation flag of long-running operations.
+//! - `cli`: Contains the command-line interface shared by all binaries.
 
 pub mod encoding; // Module for file encoding functions.
+pub mod transcode; // Module for output encodings.
 pub mod csv_compare; // Module for CSV comparison logic.
+pub mod dialect; // Module for CSV dialect settings.
+pub mod number; // Module for locale-aware number parsing.
+pub mod nulls; // Module for null-equivalence token sets.
+pub mod ragged; // Module for tolerant reading of ragged and malformed records.
+pub mod merge; // Module for three-way CSV merges.
+pub mod nway; // Module for N-way CSV comparisons.
+pub mod dir_compare; // Module for directory-to-directory comparisons.
+pub mod spec; // Module for serializable comparison options.
+pub mod jobs; // Module for batch job manifests.
+pub mod config; // Module for configuration files and profiles.
+pub mod preview; // Module for file previews.
+pub mod diff; // Module for side-by-side diffs.
+pub mod progress; // Module for progress reporting and cancellation.
+pub mod cli; // Module for the unified command-line interface.
+#[cfg(test)]
+mod test_util; // Module for temporary files used by the unit tests.
 
 use std::collections::HashSet; // Import HashSet for managing ignored columns.
-use crate::csv_compare::compare_and_write_unique_rows; // Import the function to compare and write unique CSV rows.
+use crate::csv_compare::{compare_with_options, CompareOptions, CompareReport}; // Import the CSV comparison, its options and its report.
 use crate::encoding::Encoding; // Import the Encoding enum to specify output encoding.
 
 /// Compares two CSV files and writes unique rows from the first file to a new CSV file.
@@ -30,12 +62,30 @@ use crate::encoding::Encoding; // Import the Encoding enum to specify output enc
 /// A `Result` that is `Ok(())` if the comparison and writing are successful, or
 /// an `Err(String)` containing an error message if there are issues during the process.
 pub fn compare_csv(file1: &str, file2: &str, encoding: Encoding, ignore_columns: &[&str]) -> Result<(), String> {
+    compare_with_report(file1, file2, encoding, ignore_columns).map(|_| ())
+}
+
+/// Compares two CSV files like `compare_csv`, and returns the report of the run.
+///
+/// # Arguments
+///
+/// * `file1` - The path to the first CSV file.
+/// * `file2` - The path to the second CSV file.
+/// * `encoding` - The encoding format to use for the output CSV file.
+/// * `ignore_columns` - A slice of column names to ignore during the comparison.
+///
+/// # Returns
+///
+/// The `CompareReport`, whose `output_file` names the file written, or an `Err(String)`
+/// containing an error message if there are issues during the process.
+pub fn compare_with_report(file1: &str, file2: &str, encoding: Encoding, ignore_columns: &[&str]) -> Result<CompareReport, String> {
     // Create a HashSet of ignored column names for efficient lookups.
     let ignore_set: HashSet<String> = ignore_columns.iter()
         .map(|s| s.to_string())
         .collect();
+    let options = CompareOptions { ignore_columns: ignore_set, encoding, ..CompareOptions::default() };
 
     // Call the CSV comparison function and handle any potential errors.
-    compare_and_write_unique_rows(file1, file2, &ignore_set, encoding)
+    compare_with_options(file1, file2, &options)
         .map_err(|e| e.to_string()) // Convert the error to a String if it occurs.
 }
\ No newline at end of file
@@ -1,29 +1,47 @@
-use clap::{Command, Arg}; // Use Command instead of App for argument parsing
+use std::process::ExitCode;
+use clap::Parser;
+use rust_csv_scripts::cli::{self, Cli, Commands};
 
+#[cfg(feature = "gui")]
 mod gui; // Import the GUI module for graphical user interface functionality
+#[cfg(feature = "gui")]
+mod diff_view; // Import the side-by-side diff window used by the GUI
+#[cfg(feature = "gui")]
+mod gui_state; // Import the GUI state and the messages that change it, kept apart from rendering
 
-/// The main entry point of the program. 
-/// 
-/// This function initializes the command-line interface (CLI) for the CSV comparison utility,
-/// and it optionally launches the graphical user interface (GUI) based on user input.
-fn main() {
-    // Define command-line arguments using the `Command` struct from the `clap` crate.
-    let matches = Command::new("CSV Scripts") // Use Command::new instead of App::new
-        .version("0.1.0") // Set version of the program
-        .about("Utility for comparing CSV files and checking encoding") // Brief description of the program
-        .arg(
-            Arg::new("use_gui") // Define an argument for launching the GUI
-                .short('g') // Short form of the argument (-g)
-                .long("use_gui") // Long form of the argument (--use_gui)
-                .help("Launch the GUI for CSV comparison"), // Help message for this argument
-        )
-        .get_matches(); // Parse the command-line arguments
+/// The main entry point of the program.
+///
+/// This function parses the unified command-line interface (CLI) and runs the chosen
+/// subcommand, such as `compare` or `encoding`. The `gui` subcommand (or the older
+/// `--use_gui` flag) launches the graphical user interface (GUI) instead.
+fn main() -> ExitCode {
+    let cli = Cli::parse();
 
-    // Check if the "use_gui" argument was provided by the user.
-    if matches.get_one::<String>("use_gui").is_some() {
-        gui::run_gui(); // If the "use_gui" argument is present, launch the GUI.
-    } else {
-        // If no arguments are provided, display a message with instructions.
-        println!("Please run the desired command-line program with the appropriate options.");
+    // The GUI lives in this binary, so it is launched here rather than by `cli::run`.
+    if cli.use_gui.is_some() || matches!(cli.command, Some(Commands::Gui)) {
+        return launch_gui();
     }
-}
\ No newline at end of file
+
+    // Without a subcommand, show the available commands.
+    if cli.command.is_none() {
+        use clap::CommandFactory;
+        let _ = Cli::command().print_help();
+        return ExitCode::SUCCESS;
+    }
+
+    cli::run(cli)
+}
+
+/// Launches the GUI.
+#[cfg(feature = "gui")]
+fn launch_gui() -> ExitCode {
+    gui::run_gui();
+    ExitCode::SUCCESS
+}
+
+/// Reports that the GUI is not available in this build.
+#[cfg(not(feature = "gui"))]
+fn launch_gui() -> ExitCode {
+    eprintln!("This build of rust_csv_scripts was compiled without the GUI. Rebuild with the `gui` feature enabled (it is on by default) to use it.");
+    ExitCode::FAILURE
+}
@@ -0,0 +1,320 @@
+use std::error::Error;
+use std::collections::{HashMap, HashSet};
+use serde::Serialize;
+use csv::StringRecord;
+use crate::csv_compare::{key_indices, output_path, record_key};
+use crate::ragged::{read_regular_record, RaggedPolicy, Rejects};
+use crate::dialect::Dialect;
+
+/// Options controlling a three-way merge.
+#[derive(Debug, Clone, Default)]
+pub struct MergeOptions {
+    pub key_columns: Vec<String>,  // Columns that identify the same row across the three files
+    pub ragged: RaggedPolicy,      // How records with the wrong number of fields are handled
+    pub output: Option<String>,    // Path of the merged file (default `<ours>_merged.csv`)
+    pub conflicts: Option<String>, // Path of the conflicts file (default `<ours>_conflicts.csv`)
+    pub dialect: Dialect,          // Delimiter and quoting of the input and merged files
+}
+
+/// Summary of a three-way merge.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct MergeReport {
+    pub output_file: String,            // Path of the merged CSV file
+    pub conflicts_file: Option<String>, // Path of the conflicts file, if there were conflicts
+    pub merged_rows: u64,               // Rows written to the merged file
+    pub changes_from_ours: u64,         // Rows where a change from `ours` was applied
+    pub changes_from_theirs: u64,       // Rows where a change from `theirs` was applied
+    pub conflicts: u64,                 // Keys changed differently on both sides
+}
+
+/// The rows of one file indexed by key, in file order.
+struct KeyedTable {
+    headers: StringRecord,                     // Header row of the file
+    order: Vec<Vec<String>>,                   // Keys in the order they appear in the file
+    rows: HashMap<Vec<String>, StringRecord>,  // Records by key
+}
+
+/// Result of merging the three versions of one row.
+enum RowMerge {
+    Resolved { record: Option<StringRecord>, from_ours: bool, from_theirs: bool },
+    Conflict,
+}
+
+/// Performs a three-way merge of CSV files that share a base version.
+///
+/// Rows are matched by `options.key_columns`. A change made on only one side (an
+/// added, deleted or modified row) is applied automatically. When both sides modify
+/// the same row, the changes are merged cell by cell; if the same cell (or the
+/// existence of the row) was changed differently on both sides the row is a conflict.
+/// Conflicting rows keep their base version in the merged file, and all three versions
+/// are written to the conflicts file for manual resolution, in the same dialect, after
+/// one `key_<column>` field per key column.
+///
+/// # Arguments
+///
+/// * `base` - The path to the common ancestor CSV file.
+/// * `ours` - The path to our edited copy.
+/// * `theirs` - The path to their edited copy.
+/// * `options` - The merge options.
+///
+/// # Returns
+///
+/// A `MergeReport` describing the merge, or an error if the files cannot be read or
+/// written, their headers differ, or a key appears twice in one file.
+pub fn merge_three_way(base: &str, ours: &str, theirs: &str, options: &MergeOptions) -> Result<MergeReport, Box<dyn Error>> {
+    if options.key_columns.is_empty() {
+        return Err("At least one key column is required for a three-way merge".into());
+    }
+
+    // Load the three versions, sharing one rejects file for irregular records.
+    let mut rejects = Rejects::new(output_path(ours, "rejects"));
+    let base_table = load_keyed(base, options, &mut rejects)?;
+    let ours_table = load_keyed(ours, options, &mut rejects)?;
+    let theirs_table = load_keyed(theirs, options, &mut rejects)?;
+    rejects.flush()?;
+
+    // All versions must have the same layout for rows to be merged cell by cell.
+    for (path, table) in [(ours, &ours_table), (theirs, &theirs_table)] {
+        if table.headers != base_table.headers {
+            return Err(format!("Headers of '{}' differ from the base file '{}'", path, base).into());
+        }
+    }
+
+    let output_file = options.output.clone().unwrap_or_else(|| output_path(ours, "merged"));
+    let conflicts_file = options.conflicts.clone().unwrap_or_else(|| output_path(ours, "conflicts"));
+    let mut report = MergeReport { output_file: output_file.clone(), ..MergeReport::default() };
+
+    let mut writer = options.dialect.writer_builder().from_path(&output_file)?;
+    writer.write_record(&base_table.headers)?;
+    let mut conflict_writer = None;
+
+    // Visit keys in base order, then keys added in ours, then keys added in theirs.
+    let mut seen: HashSet<&Vec<String>> = HashSet::new();
+    let keys = base_table.order.iter()
+        .chain(ours_table.order.iter())
+        .chain(theirs_table.order.iter());
+
+    for key in keys {
+        if !seen.insert(key) {
+            continue;
+        }
+        let b = base_table.rows.get(key);
+        let o = ours_table.rows.get(key);
+        let t = theirs_table.rows.get(key);
+
+        match merge_row(b, o, t) {
+            RowMerge::Resolved { record, from_ours, from_theirs } => {
+                report.changes_from_ours += u64::from(from_ours);
+                report.changes_from_theirs += u64::from(from_theirs);
+                if let Some(record) = record {
+                    writer.write_record(&record)?;
+                    report.merged_rows += 1;
+                }
+            }
+            RowMerge::Conflict => {
+                report.conflicts += 1;
+                // Keep the base version in the merged output until the conflict is resolved.
+                if let Some(record) = b {
+                    writer.write_record(record)?;
+                    report.merged_rows += 1;
+                }
+                if conflict_writer.is_none() {
+                    let mut w = options.dialect.writer_builder().from_path(&conflicts_file)?;
+                    let mut header: Vec<String> = options.key_columns.iter().map(|k| format!("key_{}", k)).collect();
+                    header.extend(["version", "state"].map(String::from));
+                    header.extend(base_table.headers.iter().map(String::from));
+                    w.write_record(&header)?;
+                    conflict_writer = Some(w);
+                }
+                let w = conflict_writer.as_mut().expect("conflicts writer is open");
+                for (version, record) in [("base", b), ("ours", o), ("theirs", t)] {
+                    let state = if record.is_some() { "present" } else { "absent" };
+                    let mut row: Vec<&str> = key.iter().map(String::as_str).collect();
+                    row.extend([version, state]);
+                    if let Some(record) = record {
+                        row.extend(record.iter());
+                    }
+                    // Pad absent versions so every row has the full width.
+                    row.resize(key.len() + 2 + base_table.headers.len(), "");
+                    w.write_record(&row)?;
+                }
+            }
+        }
+    }
+
+    writer.flush()?;
+    if let Some(mut w) = conflict_writer {
+        w.flush()?;
+        report.conflicts_file = Some(conflicts_file);
+    }
+
+    Ok(report)
+}
+
+/// Reads a CSV file into a table of records indexed by key.
+fn load_keyed(path: &str, options: &MergeOptions, rejects: &mut Rejects) -> Result<KeyedTable, Box<dyn Error>> {
+    let mut reader = options.dialect.reader_builder().flexible(options.ragged.is_flexible()).from_path(path)?;
+    let headers = reader.headers()?.clone();
+    let indices = key_indices(&headers, &options.key_columns).map_err(|e| format!("{} in '{}'", e, path))?;
+
+    let mut table = KeyedTable { headers, order: Vec::new(), rows: HashMap::new() };
+    while let Some(record) = read_regular_record(&mut reader, path, table.headers.len(), options.ragged, "", rejects)? {
+        let key = record_key(&record, &indices);
+        if table.rows.contains_key(&key) {
+            let line = record.position().map_or(0, |p| p.line());
+            return Err(format!("Duplicate key '{}' in '{}' at line {}", key.join("|"), path, line).into());
+        }
+        table.order.push(key.clone());
+        table.rows.insert(key, record);
+    }
+
+    Ok(table)
+}
+
+/// Returns `true` if two optional records have the same fields.
+fn same(a: Option<&StringRecord>, b: Option<&StringRecord>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => a.iter().eq(b.iter()),
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+/// Merges the base, ours and theirs versions of one row.
+fn merge_row(base: Option<&StringRecord>, ours: Option<&StringRecord>, theirs: Option<&StringRecord>) -> RowMerge {
+    let ours_changed = !same(base, ours);
+    let theirs_changed = !same(base, theirs);
+
+    // Same result on both sides (including both unchanged or both deleted).
+    if same(ours, theirs) {
+        return RowMerge::Resolved { record: ours.cloned(), from_ours: ours_changed, from_theirs: theirs_changed };
+    }
+    if !ours_changed {
+        return RowMerge::Resolved { record: theirs.cloned(), from_ours: false, from_theirs: true };
+    }
+    if !theirs_changed {
+        return RowMerge::Resolved { record: ours.cloned(), from_ours: true, from_theirs: false };
+    }
+
+    // Both sides modified an existing row: merge cell by cell.
+    let (Some(b), Some(o), Some(t)) = (base, ours, theirs) else {
+        return RowMerge::Conflict; // Added differently on both sides, or deleted on one and modified on the other.
+    };
+    let mut merged = StringRecord::new();
+    for ((bf, of), tf) in b.iter().zip(o.iter()).zip(t.iter()) {
+        if of == tf || tf == bf {
+            merged.push_field(of);
+        } else if of == bf {
+            merged.push_field(tf);
+        } else {
+            return RowMerge::Conflict;
+        }
+    }
+    RowMerge::Resolved { record: Some(merged), from_ours: true, from_theirs: true }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    const BASE: &str = "id,name,qty\n1,apple,5\n2,pear,3\n3,plum,7\n4,fig,1\n";
+
+    fn options() -> MergeOptions {
+        MergeOptions { key_columns: vec!["id".to_string()], ..MergeOptions::default() }
+    }
+
+    /// Merges `ours` and `theirs` against `BASE` inside `dir`.
+    fn merge(dir: &TempDir, ours: &str, theirs: &str) -> Result<MergeReport, Box<dyn Error>> {
+        let base = dir.write("base.csv", BASE);
+        let ours = dir.write("a.csv", ours);
+        let theirs = dir.write("b.csv", theirs);
+        merge_three_way(&base, &ours, &theirs, &options())
+    }
+
+    #[test]
+    fn applies_changes_from_both_sides() {
+        let dir = TempDir::new();
+        let report = merge(
+            &dir,
+            "id,name,qty\n1,apple,6\n2,pear,3\n3,plum,7\n4,fig,1\n5,kiwi,2\n",
+            "id,name,qty\n1,apple,5\n2,Pear,3\n3,plum,7\n",
+        ).unwrap();
+        assert_eq!(dir.read("a_merged.csv"), "id,name,qty\n1,apple,6\n2,Pear,3\n3,plum,7\n5,kiwi,2\n");
+        assert_eq!((report.changes_from_ours, report.changes_from_theirs, report.conflicts), (2, 2, 0));
+        assert_eq!(report.merged_rows, 4);
+        assert_eq!(report.conflicts_file, None);
+    }
+
+    #[test]
+    fn merges_different_cells_of_the_same_row() {
+        let dir = TempDir::new();
+        let report = merge(&dir, &BASE.replace("apple,5", "Apple,5"), &BASE.replace("apple,5", "apple,9")).unwrap();
+        assert!(dir.read("a_merged.csv").contains("\n1,Apple,9\n"));
+        assert_eq!(report.conflicts, 0);
+    }
+
+    #[test]
+    fn reports_conflicting_cells_and_keeps_the_base_row() {
+        let dir = TempDir::new();
+        let report = merge(&dir, &BASE.replace("pear,3", "pear,4"), &BASE.replace("pear,3", "pear,8")).unwrap();
+        assert_eq!(report.conflicts, 1);
+        assert_eq!(dir.read("a_merged.csv"), BASE);
+        assert_eq!(report.conflicts_file.as_deref(), Some(dir.path("a_conflicts.csv").as_str()));
+        assert_eq!(
+            dir.read("a_conflicts.csv"),
+            "key_id,version,state,id,name,qty\n2,base,present,2,pear,3\n2,ours,present,2,pear,4\n2,theirs,present,2,pear,8\n"
+        );
+    }
+
+    #[test]
+    fn writes_conflicts_in_the_dialect_with_one_field_per_key_column() {
+        let dir = TempDir::new();
+        let base = dir.write("base.csv", "region;id;qty\na|b;1;5\n");
+        let ours = dir.write("a.csv", "region;id;qty\na|b;1;6\n");
+        let theirs = dir.write("b.csv", "region;id;qty\na|b;1;7\n");
+        let options = MergeOptions {
+            key_columns: vec!["region".to_string(), "id".to_string()],
+            dialect: Dialect { delimiter: ';', ..Dialect::default() },
+            ..MergeOptions::default()
+        };
+
+        let report = merge_three_way(&base, &ours, &theirs, &options).unwrap();
+        assert_eq!(report.conflicts, 1);
+        assert_eq!(
+            dir.read("a_conflicts.csv"),
+            "key_region;key_id;version;state;region;id;qty\na|b;1;base;present;a|b;1;5\na|b;1;ours;present;a|b;1;6\na|b;1;theirs;present;a|b;1;7\n"
+        );
+    }
+
+    #[test]
+    fn reports_a_deletion_against_a_modification() {
+        let dir = TempDir::new();
+        let report = merge(&dir, &BASE.replace("3,plum,7\n", ""), &BASE.replace("plum,7", "plum,0")).unwrap();
+        assert_eq!(report.conflicts, 1);
+        assert!(dir.read("a_conflicts.csv").contains("\n3,ours,absent,,,\n"));
+    }
+
+    #[test]
+    fn reports_rows_added_differently_on_both_sides() {
+        let dir = TempDir::new();
+        let report = merge(&dir, &format!("{}9,lime,1\n", BASE), &format!("{}9,lime,2\n", BASE)).unwrap();
+        assert_eq!(report.conflicts, 1);
+        assert!(dir.read("a_conflicts.csv").contains("\n9,base,absent,,,\n"));
+        assert!(!dir.read("a_merged.csv").contains("lime"));
+    }
+
+    #[test]
+    fn rejects_duplicate_keys_and_different_headers() {
+        let dir = TempDir::new();
+        let error = merge(&dir, &format!("{}1,apple,5\n", BASE), BASE).unwrap_err();
+        assert!(error.to_string().starts_with("Duplicate key '1'"), "{}", error);
+
+        let error = merge(&dir, &BASE.replace("qty", "count"), BASE).unwrap_err();
+        assert!(error.to_string().starts_with("Headers of"), "{}", error);
+
+        let base = dir.write("base.csv", BASE);
+        let error = merge_three_way(&base, &base, &base, &MergeOptions::default()).unwrap_err();
+        assert!(error.to_string().contains("key column is required"));
+    }
+}
@@ -0,0 +1,120 @@
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use csv::StringRecord;
+
+/// The spellings of a missing value that are treated as equal by default.
+pub const STANDARD_NULL_TOKENS: [&str; 6] = ["", "NULL", "null", "N/A", "-", "\\N"];
+
+/// Marker substituted for any null token so that all spellings of a missing
+/// value compare equal, without colliding with real cell content.
+pub(crate) const NULL_MARKER: &str = "\u{0}NULL\u{0}";
+
+/// Empties the fields of a record that hold `NULL_MARKER` (padded into short records
+/// when missing fields are null), so the record can be written or shown as read.
+pub(crate) fn clear_null_markers(record: &StringRecord) -> Cow<'_, StringRecord> {
+    if !record.iter().any(|field| field == NULL_MARKER) {
+        return Cow::Borrowed(record);
+    }
+    let mut cleared: StringRecord = record.iter().map(|field| if field == NULL_MARKER { "" } else { field }).collect();
+    cleared.set_position(record.position().cloned());
+    Cow::Owned(cleared)
+}
+
+/// Configurable sets of tokens that represent a missing value.
+///
+/// Tokens in `global` apply to every column. A column listed in `per_column`
+/// uses its own set instead, so a column where `-` is meaningful can opt out.
+/// Tokens are matched against the cell content with surrounding whitespace removed.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NullTokens {
+    pub global: HashSet<String>,                    // Tokens treated as null in every column
+    pub per_column: HashMap<String, HashSet<String>>, // Column-specific token sets (override `global`)
+}
+
+impl NullTokens {
+    /// The standard set: empty string, `NULL`, `null`, `N/A`, `-` and `\N`.
+    pub fn standard() -> Self {
+        Self::from_tokens(STANDARD_NULL_TOKENS.iter().copied())
+    }
+
+    /// Builds a global token set from the given tokens.
+    pub fn from_tokens<'a>(tokens: impl IntoIterator<Item = &'a str>) -> Self {
+        Self {
+            global: tokens.into_iter().map(|t| t.trim().to_string()).collect(),
+            per_column: HashMap::new(),
+        }
+    }
+
+    /// Returns `true` if no tokens are configured, i.e. null equivalence is off.
+    pub fn is_empty(&self) -> bool {
+        self.global.is_empty() && self.per_column.is_empty()
+    }
+
+    /// Sets the tokens used for a single column, replacing the global set for it.
+    pub fn set_column<'a>(&mut self, column: &str, tokens: impl IntoIterator<Item = &'a str>) {
+        self.per_column.insert(column.to_string(), tokens.into_iter().map(|t| t.trim().to_string()).collect());
+    }
+
+    /// Parses a column override written as `column=tok1|tok2`.
+    ///
+    /// # Returns
+    ///
+    /// The column name and its tokens, or an error message if there is no `=`.
+    pub fn parse_column_spec(spec: &str) -> Result<(String, Vec<String>), String> {
+        let (column, tokens) = spec.split_once('=')
+            .ok_or_else(|| format!("Invalid null column spec '{}'. Use column=tok1|tok2.", spec))?;
+        Ok((column.trim().to_string(), tokens.split('|').map(|t| t.trim().to_string()).collect()))
+    }
+
+    /// Checks whether `value` is a null token for `column`.
+    pub fn is_null(&self, column: &str, value: &str) -> bool {
+        let tokens = self.per_column.get(column).unwrap_or(&self.global);
+        tokens.contains(value.trim())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn standard_tokens_match_trimmed_cells() {
+        let tokens = NullTokens::standard();
+        for cell in ["", "  ", "NULL", " null ", "N/A", "-", "\\N"] {
+            assert!(tokens.is_null("any", cell), "{:?} should be null", cell);
+        }
+        for cell in ["0", "Null", "n/a", "none", "--"] {
+            assert!(!tokens.is_null("any", cell), "{:?} should not be null", cell);
+        }
+    }
+
+    #[test]
+    fn column_tokens_replace_the_global_set() {
+        let mut tokens = NullTokens::standard();
+        tokens.set_column("delta", ["NULL", " N/A "]);
+        assert!(!tokens.is_null("delta", "-"));
+        assert!(!tokens.is_null("delta", ""));
+        assert!(tokens.is_null("delta", "N/A"));
+        assert!(tokens.is_null("other", "-"));
+    }
+
+    #[test]
+    fn empty_tokens_disable_null_equivalence() {
+        assert!(NullTokens::default().is_empty());
+        assert!(!NullTokens::default().is_null("any", ""));
+        let mut tokens = NullTokens::default();
+        tokens.set_column("status", ["none"]);
+        assert!(!tokens.is_empty());
+        assert!(tokens.is_null("status", "none"));
+        assert!(!tokens.is_null("other", "none"));
+    }
+
+    #[test]
+    fn parses_column_specs() {
+        assert_eq!(
+            NullTokens::parse_column_spec(" status = NULL|N/A| "),
+            Ok(("status".to_string(), vec!["NULL".to_string(), "N/A".to_string(), String::new()]))
+        );
+        assert!(NullTokens::parse_column_spec("status").unwrap_err().starts_with("Invalid null column spec"));
+    }
+}
@@ -0,0 +1,243 @@
+use std::str::FromStr;
+
+/// Describes how numbers are written in a particular file.
+///
+/// European exports typically write `1.234,56` while US exports write `1,234.56`.
+/// A `NumberLocale` captures the decimal separator, the characters that may be
+/// used to group thousands and the currency symbols that may surround a value,
+/// so that both spellings can be parsed to the same numeric value.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NumberLocale {
+    pub decimal_separator: char,        // Character separating the integer and fractional parts
+    pub grouping_separators: Vec<char>, // Characters allowed between groups of integer digits
+    pub currency_symbols: Vec<String>,  // Symbols or codes stripped from either end of a value
+}
+
+impl NumberLocale {
+    /// US/UK style: `1,234.56`.
+    pub fn us() -> Self {
+        Self::with_separators('.', &[','])
+    }
+
+    /// Continental European style: `1.234,56` (also accepts spaces as grouping).
+    pub fn european() -> Self {
+        Self::with_separators(',', &['.', ' ', '\u{a0}', '\u{202f}'])
+    }
+
+    /// French style: `1 234,56`.
+    pub fn french() -> Self {
+        Self::with_separators(',', &[' ', '\u{a0}', '\u{202f}'])
+    }
+
+    /// Swiss style: `1'234.56`.
+    pub fn swiss() -> Self {
+        Self::with_separators('.', &['\'', '\u{2019}'])
+    }
+
+    /// Plain machine format: `1234.56`, no grouping allowed.
+    pub fn plain() -> Self {
+        Self::with_separators('.', &[])
+    }
+
+    /// Builds a locale from explicit separators and the default currency symbols.
+    pub fn with_separators(decimal_separator: char, grouping_separators: &[char]) -> Self {
+        Self {
+            decimal_separator,
+            grouping_separators: grouping_separators.to_vec(),
+            currency_symbols: ["$", "€", "£", "¥", "CHF", "USD", "EUR", "GBP", "JPY"]
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+        }
+    }
+
+    /// Parses a cell into a number according to this locale.
+    ///
+    /// Leading or trailing signs (`-1,5`, `1,5-`), a leading `+`, the Unicode minus
+    /// sign and currency symbols on either side of the value are accepted.
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - The raw cell content.
+    ///
+    /// # Returns
+    ///
+    /// `Some(value)` if the cell is a number in this locale, or `None` otherwise.
+    pub fn parse(&self, text: &str) -> Option<f64> {
+        let mut rest = text.trim();
+        let mut negative = false;
+        let mut sign_seen = false;
+
+        // Peel currency symbols, whitespace and sign characters off both ends.
+        loop {
+            let before = rest;
+            rest = rest.trim();
+            for symbol in &self.currency_symbols {
+                rest = rest.strip_prefix(symbol.as_str()).unwrap_or(rest);
+                rest = rest.strip_suffix(symbol.as_str()).unwrap_or(rest);
+            }
+            rest = rest.trim();
+            if let Some(stripped) = strip_sign(rest) {
+                if sign_seen {
+                    return None; // Only one sign is allowed.
+                }
+                sign_seen = true;
+                negative = stripped.1;
+                rest = stripped.0;
+            }
+            if rest == before {
+                break;
+            }
+        }
+
+        // Rebuild the number in plain `1234.56` form while validating the layout.
+        // Once grouping is used, every group after the first must hold exactly three digits.
+        let mut plain = String::with_capacity(rest.len() + 1);
+        let mut seen_decimal = false;
+        let mut grouped = false;
+        let mut group_digits = 0;
+        let mut digits = 0;
+        for c in rest.chars() {
+            if c.is_ascii_digit() {
+                plain.push(c);
+                group_digits += 1;
+                digits += 1;
+            } else if c == self.decimal_separator && !seen_decimal {
+                if grouped && group_digits != 3 {
+                    return None;
+                }
+                plain.push('.');
+                seen_decimal = true;
+                group_digits = 0;
+            } else if self.grouping_separators.contains(&c) && !seen_decimal {
+                if group_digits == 0 || (grouped && group_digits != 3) {
+                    return None;
+                }
+                grouped = true;
+                group_digits = 0;
+            } else {
+                return None;
+            }
+        }
+        if digits == 0 || (grouped && !seen_decimal && group_digits != 3) {
+            return None; // Empty, or a malformed final group.
+        }
+
+        let value: f64 = plain.parse().ok()?;
+        Some(if negative { -value } else { value })
+    }
+}
+
+/// Strips a single leading or trailing sign, returning the rest and whether it was negative.
+fn strip_sign(text: &str) -> Option<(&str, bool)> {
+    for (sign, negative) in [('-', true), ('\u{2212}', true), ('+', false)] {
+        if let Some(rest) = text.strip_prefix(sign) {
+            return Some((rest, negative));
+        }
+        if negative {
+            if let Some(rest) = text.strip_suffix(sign) {
+                return Some((rest, negative));
+            }
+        }
+    }
+    None
+}
+
+impl Default for NumberLocale {
+    fn default() -> Self {
+        Self::us()
+    }
+}
+
+impl FromStr for NumberLocale {
+    type Err = String;
+
+    /// Parses a locale name such as `us`, `eu`, `fr`, `ch` or `plain`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "us" | "en" | "uk" => Ok(Self::us()),
+            "eu" | "de" | "european" | "nl" | "it" | "es" => Ok(Self::european()),
+            "fr" | "french" => Ok(Self::french()),
+            "ch" | "swiss" => Ok(Self::swiss()),
+            "plain" | "c" => Ok(Self::plain()),
+            other => Err(format!("Unknown number locale '{}'. Use us, eu, fr, ch or plain.", other)),
+        }
+    }
+}
+
+/// Turns a parsed number into the canonical text used for comparison.
+///
+/// Without `round_to` the value is compared exactly. With `round_to`, the value is
+/// rounded to the nearest multiple of it, so two values compare as equal when they
+/// round to the same multiple. This is not a tolerance: rounding to 0.01 makes `1.004`
+/// and `0.996` both `1.00`, but keeps `1.004` and `1.006` apart, even though they are closer.
+pub fn canonical_number(value: f64, round_to: Option<f64>) -> String {
+    let value = match round_to {
+        Some(t) if t > 0.0 => (value / t).round() * t,
+        _ => value,
+    };
+    // Avoid "-0" so that a negative value rounding to zero equals zero.
+    let value = if value == 0.0 { 0.0 } else { value };
+    format!("{}", value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_each_locale() {
+        assert_eq!(NumberLocale::us().parse("1,234.56"), Some(1234.56));
+        assert_eq!(NumberLocale::european().parse("1.234,56"), Some(1234.56));
+        assert_eq!(NumberLocale::european().parse("1 234,56"), Some(1234.56));
+        assert_eq!(NumberLocale::french().parse("1\u{202f}234,56"), Some(1234.56));
+        assert_eq!(NumberLocale::swiss().parse("1'234.56"), Some(1234.56));
+        assert_eq!(NumberLocale::plain().parse("1234.56"), Some(1234.56));
+        assert_eq!(NumberLocale::plain().parse("1,234.56"), None);
+    }
+
+    #[test]
+    fn validates_grouping() {
+        let us = NumberLocale::us();
+        assert_eq!(us.parse("1,234,567"), Some(1234567.0));
+        assert_eq!(us.parse("1234"), Some(1234.0));
+        assert_eq!(us.parse("12,34"), None);
+        assert_eq!(us.parse("1,2345"), None);
+        assert_eq!(us.parse("1,234,56.7"), None);
+        assert_eq!(us.parse(",123"), None);
+        assert_eq!(us.parse("1,,234"), None);
+        assert_eq!(us.parse("1.2.3"), None);
+        assert_eq!(us.parse(""), None);
+        assert_eq!(us.parse("abc"), None);
+    }
+
+    #[test]
+    fn handles_signs_and_currency() {
+        let eu = NumberLocale::european();
+        assert_eq!(eu.parse("-1,5"), Some(-1.5));
+        assert_eq!(eu.parse("1,5-"), Some(-1.5));
+        assert_eq!(eu.parse("+1,5"), Some(1.5));
+        assert_eq!(eu.parse("\u{2212}1,5"), Some(-1.5));
+        assert_eq!(eu.parse("€ -1.234,50"), Some(-1234.5));
+        assert_eq!(eu.parse("-1.234,50 EUR"), Some(-1234.5));
+        assert_eq!(eu.parse("--1"), None);
+        assert_eq!(eu.parse("-1-"), None);
+        assert_eq!(eu.parse("1+"), None);
+    }
+
+    #[test]
+    fn parses_locale_names() {
+        assert_eq!("EU".parse::<NumberLocale>(), Ok(NumberLocale::european()));
+        assert_eq!("ch".parse::<NumberLocale>(), Ok(NumberLocale::swiss()));
+        assert!("xx".parse::<NumberLocale>().unwrap_err().starts_with("Unknown number locale 'xx'"));
+    }
+
+    #[test]
+    fn rounds_to_multiples_of_round_to() {
+        assert_eq!(canonical_number(1.004, Some(0.01)), canonical_number(0.996, Some(0.01)));
+        assert_ne!(canonical_number(1.004, Some(0.01)), canonical_number(1.006, Some(0.01)));
+        assert_eq!(canonical_number(-0.001, Some(0.01)), "0");
+        assert_ne!(canonical_number(1.0, None), canonical_number(1.000000001, None));
+        assert_eq!(canonical_number(1234.5, None), "1234.5");
+    }
+}
@@ -0,0 +1,275 @@
+use std::error::Error;
+use std::collections::{HashMap, HashSet};
+use serde::Serialize;
+use crate::csv_compare::{filter_columns, key_indices, output_path, record_key};
+use crate::ragged::{read_regular_record, RaggedPolicy, Rejects};
+use crate::dialect::Dialect;
+
+/// Options controlling an N-way comparison.
+#[derive(Debug, Clone, Default)]
+pub struct NWayOptions {
+    pub ignore_columns: HashSet<String>, // Column names to ignore when rows are compared as a whole
+    pub key_columns: Vec<String>,        // Match rows by these columns instead of the whole row
+    pub labels: Vec<String>,             // Column label per file in the matrix (default: the file path)
+    pub ragged: RaggedPolicy,            // How records with the wrong number of fields are handled
+    pub output: String,                  // Path of the presence matrix CSV file
+    pub dialect: Dialect,                // Delimiter and quoting of the input and output files
+}
+
+/// Per-file counts from an N-way comparison.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct NWayFileSummary {
+    pub label: String,  // Label of the file in the matrix
+    pub rows: u64,      // Data rows read from the file
+    pub distinct: u64,  // Distinct rows (or keys) in the file
+    pub missing: u64,   // Rows (or keys) found in another file but not in this one
+}
+
+/// Summary of an N-way comparison.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct NWayReport {
+    pub matrix_file: String,           // Path of the presence matrix CSV file
+    pub summary_file: String,          // Path of the per-file summary CSV file
+    pub missing_file: String,          // Path of the CSV file listing the incomplete rows
+    pub distinct_rows: u64,            // Distinct rows (or keys) across all files
+    pub incomplete_rows: u64,          // Rows (or keys) missing from at least one file
+    pub files: Vec<NWayFileSummary>,   // Counts for each input file
+}
+
+/// Compares any number of CSV files and records which files contain each row.
+///
+/// Rows are compared as a whole after removing `options.ignore_columns` (with the same
+/// semantics as the two-file comparison), or by `options.key_columns` if any are given.
+/// The presence matrix lists every distinct row (or key) once, followed by a column per
+/// file holding how many times it occurs there (`0` when missing) and the number of files
+/// containing it. A per-file summary is written next to it as `<output>_summary.csv`, and
+/// the rows (or keys) missing from at least one file as `<output>_missing.csv`, each with
+/// the labels of the files it is missing from. All three are written in `options.dialect`.
+///
+/// # Arguments
+///
+/// * `files` - The paths of the CSV files to compare (at least two).
+/// * `options` - The comparison options.
+///
+/// # Returns
+///
+/// An `NWayReport` with the per-file counts, or an error if the files cannot be read or
+/// written, their compared columns differ, or a file label (or `files_present` or
+/// `missing_from`) has the name of a compared column or another label.
+pub fn compare_many(files: &[String], options: &NWayOptions) -> Result<NWayReport, Box<dyn Error>> {
+    if files.len() < 2 {
+        return Err("At least two files are required for an N-way comparison".into());
+    }
+    if !options.labels.is_empty() && options.labels.len() != files.len() {
+        return Err(format!("Expected {} labels but got {}", files.len(), options.labels.len()).into());
+    }
+
+    let mut rejects = Rejects::new(output_path(&options.output, "rejects"));
+    let mut columns: Option<(String, Vec<String>)> = None; // Compared columns of the first file
+    let mut order: Vec<Vec<String>> = Vec::new();          // Distinct rows in first-seen order
+    let mut counts: HashMap<Vec<String>, Vec<u64>> = HashMap::new();
+    let mut summaries = Vec::with_capacity(files.len());
+
+    for (n, file) in files.iter().enumerate() {
+        let mut reader = options.dialect.reader_builder().flexible(options.ragged.is_flexible()).from_path(file)?;
+        let headers = reader.headers()?.clone();
+
+        // Work out which columns identify a row in this file.
+        let indices = if options.key_columns.is_empty() {
+            None
+        } else {
+            Some(key_indices(&headers, &options.key_columns).map_err(|e| format!("{} in '{}'", e, file))?)
+        };
+        let compared: Vec<String> = match &indices {
+            Some(_) => options.key_columns.clone(),
+            None => headers.iter().filter(|h| !options.ignore_columns.contains(*h)).map(|h| h.to_string()).collect(),
+        };
+        match &columns {
+            Some((first, expected)) if *expected != compared => {
+                return Err(format!("Compared columns of '{}' differ from '{}'", file, first).into());
+            }
+            Some(_) => {}
+            None => columns = Some((file.clone(), compared)),
+        }
+
+        let mut summary = NWayFileSummary {
+            label: options.labels.get(n).cloned().unwrap_or_else(|| file.clone()),
+            ..NWayFileSummary::default()
+        };
+        while let Some(record) = read_regular_record(&mut reader, file, headers.len(), options.ragged, "", &mut rejects)? {
+            summary.rows += 1;
+            let row = match &indices {
+                Some(indices) => record_key(&record, indices),
+                None => filter_columns(&record, &headers, &options.ignore_columns, None),
+            };
+            let entry = counts.entry(row).or_insert_with_key(|row| {
+                order.push(row.clone());
+                vec![0; files.len()]
+            });
+            if entry[n] == 0 {
+                summary.distinct += 1;
+            }
+            entry[n] += 1;
+        }
+        summaries.push(summary);
+    }
+    rejects.flush()?;
+
+    // Every column of the matrix must have its own name.
+    let mut header: Vec<String> = columns.map(|(_, c)| c).unwrap_or_default();
+    let mut names: HashSet<&str> = header.iter().map(String::as_str).collect();
+    for added in summaries.iter().map(|s| s.label.as_str()).chain(["files_present", "missing_from"]) {
+        if !names.insert(added) {
+            return Err(format!("Column '{}' of the presence matrix would appear twice; choose other labels", added).into());
+        }
+    }
+
+    // Write the presence matrix: the compared columns, one count per file, then the total.
+    // The missing rows file repeats the matrix lines of the incomplete rows.
+    let missing_file = output_path(&options.output, "missing");
+    let mut writer = options.dialect.writer_builder().from_path(&options.output)?;
+    let mut missing_writer = options.dialect.writer_builder().from_path(&missing_file)?;
+    header.extend(summaries.iter().map(|s| s.label.clone()));
+    header.push("files_present".to_string());
+    writer.write_record(&header)?;
+    header.push("missing_from".to_string());
+    missing_writer.write_record(&header)?;
+
+    let mut incomplete_rows = 0;
+    for row in &order {
+        let row_counts = &counts[row];
+        let present = row_counts.iter().filter(|&&c| c > 0).count();
+        let mut line = row.clone();
+        line.extend(row_counts.iter().map(|c| c.to_string()));
+        line.push(present.to_string());
+        writer.write_record(&line)?;
+
+        if present < files.len() {
+            incomplete_rows += 1;
+            let mut missing_from = Vec::new();
+            for (summary, &count) in summaries.iter_mut().zip(row_counts) {
+                if count == 0 {
+                    summary.missing += 1;
+                    missing_from.push(summary.label.as_str());
+                }
+            }
+            line.push(missing_from.join("|"));
+            missing_writer.write_record(&line)?;
+        }
+    }
+    writer.flush()?;
+    missing_writer.flush()?;
+
+    // Write the per-file summary.
+    let summary_file = output_path(&options.output, "summary");
+    let mut summary_writer = options.dialect.writer_builder().from_path(&summary_file)?;
+    summary_writer.write_record(["file", "rows", "distinct", "missing"])?;
+    for s in &summaries {
+        summary_writer.write_record([s.label.clone(), s.rows.to_string(), s.distinct.to_string(), s.missing.to_string()])?;
+    }
+    summary_writer.flush()?;
+
+    Ok(NWayReport {
+        matrix_file: options.output.clone(),
+        summary_file,
+        missing_file,
+        distinct_rows: order.len() as u64,
+        incomplete_rows,
+        files: summaries,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    /// Writes the three environments and returns their paths.
+    fn files(dir: &TempDir) -> Vec<String> {
+        vec![
+            dir.write("dev.csv", "id,name,ts\n1,a,10\n2,b,11\n2,b,12\n"),
+            dir.write("qa.csv", "id,name,ts\n1,a,20\n3,c,21\n"),
+            dir.write("prod.csv", "id,name,ts\n1,a,30\n2,b,31\n"),
+        ]
+    }
+
+    fn options(dir: &TempDir) -> NWayOptions {
+        NWayOptions {
+            ignore_columns: HashSet::from(["ts".to_string()]),
+            labels: vec!["dev".to_string(), "qa".to_string(), "prod".to_string()],
+            output: dir.path("matrix.csv"),
+            ..NWayOptions::default()
+        }
+    }
+
+    #[test]
+    fn writes_the_presence_matrix_and_missing_rows() {
+        let dir = TempDir::new();
+        let report = compare_many(&files(&dir), &options(&dir)).unwrap();
+
+        assert_eq!(
+            dir.read("matrix.csv"),
+            "id,name,dev,qa,prod,files_present\n1,a,1,1,1,3\n2,b,2,0,1,2\n3,c,0,1,0,1\n"
+        );
+        assert_eq!(
+            dir.read("matrix_missing.csv"),
+            "id,name,dev,qa,prod,files_present,missing_from\n2,b,2,0,1,2,qa\n3,c,0,1,0,1,dev|prod\n"
+        );
+        assert_eq!(report.missing_file, dir.path("matrix_missing.csv"));
+        assert_eq!((report.distinct_rows, report.incomplete_rows), (3, 2));
+
+        let counts: Vec<(u64, u64, u64)> = report.files.iter().map(|f| (f.rows, f.distinct, f.missing)).collect();
+        assert_eq!(counts, [(3, 2, 1), (2, 2, 1), (2, 2, 1)]);
+        assert_eq!(
+            dir.read("matrix_summary.csv"),
+            "file,rows,distinct,missing\ndev,3,2,1\nqa,2,2,1\nprod,2,2,1\n"
+        );
+    }
+
+    #[test]
+    fn writes_every_output_in_the_dialect() {
+        let dir = TempDir::new();
+        let files = vec![dir.write("a.csv", "id;name\n1;x,y\n2;z\n"), dir.write("b.csv", "id;name\n1;x,y\n")];
+        let options = NWayOptions {
+            labels: vec!["a".to_string(), "b".to_string()],
+            output: dir.path("matrix.csv"),
+            dialect: Dialect { delimiter: ';', ..Dialect::default() },
+            ..NWayOptions::default()
+        };
+
+        compare_many(&files, &options).unwrap();
+        assert_eq!(dir.read("matrix.csv"), "id;name;a;b;files_present\n1;x,y;1;1;2\n2;z;1;0;1\n");
+        assert_eq!(dir.read("matrix_missing.csv"), "id;name;a;b;files_present;missing_from\n2;z;1;0;1;b\n");
+        assert_eq!(dir.read("matrix_summary.csv"), "file;rows;distinct;missing\na;2;2;0\nb;1;1;1\n");
+    }
+
+    #[test]
+    fn matches_rows_by_key() {
+        let dir = TempDir::new();
+        let options = NWayOptions { key_columns: vec!["id".to_string()], ..options(&dir) };
+        let report = compare_many(&files(&dir), &options).unwrap();
+        assert_eq!(dir.read("matrix.csv"), "id,dev,qa,prod,files_present\n1,1,1,1,3\n2,2,0,1,2\n3,0,1,0,1\n");
+        assert_eq!(report.incomplete_rows, 2);
+    }
+
+    #[test]
+    fn rejects_invalid_inputs() {
+        let dir = TempDir::new();
+        let files = files(&dir);
+        let error = compare_many(&files[..1], &options(&dir)).unwrap_err();
+        assert!(error.to_string().starts_with("At least two files"));
+
+        let options = NWayOptions { labels: vec!["dev".to_string()], ..options(&dir) };
+        let error = compare_many(&files, &options).unwrap_err();
+        assert_eq!(error.to_string(), "Expected 3 labels but got 1");
+
+        let error = compare_many(&files, &NWayOptions { labels: vec!["dev".to_string(), "name".to_string(), "prod".to_string()], ..options.clone() }).unwrap_err();
+        assert_eq!(error.to_string(), "Column 'name' of the presence matrix would appear twice; choose other labels");
+        let error = compare_many(&files, &NWayOptions { labels: vec!["dev".to_string(), "dev".to_string(), "files_present".to_string()], ..options.clone() }).unwrap_err();
+        assert!(error.to_string().starts_with("Column 'dev' "), "{}", error);
+
+        let other = dir.write("other.csv", "id,title\n1,a\n");
+        let error = compare_many(&[files[0].clone(), other], &NWayOptions { labels: Vec::new(), ..options }).unwrap_err();
+        assert!(error.to_string().starts_with("Compared columns of"), "{}", error);
+    }
+}
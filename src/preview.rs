@@ -0,0 +1,128 @@
+use std::error::Error;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use serde::Serialize;
+use crate::dialect::Dialect;
+use crate::encoding::{detect_encoding_of, reject_utf32, Bom, Encoding, InputEncoding};
+
+/// Number of bytes read from the start of a file to build its preview.
+pub const SAMPLE_BYTES: usize = 64 * 1024;
+
+/// A quick look at the beginning of a CSV file.
+#[derive(Debug, Clone, Serialize)]
+pub struct FilePreview {
+    pub encoding: String,        // Encoding the sample was read as
+    pub detected: bool,          // `true` if the encoding was detected rather than chosen
+    pub confidence: f64,         // Confidence of the encoding detection (0.0 to 1.0, 1.0 when chosen)
+    pub dialect: Dialect,        // Dialect guessed from the sample
+    pub headers: Vec<String>,    // Header row
+    pub rows: Vec<Vec<String>>,  // The first data rows
+    pub file_size: u64,          // Size of the file in bytes
+    pub estimated_rows: u64,     // Number of data rows, extrapolated from the sample if needed
+    pub exact_count: bool,       // `true` if the whole file fit in the sample, so the count is exact
+}
+
+/// Reads the beginning of a CSV file to preview its content and format.
+///
+/// Up to `SAMPLE_BYTES` bytes are read, and files in UTF-32 are rejected. The sample is decoded from `encoding`, or from
+/// the encoding detected in it for `InputEncoding::Auto` (a byte-order mark takes
+/// precedence either way), and bytes that cannot be decoded are shown as replacement
+/// characters. The dialect is guessed from the decoded text, which is then parsed for the
+/// headers and the first `max_rows` rows. For files larger than the sample, the row count
+/// is extrapolated from the average row size.
+///
+/// # Arguments
+///
+/// * `path` - The path to the CSV file.
+/// * `max_rows` - The maximum number of data rows to return.
+/// * `encoding` - The encoding the file is read with, or `Auto` to detect it.
+///
+/// # Returns
+///
+/// The `FilePreview`, or an error if the file cannot be read.
+pub fn preview_file(path: &str, max_rows: usize, encoding: InputEncoding) -> Result<FilePreview, Box<dyn Error>> {
+    let file = File::open(path)?;
+    let file_size = file.metadata()?.len();
+    let mut sample = Vec::with_capacity(SAMPLE_BYTES);
+    file.take(SAMPLE_BYTES as u64).read_to_end(&mut sample)?;
+    reject_utf32(Path::new(path), &sample)?;
+
+    // Decode the sample from the chosen or detected encoding (a byte-order mark takes
+    // precedence), falling back to UTF-8 for detected encodings that cannot be read.
+    let detected = encoding == InputEncoding::Auto;
+    let (decoder, encoding, confidence) = match encoding {
+        InputEncoding::Fixed(encoding) => (encoding, encoding.label().to_string(), 1.0),
+        InputEncoding::Auto => {
+            let (label, confidence) = detect_encoding_of(&sample);
+            (Encoding::from_detected(&label).unwrap_or_default(), label, confidence)
+        }
+    };
+    let decoded = if Bom::detect(&sample).is_some() {
+        decoder.codec().decode(&sample).0
+    } else {
+        decoder.decode(&sample).0
+    };
+
+    // Only parse complete lines when the sample stops in the middle of the file.
+    let exact_count = sample.len() as u64 >= file_size;
+    let mut text: &str = &decoded;
+    if !exact_count {
+        if let Some(end) = text.rfind('\n') {
+            text = &text[..end + 1];
+        }
+    }
+    // Bytes of the sample covered by the parsed lines, for extrapolating the row count.
+    let sample_bytes = if decoded.is_empty() {
+        0.0
+    } else {
+        sample.len() as f64 * text.len() as f64 / decoded.len() as f64
+    };
+
+    let dialect = Dialect::sniff(text);
+    let mut reader = dialect.reader_builder().flexible(true).from_reader(text.as_bytes());
+    let headers = reader.headers()?.iter().map(|h| h.to_string()).collect();
+
+    let mut rows = Vec::new();
+    let mut count: u64 = 0;
+    for record in reader.records() {
+        let record = record?;
+        if rows.len() < max_rows {
+            rows.push(record.iter().map(|f| f.to_string()).collect());
+        }
+        count += 1;
+    }
+
+    let estimated_rows = if exact_count || sample_bytes == 0.0 {
+        count
+    } else {
+        (count as f64 * file_size as f64 / sample_bytes).round() as u64
+    };
+
+    Ok(FilePreview { encoding, detected, confidence, dialect, headers, rows, file_size, estimated_rows, exact_count })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    #[test]
+    fn reads_the_sample_in_the_chosen_encoding() {
+        let dir = TempDir::new();
+        let path = dir.write("latin1.csv", b"id;name\n1;caf\xe9\n2;na\xefve\n");
+
+        let preview = preview_file(&path, 1, InputEncoding::Fixed(Encoding::Windows1252)).unwrap();
+        assert_eq!((preview.encoding.as_str(), preview.detected, preview.confidence), (Encoding::Windows1252.label(), false, 1.0));
+        assert_eq!(preview.dialect.delimiter, ';');
+        assert_eq!(preview.headers, ["id", "name"]);
+        assert_eq!(preview.rows, [["1", "café"]]);
+        assert_eq!((preview.estimated_rows, preview.exact_count), (2, true));
+
+        let preview = preview_file(&path, 1, InputEncoding::Fixed(Encoding::Utf8)).unwrap();
+        assert_eq!(preview.rows, [["1", "caf\u{fffd}"]]);
+
+        let preview = preview_file(&path, 1, InputEncoding::Auto).unwrap();
+        assert!(preview.detected);
+    }
+}
@@ -0,0 +1,131 @@
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Read};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+/// Progress counters and a cancellation flag shared with a running operation.
+///
+/// The caller keeps an `Arc<Progress>`, passes a clone in the operation's options and
+/// reads the counters from another thread (e.g. to draw a progress bar) while the
+/// operation updates them. Calling `cancel` makes the operation stop at the next record
+/// and return a `Cancelled` error.
+#[derive(Debug, Default)]
+pub struct Progress {
+    rows: AtomicU64,         // Records read so far, across all input files
+    bytes: AtomicU64,        // Bytes read so far, across all input files
+    total_bytes: AtomicU64,  // Combined size of the input files, if known
+    cancelled: AtomicBool,   // Set when the caller asked the operation to stop
+}
+
+impl Progress {
+    /// Creates counters for a new operation.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of records read so far.
+    pub fn rows(&self) -> u64 {
+        self.rows.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of bytes read so far.
+    pub fn bytes_read(&self) -> u64 {
+        self.bytes.load(Ordering::Relaxed)
+    }
+
+    /// Returns the combined size of the input files, or 0 if not known yet.
+    pub fn total_bytes(&self) -> u64 {
+        self.total_bytes.load(Ordering::Relaxed)
+    }
+
+    /// Returns the share of the input read so far (0.0 to 1.0), if the total size is known.
+    pub fn fraction(&self) -> Option<f32> {
+        match self.total_bytes() {
+            0 => None,
+            total => Some((self.bytes_read() as f64 / total as f64).min(1.0) as f32),
+        }
+    }
+
+    /// Asks the operation to stop as soon as possible.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// Returns `true` if `cancel` was called.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Resets the counters at the start of an operation reading `total_bytes` bytes.
+    ///
+    /// The cancellation flag is kept, so a cancelled batch does not restart.
+    pub(crate) fn start(&self, total_bytes: u64) {
+        self.rows.store(0, Ordering::Relaxed);
+        self.bytes.store(0, Ordering::Relaxed);
+        self.total_bytes.store(total_bytes, Ordering::Relaxed);
+    }
+}
+
+/// Error returned by an operation stopped through `Progress::cancel`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cancelled;
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Operation cancelled")
+    }
+}
+
+impl Error for Cancelled {}
+
+/// Reports the records read from one file to an optional `Progress`.
+///
+/// Bytes are counted separately, by reading the file through a `CountingReader`.
+pub(crate) struct FileProgress<'a> {
+    progress: Option<&'a Progress>, // Counters to update, if the caller asked for them
+}
+
+impl<'a> FileProgress<'a> {
+    /// Starts tracking a file read from its beginning.
+    pub(crate) fn new(progress: Option<&'a Progress>) -> Self {
+        Self { progress }
+    }
+
+    /// Counts one record, or returns `Cancelled` if the operation should stop.
+    pub(crate) fn record(&self) -> Result<(), Cancelled> {
+        let Some(progress) = self.progress else {
+            return Ok(());
+        };
+        if progress.is_cancelled() {
+            return Err(Cancelled);
+        }
+        progress.rows.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+/// Adds the bytes read from an input file to an optional `Progress`.
+///
+/// The counted bytes are those on disk, before any decoding, so they add up to the
+/// file sizes passed to `Progress::start` whatever the encoding of the file.
+pub(crate) struct CountingReader<'a, R> {
+    inner: R,                       // The file being read
+    progress: Option<&'a Progress>, // Counters to update, if the caller asked for them
+}
+
+impl<'a, R: Read> CountingReader<'a, R> {
+    /// Wraps `inner`, counting its bytes into `progress`.
+    pub(crate) fn new(inner: R, progress: Option<&'a Progress>) -> Self {
+        Self { inner, progress }
+    }
+}
+
+impl<R: Read> Read for CountingReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        if let Some(progress) = self.progress {
+            progress.bytes.fetch_add(n as u64, Ordering::Relaxed);
+        }
+        Ok(n)
+    }
+}
@@ -0,0 +1,278 @@
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::Read;
+use std::str::FromStr;
+use csv::{ErrorKind, Reader, StringRecord, Writer, WriterBuilder};
+
+/// How records whose field count differs from the header are handled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RaggedPolicy {
+    /// Abort the run on the first irregular record (the `csv` crate's default).
+    #[default]
+    Strict,
+    /// Skip irregular or malformed records and list them in the rejects file.
+    Reject,
+    /// Pad short records with empty fields (null ones when missing fields are null); reject long ones.
+    Pad,
+    /// Drop the extra fields of long records; reject short ones.
+    Truncate,
+    /// Pad short records and truncate long ones.
+    PadAndTruncate,
+}
+
+impl RaggedPolicy {
+    /// Returns `true` if the reader must accept records of any length.
+    pub fn is_flexible(self) -> bool {
+        self != RaggedPolicy::Strict
+    }
+}
+
+impl FromStr for RaggedPolicy {
+    type Err = String;
+
+    /// Parses a policy name: `strict`, `reject`, `pad`, `truncate` or `pad-truncate`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "strict" => Ok(RaggedPolicy::Strict),
+            "reject" => Ok(RaggedPolicy::Reject),
+            "pad" => Ok(RaggedPolicy::Pad),
+            "truncate" => Ok(RaggedPolicy::Truncate),
+            "pad-truncate" | "pad_truncate" | "both" => Ok(RaggedPolicy::PadAndTruncate),
+            other => Err(format!("Unknown ragged policy '{}'. Use strict, reject, pad, truncate or pad-truncate.", other)),
+        }
+    }
+}
+
+/// Writes rejected and irregular records, with their line numbers, to a CSV file.
+///
+/// The file is only created once the first record is written.
+pub struct Rejects {
+    path: Option<String>,         // Path of the rejects file, or `None` to only count records
+    writer: Option<Writer<File>>, // Opened lazily on the first reject
+    pub rejected: u64,            // Records skipped from the comparison
+    pub repaired: u64,            // Records padded or truncated and then compared
+}
+
+impl Rejects {
+    /// Creates a rejects sink that will write to `path` if needed.
+    pub fn new(path: String) -> Self {
+        Self { path: Some(path), writer: None, rejected: 0, repaired: 0 }
+    }
+
+    /// Creates a sink that counts rejected and repaired records without writing them.
+    pub fn discard() -> Self {
+        Self { path: None, writer: None, rejected: 0, repaired: 0 }
+    }
+
+    /// Returns the path of the rejects file if anything was written to it.
+    pub fn path(&self) -> Option<&str> {
+        self.writer.as_ref().and(self.path.as_deref())
+    }
+
+    /// Appends one entry to the rejects file.
+    fn write(&mut self, file: &str, line: u64, action: &str, reason: &str, fields: &[&str]) -> Result<(), Box<dyn Error>> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if self.writer.is_none() {
+            let mut writer = WriterBuilder::new().flexible(true).from_path(path)?;
+            writer.write_record(["file", "line", "action", "reason", "fields"])?;
+            self.writer = Some(writer);
+        }
+        let writer = self.writer.as_mut().expect("rejects writer is open");
+        let line = line.to_string();
+        let mut entry = vec![file, line.as_str(), action, reason];
+        entry.extend_from_slice(fields);
+        writer.write_record(&entry)?;
+        Ok(())
+    }
+
+    /// Closes and deletes the rejects file, if one was created, e.g. when the run is cancelled.
+    pub fn remove(&mut self) {
+        if self.writer.take().is_some() {
+            if let Some(path) = &self.path {
+                let _ = fs::remove_file(path);
+            }
+        }
+    }
+
+    /// Flushes the rejects file, if one was created.
+    pub fn flush(&mut self) -> Result<(), Box<dyn Error>> {
+        if let Some(writer) = self.writer.as_mut() {
+            writer.flush()?;
+        }
+        Ok(())
+    }
+}
+
+/// Reads the next record that can take part in the comparison.
+///
+/// Records with the wrong number of fields are padded, truncated or rejected according
+/// to `policy`, and records that cannot be decoded (e.g. invalid UTF-8) are rejected
+/// unless the policy is `Strict`. Every rejected or repaired record is written to `rejects`.
+///
+/// Under `Strict`, short records only get this far if the reader is flexible because
+/// missing fields are treated as null; they are returned as they are. Long records are
+/// still an error.
+///
+/// # Arguments
+///
+/// * `reader` - The CSV reader, built with `flexible(policy.is_flexible())` or flexible
+///   because missing fields are treated as null.
+/// * `file` - The path of the file being read, used in the rejects file.
+/// * `expected` - The number of fields in the header.
+/// * `policy` - How to handle irregular records.
+/// * `pad` - The value of the fields added to short records by the padding policies.
+/// * `rejects` - Where to record rejected and repaired records.
+///
+/// # Returns
+///
+/// `Ok(Some(record))` for the next usable record, `Ok(None)` at the end of the file,
+/// or an error if the file cannot be read (or a record is irregular under `Strict`).
+pub fn read_regular_record<R: Read>(
+    reader: &mut Reader<R>,
+    file: &str,
+    expected: usize,
+    policy: RaggedPolicy,
+    pad: &str,
+    rejects: &mut Rejects,
+) -> Result<Option<StringRecord>, Box<dyn Error>> {
+    loop {
+        let mut record = StringRecord::new();
+        match reader.read_record(&mut record) {
+            Ok(false) => return Ok(None),
+            Ok(true) => {}
+            Err(e) if policy.is_flexible() && matches!(e.kind(), ErrorKind::Utf8 { .. }) => {
+                // The record is malformed but the reader can carry on with the next one.
+                let line = e.position().map_or(0, |p| p.line());
+                rejects.write(file, line, "rejected", &e.to_string(), &[])?;
+                rejects.rejected += 1;
+                continue;
+            }
+            Err(e) => return Err(e.into()),
+        }
+
+        let line = record.position().map_or(0, |p| p.line());
+        let len = record.len();
+        if len == expected || (policy == RaggedPolicy::Strict && len < expected) {
+            return Ok(Some(record));
+        }
+        if policy == RaggedPolicy::Strict {
+            return Err(format!("{}: line {}: expected {} fields, found {}", file, line, expected, len).into());
+        }
+
+        let fields: Vec<&str> = record.iter().collect();
+        let reason = format!("expected {} fields, found {}", expected, len);
+        let repaired = match policy {
+            RaggedPolicy::Pad | RaggedPolicy::PadAndTruncate if len < expected => {
+                let mut padded = record.clone();
+                for _ in len..expected {
+                    padded.push_field(pad);
+                }
+                Some(("padded", padded))
+            }
+            RaggedPolicy::Truncate | RaggedPolicy::PadAndTruncate if len > expected => {
+                let mut truncated = record.clone();
+                truncated.truncate(expected);
+                Some(("truncated", truncated))
+            }
+            _ => None,
+        };
+
+        match repaired {
+            Some((action, fixed)) => {
+                rejects.write(file, line, action, &reason, &fields)?;
+                rejects.repaired += 1;
+                return Ok(Some(fixed));
+            }
+            None => {
+                rejects.write(file, line, "rejected", &reason, &fields)?;
+                rejects.rejected += 1;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use csv::ReaderBuilder;
+
+    const DATA: &str = "a,b,c\n1,2,3\n4,5\n6,7,8,9\n10,11,12\n";
+
+    /// Reads every usable record of `DATA` under `policy`.
+    fn read_all(policy: RaggedPolicy, flexible: bool) -> (Result<Vec<Vec<String>>, String>, Rejects) {
+        let mut reader = ReaderBuilder::new().flexible(flexible).from_reader(DATA.as_bytes());
+        let mut rejects = Rejects::discard();
+        let mut records = Vec::new();
+        loop {
+            match read_regular_record(&mut reader, "data.csv", 3, policy, "", &mut rejects) {
+                Ok(Some(record)) => records.push(record.iter().map(str::to_string).collect()),
+                Ok(None) => return (Ok(records), rejects),
+                Err(e) => return (Err(e.to_string()), rejects),
+            }
+        }
+    }
+
+    fn rows(rows: &[&[&str]]) -> Vec<Vec<String>> {
+        rows.iter().map(|row| row.iter().map(|f| f.to_string()).collect()).collect()
+    }
+
+    #[test]
+    fn strict_stops_at_the_first_irregular_record() {
+        let (result, _) = read_all(RaggedPolicy::Strict, false);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn strict_with_missing_as_null_accepts_only_short_records() {
+        let (result, rejects) = read_all(RaggedPolicy::Strict, true);
+        let error = result.unwrap_err();
+        assert!(error.contains("line 4: expected 3 fields, found 4"), "{}", error);
+        assert_eq!((rejects.rejected, rejects.repaired), (0, 0));
+    }
+
+    #[test]
+    fn reject_skips_irregular_records() {
+        let (result, rejects) = read_all(RaggedPolicy::Reject, true);
+        assert_eq!(result.unwrap(), rows(&[&["1", "2", "3"], &["10", "11", "12"]]));
+        assert_eq!((rejects.rejected, rejects.repaired), (2, 0));
+    }
+
+    #[test]
+    fn pad_fills_short_records_and_rejects_long_ones() {
+        let (result, rejects) = read_all(RaggedPolicy::Pad, true);
+        assert_eq!(result.unwrap(), rows(&[&["1", "2", "3"], &["4", "5", ""], &["10", "11", "12"]]));
+        assert_eq!((rejects.rejected, rejects.repaired), (1, 1));
+
+        let mut reader = ReaderBuilder::new().flexible(true).from_reader("a,b,c\n4,5\n".as_bytes());
+        let record = read_regular_record(&mut reader, "data.csv", 3, RaggedPolicy::Pad, "?", &mut Rejects::discard()).unwrap();
+        assert_eq!(record.unwrap(), StringRecord::from(vec!["4", "5", "?"]));
+    }
+
+    #[test]
+    fn truncate_cuts_long_records_and_rejects_short_ones() {
+        let (result, rejects) = read_all(RaggedPolicy::Truncate, true);
+        assert_eq!(result.unwrap(), rows(&[&["1", "2", "3"], &["6", "7", "8"], &["10", "11", "12"]]));
+        assert_eq!((rejects.rejected, rejects.repaired), (1, 1));
+    }
+
+    #[test]
+    fn pad_and_truncate_repairs_both() {
+        let (result, rejects) = read_all(RaggedPolicy::PadAndTruncate, true);
+        assert_eq!(
+            result.unwrap(),
+            rows(&[&["1", "2", "3"], &["4", "5", ""], &["6", "7", "8"], &["10", "11", "12"]])
+        );
+        assert_eq!((rejects.rejected, rejects.repaired), (0, 2));
+    }
+
+    #[test]
+    fn parses_policy_names() {
+        assert_eq!("Pad-Truncate".parse(), Ok(RaggedPolicy::PadAndTruncate));
+        assert_eq!("both".parse(), Ok(RaggedPolicy::PadAndTruncate));
+        assert!("lenient".parse::<RaggedPolicy>().is_err());
+        assert!(!RaggedPolicy::Strict.is_flexible());
+        assert!(RaggedPolicy::Reject.is_flexible());
+    }
+}
@@ -0,0 +1,272 @@
+use std::collections::BTreeMap;
+use serde::{Deserialize, Serialize};
+use crate::csv_compare::CompareOptions;
+use crate::dialect::Dialect;
+use crate::encoding::{Encoding, InputEncoding};
+use crate::nulls::NullTokens;
+use crate::number::NumberLocale;
+use crate::ragged::RaggedPolicy;
+use crate::transcode::UnencodablePolicy;
+
+/// A serializable description of comparison options, as written in manifest files
+/// and configuration profiles.
+///
+/// Every field is optional; unset fields keep the defaults of `CompareOptions`.
+/// Names and values follow the `compare_csv` command-line options, e.g.
+/// `ignore_columns = ["updated_at"]`, `encoding = "utf8bom"` or `delimiter = "semicolon"`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CompareSpec {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ignore_columns: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key_columns: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub numeric_columns: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub locale1: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub locale2: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub round_to: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub null_tokens: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub column_nulls: Option<BTreeMap<String, Vec<String>>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub missing_as_null: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ragged: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub delimiter: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quote: Option<char>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub escape: Option<char>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trim: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub encoding: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unencodable: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub input_encoding1: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub input_encoding2: Option<String>,
+}
+
+impl CompareSpec {
+    /// Layers `overrides` on top of this description.
+    ///
+    /// Fields set in `overrides` win; unset fields fall back to `self`. This is how
+    /// command-line flags override a profile, and a project profile overrides a user one.
+    pub fn merged_with(&self, overrides: &CompareSpec) -> CompareSpec {
+        CompareSpec {
+            ignore_columns: overrides.ignore_columns.clone().or_else(|| self.ignore_columns.clone()),
+            key_columns: overrides.key_columns.clone().or_else(|| self.key_columns.clone()),
+            numeric_columns: overrides.numeric_columns.clone().or_else(|| self.numeric_columns.clone()),
+            locale1: overrides.locale1.clone().or_else(|| self.locale1.clone()),
+            locale2: overrides.locale2.clone().or_else(|| self.locale2.clone()),
+            round_to: overrides.round_to.or(self.round_to),
+            null_tokens: overrides.null_tokens.clone().or_else(|| self.null_tokens.clone()),
+            column_nulls: overrides.column_nulls.clone().or_else(|| self.column_nulls.clone()),
+            missing_as_null: overrides.missing_as_null.or(self.missing_as_null),
+            ragged: overrides.ragged.clone().or_else(|| self.ragged.clone()),
+            delimiter: overrides.delimiter.clone().or_else(|| self.delimiter.clone()),
+            quote: overrides.quote.or(self.quote),
+            escape: overrides.escape.or(self.escape),
+            trim: overrides.trim.or(self.trim),
+            encoding: overrides.encoding.clone().or_else(|| self.encoding.clone()),
+            unencodable: overrides.unencodable.clone().or_else(|| self.unencodable.clone()),
+            input_encoding1: overrides.input_encoding1.clone().or_else(|| self.input_encoding1.clone()),
+            input_encoding2: overrides.input_encoding2.clone().or_else(|| self.input_encoding2.clone()),
+        }
+    }
+
+    /// Converts the description into `CompareOptions`.
+    ///
+    /// # Returns
+    ///
+    /// The options, or an error message naming the first invalid value.
+    pub fn to_options(&self) -> Result<CompareOptions, String> {
+        let mut options = CompareOptions::default();
+
+        if let Some(columns) = &self.ignore_columns {
+            options.ignore_columns = columns.iter().cloned().collect();
+        }
+        if let Some(columns) = &self.key_columns {
+            options.key_columns = columns.clone();
+        }
+        if let Some(columns) = &self.numeric_columns {
+            options.numeric_columns = columns.iter().cloned().collect();
+        }
+        if let Some(locale) = &self.locale1 {
+            options.locale1 = locale.parse::<NumberLocale>()?;
+        }
+        if let Some(locale) = &self.locale2 {
+            options.locale2 = locale.parse::<NumberLocale>()?;
+        }
+        options.round_to = self.round_to;
+        if let Some(tokens) = &self.null_tokens {
+            options.null_tokens = NullTokens::from_tokens(tokens.iter().map(|t| t.as_str()));
+        }
+        if let Some(columns) = &self.column_nulls {
+            for (column, tokens) in columns {
+                options.null_tokens.set_column(column, tokens.iter().map(|t| t.as_str()));
+            }
+        }
+        options.missing_as_null = self.missing_as_null.unwrap_or(false);
+        if let Some(ragged) = &self.ragged {
+            options.ragged = ragged.parse::<RaggedPolicy>()?;
+        }
+        if let Some(delimiter) = &self.delimiter {
+            options.dialect = delimiter.parse::<Dialect>()?;
+        }
+        if let Some(quote) = self.quote {
+            options.dialect.quote = quote;
+        }
+        if let Some(escape) = self.escape {
+            options.dialect.escape = Some(escape);
+        }
+        if let Some(trim) = self.trim {
+            options.dialect.trim = trim;
+        }
+        options.dialect.validate()?;
+        if let Some(encoding) = &self.encoding {
+            options.encoding = encoding.parse::<Encoding>()?;
+        }
+        if let Some(policy) = &self.unencodable {
+            options.unencodable = policy.parse::<UnencodablePolicy>()?;
+        }
+        if let Some(encoding) = &self.input_encoding1 {
+            options.input_encoding1 = encoding.parse::<InputEncoding>()?;
+        }
+        if let Some(encoding) = &self.input_encoding2 {
+            options.input_encoding2 = encoding.parse::<InputEncoding>()?;
+        }
+
+        Ok(options)
+    }
+}
+
+/// Splits a comma-separated list, trimming whitespace and dropping empty items.
+///
+/// An item in double quotes is taken literally, so it may contain commas or surrounding
+/// spaces; a quote inside it is written twice, as in CSV.
+pub fn split_list(value: &str) -> Vec<String> {
+    let mut items = Vec::new();
+    let mut start = 0;
+    let mut quoted = false;
+    for (i, c) in value.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            ',' if !quoted => {
+                items.push(&value[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    items.push(&value[start..]);
+
+    items.into_iter()
+        .map(|item| {
+            let item = item.trim();
+            match item.strip_prefix('"').and_then(|s| s.strip_suffix('"')) {
+                Some(inner) => inner.replace("\"\"", "\""),
+                None => item.to_string(),
+            }
+        })
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
+/// Joins items into a comma-separated list that `split_list` reads back unchanged,
+/// quoting the items that contain commas or quotes or have surrounding spaces.
+pub fn join_list(items: &[String]) -> String {
+    items.iter()
+        .map(|item| {
+            if item.contains([',', '"']) || item.trim() != item {
+                format!("\"{}\"", item.replace('"', "\"\""))
+            } else {
+                item.clone()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn list(items: &[&str]) -> Option<Vec<String>> {
+        Some(items.iter().map(|s| s.to_string()).collect())
+    }
+
+    #[test]
+    fn later_layers_win_and_unset_fields_fall_back() {
+        let user = CompareSpec { ignore_columns: list(&["ts"]), delimiter: Some("semicolon".to_string()), trim: Some(true), ..CompareSpec::default() };
+        let project = CompareSpec { ignore_columns: list(&["batch"]), missing_as_null: Some(true), ..CompareSpec::default() };
+        let cli = CompareSpec { missing_as_null: Some(false), round_to: Some(0.5), ..CompareSpec::default() };
+
+        let merged = user.merged_with(&project).merged_with(&cli);
+        assert_eq!(merged.ignore_columns, list(&["batch"]));
+        assert_eq!(merged.delimiter.as_deref(), Some("semicolon"));
+        assert_eq!(merged.trim, Some(true));
+        assert_eq!(merged.missing_as_null, Some(false));
+        assert_eq!(merged.round_to, Some(0.5));
+        assert_eq!(CompareSpec::default().merged_with(&merged), merged);
+    }
+
+    #[test]
+    fn converts_to_compare_options() {
+        let spec = CompareSpec {
+            ignore_columns: list(&["ts"]),
+            key_columns: list(&["id"]),
+            locale1: Some("eu".to_string()),
+            null_tokens: list(&["NULL"]),
+            column_nulls: Some(BTreeMap::from([("status".to_string(), vec!["-".to_string()])])),
+            ragged: Some("pad".to_string()),
+            delimiter: Some("semicolon".to_string()),
+            escape: Some('\\'),
+            encoding: Some("latin1".to_string()),
+            unencodable: Some("replace".to_string()),
+            input_encoding1: Some("windows-1252".to_string()),
+            ..CompareSpec::default()
+        };
+        let options = spec.to_options().unwrap();
+        assert!(options.ignore_columns.contains("ts"));
+        assert_eq!(options.key_columns, ["id"]);
+        assert_eq!(options.locale1, NumberLocale::european());
+        assert!(options.null_tokens.is_null("other", "NULL"));
+        assert!(options.null_tokens.is_null("status", "-"));
+        assert!(!options.null_tokens.is_null("status", "NULL"));
+        assert_eq!(options.ragged, RaggedPolicy::Pad);
+        assert_eq!((options.dialect.delimiter, options.dialect.escape), (';', Some('\\')));
+        assert_eq!(options.encoding, Encoding::Latin1);
+        assert_eq!(options.unencodable, UnencodablePolicy::Replace);
+        assert_eq!(options.input_encoding1, InputEncoding::Fixed(Encoding::Windows1252));
+        assert_eq!(options.input_encoding2, InputEncoding::default());
+        assert!(!options.missing_as_null);
+    }
+
+    #[test]
+    fn reports_the_first_invalid_value() {
+        let spec = CompareSpec { ragged: Some("lenient".to_string()), ..CompareSpec::default() };
+        assert!(spec.to_options().unwrap_err().starts_with("Unknown ragged policy 'lenient'"));
+        let spec = CompareSpec { locale2: Some("xx".to_string()), ..CompareSpec::default() };
+        assert!(spec.to_options().unwrap_err().starts_with("Unknown number locale 'xx'"));
+        let spec = CompareSpec { encoding: Some("latin9".to_string()), ..CompareSpec::default() };
+        assert!(spec.to_options().unwrap_err().starts_with("Invalid encoding 'latin9'"));
+    }
+
+    #[test]
+    fn lists_split_and_join_with_quotes() {
+        assert_eq!(split_list(" a, ,b ,"), ["a", "b"]);
+        assert_eq!(split_list(r#""x, y", "say ""hi""", z"#), ["x, y", r#"say "hi""#, "z"]);
+        let items = vec!["plain".to_string(), "a,b".to_string(), "q\"".to_string(), " s".to_string()];
+        assert_eq!(join_list(&items), r#"plain, "a,b", "q""", " s""#);
+        assert_eq!(split_list(&join_list(&items)), items);
+    }
+}
@@ -0,0 +1,49 @@
+//! Helpers shared by the unit tests.
+
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Number of temporary directories created so far by this test process.
+static CREATED: AtomicUsize = AtomicUsize::new(0);
+
+/// A directory under the system temporary directory, removed again when dropped.
+pub struct TempDir {
+    root: PathBuf, // Absolute path of the directory
+}
+
+impl TempDir {
+    /// Creates a new, empty directory unique to this process and call.
+    pub fn new() -> Self {
+        let name = format!("rust_csv_scripts_{}_{}", std::process::id(), CREATED.fetch_add(1, Ordering::Relaxed));
+        let root = std::env::temp_dir().join(name);
+        fs::create_dir_all(&root).expect("temporary directory can be created");
+        Self { root }
+    }
+
+    /// Returns the path of `name` inside the directory, as the library functions take it.
+    pub fn path(&self, name: &str) -> String {
+        self.root.join(name).to_string_lossy().into_owned()
+    }
+
+    /// Writes `contents` to `name` (creating parent directories) and returns its path.
+    pub fn write(&self, name: &str, contents: impl AsRef<[u8]>) -> String {
+        let path = self.path(name);
+        if let Some(parent) = PathBuf::from(&path).parent() {
+            fs::create_dir_all(parent).expect("parent directory can be created");
+        }
+        fs::write(&path, contents).expect("test file can be written");
+        path
+    }
+
+    /// Reads `name` back as text.
+    pub fn read(&self, name: &str) -> String {
+        fs::read_to_string(self.path(name)).expect("test file can be read")
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.root);
+    }
+}
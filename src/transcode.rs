@@ -0,0 +1,403 @@
+use std::borrow::Cow;
+use std::fmt;
+use std::io::{self, Write};
+use std::str::FromStr;
+use csv::StringRecord;
+use encoding_rs::EncoderResult;
+use serde::{Deserialize, Serialize};
+use crate::encoding::Encoding;
+
+/// What happens to characters the output encoding cannot represent (e.g. `€` in
+/// ISO-8859-1 or `ł` in Windows-1252).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UnencodablePolicy {
+    /// Stop the run at the first cell that cannot be written.
+    #[default]
+    Error,
+    /// Write `?` in place of each character that cannot be written.
+    Replace,
+    /// Write an ASCII approximation (`ł` as `l`, `€` as `EUR`), or `?` if there is none.
+    Transliterate,
+}
+
+impl UnencodablePolicy {
+    /// Every policy, in the order menus list them.
+    pub const ALL: [UnencodablePolicy; 3] = [UnencodablePolicy::Error, UnencodablePolicy::Replace, UnencodablePolicy::Transliterate];
+
+    /// Returns the name used on the command line and in profiles, e.g. `replace`.
+    pub fn name(self) -> &'static str {
+        match self {
+            UnencodablePolicy::Error => "error",
+            UnencodablePolicy::Replace => "replace",
+            UnencodablePolicy::Transliterate => "transliterate",
+        }
+    }
+}
+
+impl fmt::Display for UnencodablePolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for UnencodablePolicy {
+    type Err = String;
+
+    /// Parses a policy name: `error`, `replace` or `transliterate`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "error" => Ok(UnencodablePolicy::Error),
+            "replace" => Ok(UnencodablePolicy::Replace),
+            "transliterate" | "translit" => Ok(UnencodablePolicy::Transliterate),
+            other => Err(format!("Unknown unencodable policy '{}'. Use error, replace or transliterate.", other)),
+        }
+    }
+}
+
+/// A cell that could not be written exactly in the output encoding.
+#[derive(Debug, Clone, Serialize)]
+pub struct UnencodableCell {
+    pub line: u64,       // Line number of the record in its source file (1-based)
+    pub column: String,  // Header of the column
+    pub value: String,   // The original cell content
+    pub written: String, // What was written instead
+}
+
+/// Prepares records for an output encoding, applying the unencodable policy and keeping
+/// track of the cells it changed.
+#[derive(Debug)]
+pub struct OutputEncoder {
+    encoding: Encoding,                  // Encoding of the output file
+    policy: UnencodablePolicy,           // What happens to characters it cannot represent
+    pub cells: Vec<UnencodableCell>,     // Cells written differently from their original content
+}
+
+impl OutputEncoder {
+    /// Creates an encoder for `encoding`.
+    pub fn new(encoding: Encoding, policy: UnencodablePolicy) -> Self {
+        Self { encoding, policy, cells: Vec::new() }
+    }
+
+    /// Makes a record writable in the output encoding.
+    ///
+    /// Records whose characters can all be represented are returned as they are. Other
+    /// cells are replaced or transliterated and listed in `cells`, or, with
+    /// `UnencodablePolicy::Error`, the first one is reported as an error.
+    ///
+    /// # Arguments
+    ///
+    /// * `record` - The record to write.
+    /// * `headers` - The header row, to name the columns of the affected cells.
+    /// * `line` - The line number of the record in its source file.
+    ///
+    /// # Returns
+    ///
+    /// The record to write, or an error describing the first cell that cannot be written.
+    pub fn prepare<'r>(&mut self, record: &'r StringRecord, headers: &StringRecord, line: u64) -> Result<Cow<'r, StringRecord>, String> {
+        if self.encoding.is_unicode() || record.iter().all(|field| field.chars().all(|c| can_encode(self.encoding, c))) {
+            return Ok(Cow::Borrowed(record));
+        }
+
+        let mut prepared = StringRecord::with_capacity(record.as_slice().len(), record.len());
+        for (i, field) in record.iter().enumerate() {
+            let Some(c) = field.chars().find(|&c| !can_encode(self.encoding, c)) else {
+                prepared.push_field(field);
+                continue;
+            };
+            let column = headers.get(i).map_or_else(|| format!("#{}", i + 1), |h| h.to_string());
+            if self.policy == UnencodablePolicy::Error {
+                return Err(format!("Cell '{}' in column '{}' at line {} contains '{}' (U+{:04X}), which cannot be written as {}",
+                    field, column, line, c, c as u32, self.encoding));
+            }
+            let written = self.substitute(field);
+            prepared.push_field(&written);
+            self.cells.push(UnencodableCell { line, column, value: field.to_string(), written });
+        }
+        Ok(Cow::Owned(prepared))
+    }
+
+    /// Replaces or transliterates the characters of a cell that cannot be written.
+    fn substitute(&self, field: &str) -> String {
+        let mut written = String::with_capacity(field.len());
+        for c in field.chars() {
+            if can_encode(self.encoding, c) {
+                written.push(c);
+                continue;
+            }
+            match transliterate(c) {
+                Some(ascii) if self.policy == UnencodablePolicy::Transliterate => written.push_str(ascii),
+                _ => written.push('?'),
+            }
+        }
+        written
+    }
+}
+
+/// A writer that encodes the UTF-8 text written to it in another encoding.
+///
+/// The byte-order mark of the encoding, if any, is written first. Characters that
+/// cannot be represented fail the write with `io::ErrorKind::InvalidData`, so records
+/// should go through an `OutputEncoder` first.
+pub struct EncodedWriter<W: Write> {
+    inner: W,             // Destination of the encoded bytes
+    encoding: Encoding,   // Encoding of the destination
+    pending: Vec<u8>,     // Start of a UTF-8 sequence split across writes
+    encoded: Vec<u8>,     // Buffer reused for each encoded chunk
+}
+
+impl<W: Write> EncodedWriter<W> {
+    /// Wraps `inner`, writing the byte-order mark of `encoding` to it.
+    pub fn new(mut inner: W, encoding: Encoding) -> io::Result<Self> {
+        if let Some(bom) = encoding.bom() {
+            inner.write_all(bom.bytes())?;
+        }
+        Ok(Self { inner, encoding, pending: Vec::new(), encoded: Vec::new() })
+    }
+}
+
+impl<W: Write> Write for EncodedWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.pending.extend_from_slice(buf);
+        // Only encode complete characters; keep a split sequence for the next write.
+        let complete = match std::str::from_utf8(&self.pending) {
+            Ok(text) => text.len(),
+            Err(e) if e.error_len().is_none() => e.valid_up_to(),
+            Err(e) => return Err(io::Error::new(io::ErrorKind::InvalidData, e)),
+        };
+        let text = std::str::from_utf8(&self.pending[..complete]).expect("checked above");
+        self.encoded.clear();
+        encode_into(self.encoding, text, &mut self.encoded).map_err(|c| {
+            io::Error::new(io::ErrorKind::InvalidData, format!("'{}' (U+{:04X}) cannot be written as {}", c, c as u32, self.encoding))
+        })?;
+        self.inner.write_all(&self.encoded)?;
+        self.pending.drain(..complete);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Returns `true` if `c` can be written in `encoding`.
+pub fn can_encode(encoding: Encoding, c: char) -> bool {
+    if c.is_ascii() || encoding.is_unicode() {
+        return true;
+    }
+    let mut utf8 = [0; 4];
+    encode_into(encoding, c.encode_utf8(&mut utf8), &mut Vec::new()).is_ok()
+}
+
+/// Appends `text` encoded in `encoding` to `out`, without a byte-order mark.
+///
+/// # Returns
+///
+/// `Ok(())`, or the first character that cannot be represented in `encoding` (what
+/// was encoded before it is left in `out`).
+pub fn encode_into(encoding: Encoding, text: &str, out: &mut Vec<u8>) -> Result<(), char> {
+    match encoding {
+        Encoding::Utf8 | Encoding::Utf8Bom => out.extend_from_slice(text.as_bytes()),
+        Encoding::Utf16Le => out.extend(text.encode_utf16().flat_map(u16::to_le_bytes)),
+        Encoding::Utf16Be => out.extend(text.encode_utf16().flat_map(u16::to_be_bytes)),
+        // Each code point up to U+00FF is the byte of the same value.
+        Encoding::Latin1 => {
+            for c in text.chars() {
+                out.push(u8::try_from(c).map_err(|_| c)?);
+            }
+        }
+        _ => {
+            let mut encoder = encoding.codec().new_encoder();
+            let mut rest = text;
+            loop {
+                out.reserve(rest.len() * 2 + 16);
+                let (result, read) = encoder.encode_from_utf8_to_vec_without_replacement(rest, out, true);
+                rest = &rest[read..];
+                match result {
+                    EncoderResult::InputEmpty => break,
+                    EncoderResult::OutputFull => continue,
+                    EncoderResult::Unmappable(c) => return Err(c),
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Returns an ASCII approximation of a character, for the `transliterate` policy.
+fn transliterate(c: char) -> Option<&'static str> {
+    let ascii = match c {
+        'À'..='Å' | 'Ā' | 'Ă' | 'Ą' => "A",
+        'à'..='å' | 'ā' | 'ă' | 'ą' => "a",
+        'Æ' => "AE",
+        'æ' => "ae",
+        'Ç' | 'Ć' | 'Ĉ' | 'Ċ' | 'Č' => "C",
+        'ç' | 'ć' | 'ĉ' | 'ċ' | 'č' => "c",
+        'Ð' | 'Ď' | 'Đ' => "D",
+        'ð' | 'ď' | 'đ' => "d",
+        'È'..='Ë' | 'Ē' | 'Ĕ' | 'Ė' | 'Ę' | 'Ě' => "E",
+        'è'..='ë' | 'ē' | 'ĕ' | 'ė' | 'ę' | 'ě' => "e",
+        'Ĝ' | 'Ğ' | 'Ġ' | 'Ģ' => "G",
+        'ĝ' | 'ğ' | 'ġ' | 'ģ' => "g",
+        'Ĥ' | 'Ħ' => "H",
+        'ĥ' | 'ħ' => "h",
+        'Ì'..='Ï' | 'Ĩ' | 'Ī' | 'Ĭ' | 'Į' | 'İ' => "I",
+        'ì'..='ï' | 'ĩ' | 'ī' | 'ĭ' | 'į' | 'ı' => "i",
+        'Ĵ' => "J",
+        'ĵ' => "j",
+        'Ķ' => "K",
+        'ķ' => "k",
+        'Ĺ' | 'Ļ' | 'Ľ' | 'Ŀ' | 'Ł' => "L",
+        'ĺ' | 'ļ' | 'ľ' | 'ŀ' | 'ł' => "l",
+        'Ñ' | 'Ń' | 'Ņ' | 'Ň' => "N",
+        'ñ' | 'ń' | 'ņ' | 'ň' => "n",
+        'Ò'..='Ö' | 'Ø' | 'Ō' | 'Ŏ' | 'Ő' => "O",
+        'ò'..='ö' | 'ø' | 'ō' | 'ŏ' | 'ő' => "o",
+        'Œ' => "OE",
+        'œ' => "oe",
+        'Ŕ' | 'Ŗ' | 'Ř' => "R",
+        'ŕ' | 'ŗ' | 'ř' => "r",
+        'Ś' | 'Ŝ' | 'Ş' | 'Š' | 'Ș' => "S",
+        'ś' | 'ŝ' | 'ş' | 'š' | 'ș' => "s",
+        'ß' => "ss",
+        'Ţ' | 'Ť' | 'Ŧ' | 'Ț' => "T",
+        'ţ' | 'ť' | 'ŧ' | 'ț' => "t",
+        'Þ' => "TH",
+        'þ' => "th",
+        'Ù'..='Ü' | 'Ũ' | 'Ū' | 'Ŭ' | 'Ů' | 'Ű' | 'Ų' => "U",
+        'ù'..='ü' | 'ũ' | 'ū' | 'ŭ' | 'ů' | 'ű' | 'ų' => "u",
+        'Ŵ' => "W",
+        'ŵ' => "w",
+        'Ý' | 'Ŷ' | 'Ÿ' => "Y",
+        'ý' | 'ÿ' | 'ŷ' => "y",
+        'Ź' | 'Ż' | 'Ž' => "Z",
+        'ź' | 'ż' | 'ž' => "z",
+        '‘' | '’' | '‚' | '‛' | '′' => "'",
+        '“' | '”' | '„' | '‟' | '″' => "\"",
+        '‐' | '‑' | '‒' | '–' | '—' | '―' | '−' => "-",
+        '‹' => "<",
+        '›' => ">",
+        '«' => "<<",
+        '»' => ">>",
+        '…' => "...",
+        '•' | '·' => "*",
+        '€' => "EUR",
+        '£' => "GBP",
+        '¥' => "JPY",
+        '©' => "(C)",
+        '®' => "(R)",
+        '™' => "(TM)",
+        '°' => "deg",
+        '×' => "x",
+        '÷' => "/",
+        '\u{a0}' | '\u{2000}'..='\u{200a}' | '\u{202f}' | '\u{3000}' => " ",
+        '\u{200b}' | '\u{feff}' => "",
+        _ => return None,
+    };
+    Some(ascii)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+    use crate::encoding::decode_reader;
+
+    fn encoded(encoding: Encoding, text: &str) -> Result<Vec<u8>, char> {
+        let mut out = Vec::new();
+        encode_into(encoding, text, &mut out).map(|_| out)
+    }
+
+    fn prepare(policy: UnencodablePolicy, fields: &[&str]) -> (Result<StringRecord, String>, Vec<UnencodableCell>) {
+        let mut encoder = OutputEncoder::new(Encoding::Latin1, policy);
+        let headers = StringRecord::from(vec!["id", "name", "price"]);
+        let prepared = encoder.prepare(&StringRecord::from(fields.to_vec()), &headers, 7).map(Cow::into_owned);
+        (prepared, encoder.cells)
+    }
+
+    #[test]
+    fn encodes_text_or_reports_the_first_unencodable_character() {
+        assert_eq!(encoded(Encoding::Utf8Bom, "café"), Ok("café".as_bytes().to_vec()));
+        assert_eq!(encoded(Encoding::Utf16Le, "é€"), Ok(b"\xe9\x00\xac\x20".to_vec()));
+        assert_eq!(encoded(Encoding::Utf16Be, "é€"), Ok(b"\x00\xe9\x20\xac".to_vec()));
+        assert_eq!(encoded(Encoding::Latin1, "café\u{85}"), Ok(b"caf\xe9\x85".to_vec()));
+        assert_eq!(encoded(Encoding::Latin1, "5 €"), Err('€'));
+        assert_eq!(encoded(Encoding::Windows1252, "5 €"), Ok(b"5 \x80".to_vec()));
+        assert_eq!(encoded(Encoding::Windows1252, "łódź"), Err('ł'));
+        assert_eq!(encoded(Encoding::ShiftJis, "日本"), Ok(b"\x93\xfa\x96\x7b".to_vec()));
+        assert!(can_encode(Encoding::Windows1250, 'ł'));
+        assert!(!can_encode(Encoding::Gbk, '\u{85}'));
+    }
+
+    #[test]
+    fn leaves_encodable_records_untouched() {
+        let mut encoder = OutputEncoder::new(Encoding::Latin1, UnencodablePolicy::Error);
+        let record = StringRecord::from(vec!["1", "café"]);
+        let prepared = encoder.prepare(&record, &StringRecord::new(), 2).unwrap();
+        assert!(matches!(prepared, Cow::Borrowed(_)));
+
+        let mut encoder = OutputEncoder::new(Encoding::Utf16Le, UnencodablePolicy::Error);
+        let record = StringRecord::from(vec!["1", "łódź €"]);
+        assert!(matches!(encoder.prepare(&record, &StringRecord::new(), 2).unwrap(), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn error_policy_reports_the_first_unencodable_cell() {
+        let (prepared, cells) = prepare(UnencodablePolicy::Error, &["1", "Łódź", "5 €"]);
+        assert_eq!(prepared.unwrap_err(), "Cell 'Łódź' in column 'name' at line 7 contains 'Ł' (U+0141), which cannot be written as ISO-8859-1");
+        assert!(cells.is_empty());
+    }
+
+    #[test]
+    fn replace_policy_writes_question_marks() {
+        let (prepared, cells) = prepare(UnencodablePolicy::Replace, &["1", "Łódź", "5 €", "extra ł"]);
+        assert_eq!(prepared.unwrap(), StringRecord::from(vec!["1", "?ód?", "5 ?", "extra ?"]));
+        let cells: Vec<_> = cells.iter().map(|c| (c.line, c.column.as_str(), c.value.as_str(), c.written.as_str())).collect();
+        assert_eq!(cells, [(7, "name", "Łódź", "?ód?"), (7, "price", "5 €", "5 ?"), (7, "#4", "extra ł", "extra ?")]);
+    }
+
+    #[test]
+    fn transliterate_policy_writes_ascii_approximations() {
+        let (prepared, cells) = prepare(UnencodablePolicy::Transliterate, &["1", "Łódź", "5 € ✓"]);
+        assert_eq!(prepared.unwrap(), StringRecord::from(vec!["1", "Lódz", "5 EUR ?"]));
+        assert_eq!(cells.len(), 2);
+    }
+
+    #[test]
+    fn writer_keeps_utf8_split_across_writes() {
+        let mut writer = EncodedWriter::new(Vec::new(), Encoding::Utf16Le).unwrap();
+        let text = "café €\n".as_bytes();
+        for byte in text {
+            assert_eq!(writer.write(std::slice::from_ref(byte)).unwrap(), 1);
+        }
+        assert!(writer.pending.is_empty());
+        let expected: Vec<u8> = b"\xff\xfe".iter().copied().chain("café €\n".encode_utf16().flat_map(u16::to_le_bytes)).collect();
+        assert_eq!(writer.inner, expected);
+
+        let mut writer = EncodedWriter::new(Vec::new(), Encoding::Latin1).unwrap();
+        writer.write_all(&text[..4]).unwrap();
+        assert_eq!(writer.inner, b"caf");
+        writer.write_all(&text[4..6]).unwrap();
+        assert_eq!(writer.inner, b"caf\xe9 ");
+        let error = writer.write_all(&text[6..]).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(error.to_string(), "'€' (U+20AC) cannot be written as ISO-8859-1");
+    }
+
+    #[test]
+    fn round_trips_through_each_output_encoding() {
+        for encoding in Encoding::ALL {
+            let text: String = "id,name\n1,".chars()
+                .chain("éłЖ日€\u{85}".chars().filter(|&c| can_encode(encoding, c)))
+                .chain("\n".chars())
+                .collect();
+            assert!(!text.is_ascii(), "{}", encoding);
+
+            let mut writer = EncodedWriter::new(Vec::new(), encoding).unwrap();
+            writer.write_all(text.as_bytes()).unwrap();
+            let mut decoded = String::new();
+            decode_reader(writer.inner.as_slice(), encoding).read_to_string(&mut decoded).unwrap();
+            assert_eq!(decoded, text, "{}", encoding);
+        }
+    }
+}